@@ -0,0 +1,50 @@
+use std::error::Error;
+
+use log::info;
+use nalgebra::Vector3;
+use rapier3d::prelude::SharedShape;
+
+use syrillian::components::{Collider3D, RigidBodyComponent};
+use syrillian::headless::HeadlessApp;
+use syrillian::world::World;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+
+    let mut app = HeadlessApp::create(60);
+    app.with_init(Some(init));
+
+    let world = app.run_until(
+        |world| {
+            world
+                .find_object_by_name("Crate")
+                .is_some_and(|obj| obj.transform.position().y < 0.0)
+        },
+        Some(600),
+    )?;
+
+    let crate_obj = world.find_object_by_name("Crate").unwrap();
+    info!("Crate ended up at {:?}", crate_obj.transform.position());
+
+    Ok(())
+}
+
+fn init(world: &mut World) -> Result<(), Box<dyn Error>> {
+    let mut ground = world.new_object("Ground");
+    ground
+        .add_component::<Collider3D>()
+        .get_collider_mut(world)
+        .unwrap()
+        .set_shape(SharedShape::cuboid(50.0, 0.5, 50.0));
+    ground.transform.set_position(Vector3::new(0.0, -5.0, 0.0));
+
+    let mut crate_obj = world.new_object("Crate");
+    crate_obj.transform.set_position(Vector3::new(0.0, 20.0, 0.0));
+    crate_obj.add_component::<Collider3D>();
+    crate_obj.add_component::<RigidBodyComponent>();
+
+    world.add_child(ground);
+    world.add_child(crate_obj);
+
+    Ok(())
+}
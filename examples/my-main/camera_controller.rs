@@ -23,10 +23,10 @@ impl Component for CameraController {
 		}
 	}
 
-	unsafe fn update(&mut self) {
+	unsafe fn update(&mut self, world: &mut World) {
 		let transform = &mut self.get_parent().transform;
 		
-		let input = &World::instance().input;
+		let input = &world.input;
 
 		let mouse_delta = input.get_mouse_delta(); 
 		self.yaw += mouse_delta.x * self.look_sensitivity / 30.0;
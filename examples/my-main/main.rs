@@ -8,12 +8,12 @@ use std::sync::Mutex;
 
 use env_logger::Env;
 use log::{error, LevelFilter};
-use nalgebra::Vector3;
+use nalgebra::{UnitQuaternion, Vector3};
 use rapier3d::prelude::*;
 use winit::window::Window;
 
 use syrillian::app::App;
-use syrillian::components::{Collider3D, RigidBodyComponent};
+use syrillian::components::{Collider3D, DirectionalLight, RigidBodyComponent};
 use syrillian::components::collider::MeshShapeExtra;
 use syrillian::drawables::mesh_renderer::MeshRenderer;
 use syrillian::scene_loader::SceneLoader;
@@ -63,12 +63,16 @@ fn funnyinit(world: &mut World, _window: &Window) -> Result<(), Box<dyn Error>>
             Some(renderer) => renderer,
         };
 
-        let collider = collider.get_collider_mut();
-        let shape = SharedShape::mesh(renderer.mesh()).unwrap();
-        collider.unwrap().set_shape(shape)
+        let shape = SharedShape::mesh(renderer.mesh(), world).unwrap();
+        collider.get_collider_mut(world).unwrap().set_shape(shape)
     }
 
     world.add_child(city);
+
+    let mut sun = world.new_object("Sun");
+    sun.transform.set_rotation(UnitQuaternion::from_euler_angles(-45f32.to_radians(), 30f32.to_radians(), 0.0));
+    sun.add_component::<DirectionalLight>();
+    world.add_child(sun);
     
     // Prepare camera
     let mut camera = world.new_camera();
@@ -81,7 +85,7 @@ fn funnyinit(world: &mut World, _window: &Window) -> Result<(), Box<dyn Error>>
          .set_position(Vector3::new(0.0, 100.0, 0.0));
     
     let collider = char_controller.add_component::<Collider3D>();
-    collider.get_collider_mut().unwrap().set_shape(SharedShape::capsule_y(1.0, 0.25));
+    collider.get_collider_mut(world).unwrap().set_shape(SharedShape::capsule_y(1.0, 0.25));
 
     let _rigid_body = char_controller.add_component::<RigidBodyComponent>();
     char_controller.add_component::<PlayerMovement>();
@@ -1,11 +1,9 @@
-use std::cell::RefCell;
-use std::rc::Rc;
 use log::warn;
 use nalgebra::Vector3;
 use num_traits::Zero;
 use rapier3d::prelude::{vector, LockedAxes};
 use winit::keyboard::KeyCode;
-use syrillian::components::{Component, RigidBodyComponent};
+use syrillian::components::{Component, ComponentRef, RigidBodyComponent};
 use syrillian::object::GameObjectId;
 use syrillian::world::World;
 
@@ -14,7 +12,7 @@ pub struct PlayerMovement {
 	move_speed: f32,
 	jump_factor: f32,
 	damping_factor: f32,
-	rigid_body: Option<Rc<RefCell<Box<RigidBodyComponent>>>>,
+	rigid_body: Option<ComponentRef<RigidBodyComponent>>,
 	velocity: Vector3<f32>,
 	sprint_multiplier: f32,
 }
@@ -35,12 +33,12 @@ impl Component for PlayerMovement {
 		}
 	}
 
-	unsafe fn init(&mut self) {
+	unsafe fn init(&mut self, world: &mut World) {
 		let rigid = self.get_parent().get_component::<RigidBodyComponent>();
 		if let Some(rigid) = rigid.clone() {
 			if let Some(rigid) = rigid
-				.borrow_mut()
-				.get_body_mut()	{
+				.borrow()
+				.get_body_mut(world)	{
 				rigid.set_locked_axes(LockedAxes::ROTATION_LOCKED_X | LockedAxes::ROTATION_LOCKED_Y | LockedAxes::ROTATION_LOCKED_Z, false);
 				rigid.enable_ccd(true);
 			}
@@ -48,30 +46,18 @@ impl Component for PlayerMovement {
 		self.rigid_body = rigid;
 	}
 
-	unsafe fn update(&mut self) {
-		let mut rigid = match &self.rigid_body {
+	unsafe fn update(&mut self, world: &mut World) {
+		let rigid = match &self.rigid_body {
 			None => {
 				warn!("Rigid body not set!");
 				return;
 			}
-			Some(rigid) => rigid.borrow_mut()
+			Some(rigid) => rigid.borrow()
 		};
 
-		let body = match rigid.get_body_mut() {
-			None => {
-				warn!("Rigid body not in set");
-				return;
-			}
-			Some(rigid) => rigid,
-		};
-
-		let world= World::instance();
-		
 		self.velocity /= self.damping_factor;
 
-		if world.input.is_key_down(KeyCode::Space) {
-			body.apply_impulse(vector![0.0, 0.2 * self.jump_factor, 0.0], true);
-		}
+		let jump = world.input.is_key_down(KeyCode::Space);
 
 		let mut factor = self.move_speed;
 
@@ -102,6 +88,17 @@ impl Component for PlayerMovement {
 		}
 		self.velocity += base_vel * factor;
 		
+		let body = match rigid.get_body_mut(world) {
+			None => {
+				warn!("Rigid body not in set");
+				return;
+			}
+			Some(rigid) => rigid,
+		};
+		if jump {
+			body.apply_impulse(vector![0.0, 0.2 * self.jump_factor, 0.0], true);
+		}
+
 		let mut linvel = body.linvel().clone();
 		linvel.x = self.velocity.x;
 		linvel.z = self.velocity.z;
@@ -5,14 +5,14 @@ use std::sync::Mutex;
 
 use env_logger::Env;
 use log::{error, LevelFilter};
-use nalgebra::Vector3;
+use nalgebra::{UnitQuaternion, Vector3};
 use winit::window::Window;
 
 use syrillian::app::App;
 use syrillian::asset_management::mesh::Mesh;
 use syrillian::buffer::{CUBE, CUBE_INDICES};
 use syrillian::components::freecam::FreecamController;
-use syrillian::components::RotateComponent;
+use syrillian::components::{DirectionalLight, RotateComponent};
 use syrillian::logichooks::LogicHooks;
 use syrillian::drawables::mesh_renderer::MeshRenderer;
 use syrillian::scene_loader::SceneLoader;
@@ -51,8 +51,14 @@ fn init(world: &mut World, _window: &Window) -> Result<(), Box<dyn Error>> {
     obj2.transform.set_uniform_scale(0.03);
     obj2.add_component::<RotateComponent>();
     obj1.add_child(obj2);
+
+    let mut sun = world.new_object("Sun");
+    sun.transform.set_rotation(UnitQuaternion::from_euler_angles(-45f32.to_radians(), 30f32.to_radians(), 0.0));
+    sun.add_component::<DirectionalLight>();
+
     world.add_child(obj1);
     world.add_child(camera);
+    world.add_child(sun);
 
     world.print_objects();
 
@@ -0,0 +1,47 @@
+use std::env;
+use std::error::Error;
+
+use log::info;
+use nalgebra::{UnitQuaternion, Vector3};
+
+use syrillian::components::DirectionalLight;
+use syrillian::renderer::Renderer;
+use syrillian::scene_loader::SceneLoader;
+use syrillian::world::World;
+
+// Renders a model without opening a window and saves the result as a PNG.
+// Usage: cargo run --example render_to_image -- [model path] [output path]
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+
+    let mut args = env::args().skip(1);
+    let model_path = args.next().unwrap_or("testmodels/simple_trans.fbx".to_string());
+    let output_path = args.next().unwrap_or("render.png".to_string());
+
+    let mut world = World::new();
+    let mut renderer = Renderer::new_headless(800, 600).await?;
+    renderer.init_for_world(&mut world);
+
+    let mut scene = SceneLoader::load(&mut world, &model_path)?;
+    scene.transform.set_uniform_scale(0.01);
+
+    let mut camera = world.new_camera();
+    camera.transform.set_position(Vector3::new(0.0, 1.0, 10.0));
+
+
+    let mut sun = world.new_object("Sun");
+    sun.transform.set_rotation(UnitQuaternion::from_euler_angles(-45f32.to_radians(), 30f32.to_radians(), 0.0));
+    sun.add_component::<DirectionalLight>();
+
+    world.add_child(scene);
+    world.add_child(camera);
+    world.add_child(sun);
+
+    let image = renderer.render_to_image(&mut world)?;
+    image.save(&output_path)?;
+
+    info!("Saved render of {model_path} to {output_path}");
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::error::Error;
 use nalgebra::{UnitQuaternion, Vector3};
 use winit::window::Window;
 use syrillian::app::App;
+use syrillian::components::DirectionalLight;
 use syrillian::scene_loader::SceneLoader;
 use syrillian::world::World;
 
@@ -22,8 +23,13 @@ fn init(world: &mut World, _window: &Window) -> Result<(), Box<dyn Error>> {
 
     let camera = world.new_camera();
 
+    let mut sun = world.new_object("Sun");
+    sun.transform.set_rotation(UnitQuaternion::from_euler_angles(-45f32.to_radians(), 30f32.to_radians(), 0.0));
+    sun.add_component::<DirectionalLight>();
+
     world.add_child(camera);
     world.add_child(scene);
+    world.add_child(sun);
 
     world.print_objects();
 
@@ -9,7 +9,6 @@ use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{WindowAttributes, WindowId};
 
-use crate::components::CameraComp;
 use crate::logichooks::{HookFunc, LogicHooks};
 use crate::renderer::Renderer;
 use crate::world::World;
@@ -19,12 +18,14 @@ pub struct PrematureApp {
     init_cb: Option<HookFunc>,
     update_cb: Option<HookFunc>,
     deinit_cb: Option<HookFunc>,
+    msaa_samples: u32,
 }
 
 pub struct App {
     renderer: Option<Renderer>,
     world: Box<World>,
     window_attributes: WindowAttributes,
+    msaa_samples: u32,
     pub hook_funcs: LogicHooks,
 }
 
@@ -41,6 +42,7 @@ impl Default for PrematureApp {
             init_cb: None,
             update_cb: None,
             deinit_cb: None,
+            msaa_samples: 1,
         }
     }
 }
@@ -56,6 +58,7 @@ impl App {
             init_cb: None,
             update_cb: None,
             deinit_cb: None,
+            msaa_samples: 1,
         }
     }
 
@@ -74,12 +77,13 @@ impl PrematureApp {
         };
         event_loop.set_control_flow(ControlFlow::Poll);
         
-        let world = unsafe { World::new() };
+        let world = World::new();
 
         let app = App {
             renderer: None,
             world,
             window_attributes: self.window_attributes.clone(),
+            msaa_samples: self.msaa_samples,
             hook_funcs: LogicHooks {
                 init: self.init_cb,
                 update: self.update_cb,
@@ -101,6 +105,11 @@ impl PrematureApp {
     pub fn with_deinit(&mut self, deinit: Option<HookFunc>) {
         self.deinit_cb = deinit;
     }
+
+    // MSAA samples of the scene (1, 2, 4 or 8), lowered to what the adapter supports
+    pub fn with_msaa_samples(&mut self, samples: u32) {
+        self.msaa_samples = samples;
+    }
     
     pub async fn run(mut self) -> Result<(), Box<dyn Error>> {
         let (event_loop, app) = self.init_state().await?;
@@ -128,14 +137,9 @@ impl ApplicationHandler for App {
             .create_window(self.window_attributes.clone())
             .unwrap();
 
-        self.world.assets.invalidate();
-
         let mut renderer = block_on(Renderer::new(window));
-        let state = &renderer.state;
-
-        self.world.assets.init_runtime(state.device.clone(), state.queue.clone());
-
-        renderer.init();
+        renderer.init_for_world(&mut self.world);
+        renderer.set_msaa_samples(self.msaa_samples, &mut self.world);
 
         self.renderer = Some(renderer);
 
@@ -185,17 +189,8 @@ impl ApplicationHandler for App {
                 ..
             } => event_loop.exit(),
             WindowEvent::Resized(size) => {
+                // the renderer keeps the aspect ratio of every camera in sync with its viewport
                 renderer.resize(size);
-
-                // For I have sinned, this now becomes my recovery.
-                // I was forgiven, shall it come haunt me later.
-                if let Some(cam) = world.active_camera {
-                    if let Some(cam_comp) = cam.get_component::<CameraComp>() {
-                        if let Ok(mut comp) = cam_comp.try_borrow_mut() {
-                            comp.resize(size.width as f32, size.height as f32);
-                        }
-                    }
-                }
             }
             _ => {}
         }
@@ -39,6 +39,9 @@ pub const CAMERA_UBGL_ID: BGLId = 0;
 pub const MODEL_UBGL_ID: BGLId = 1;
 pub const MATERIAL_UBGL_ID: BGLId = 2;
 pub const POST_PROCESS_BGL_ID: BGLId = 3;
+pub const LIGHT_UBGL_ID: BGLId = 4;
+// Parameters and an optional texture, like a color grading table, of a post-processing effect
+pub const POST_EFFECT_BGL_ID: BGLId = 5;
 
 impl BindGroupLayoutManager {
     pub fn new() -> Self {
@@ -62,12 +65,13 @@ impl BindGroupLayoutManager {
         );
         assert_eq!(id, CAMERA_UBGL_ID);
 
-        let id = manager.add_bind_group_layout(Some("Model Uniform Bind Group Layout"), vec![
+        // the model data of every drawable in the frame, indexed by the instance index
+        let id = manager.add_bind_group_layout(Some("Model Storage Bind Group Layout"), vec![
             BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::VERTEX_FRAGMENT,
                 ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
+                    ty: BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
                     min_binding_size: None,
                 },
@@ -76,7 +80,9 @@ impl BindGroupLayoutManager {
         );
         assert_eq!(id, MODEL_UBGL_ID);
 
-        let id = manager.add_bind_group_layout(Some("Material Uniform Bind Group Layout"), vec![
+        // uniform data, followed by a texture and sampler each for the diffuse, normal, shininess,
+        // metallic, roughness, occlusion and emissive texture
+        let mut material_entries = vec![
             BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::FRAGMENT,
@@ -87,8 +93,31 @@ impl BindGroupLayoutManager {
                 },
                 count: None,
             },
+        ];
+        for binding in (1..15).step_by(2) {
+            material_entries.push(BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            });
+            material_entries.push(BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                count: None,
+            });
+        }
+        let id = manager.add_bind_group_layout(Some("Material Uniform Bind Group Layout"), material_entries);
+        assert_eq!(id, MATERIAL_UBGL_ID);
+
+        let id = manager.add_bind_group_layout(Some("Post-Processing Bind Group Layout"), vec![
             BindGroupLayoutEntry {
-                binding: 1,
+                binding: 0,
                 visibility: ShaderStages::FRAGMENT,
                 ty: BindingType::Texture {
                     sample_type: TextureSampleType::Float { filterable: true },
@@ -97,50 +126,95 @@ impl BindGroupLayoutManager {
                 },
                 count: None,
             },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]);
+        assert_eq!(id, POST_PROCESS_BGL_ID);
+
+        let id = manager.add_bind_group_layout(Some("Light Uniform Bind Group Layout"), vec![
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
             BindGroupLayoutEntry {
                 binding: 2,
                 visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                ty: BindingType::Sampler(SamplerBindingType::Comparison),
                 count: None,
             },
+            // the sky, for ambient light and reflections
             BindGroupLayoutEntry {
                 binding: 3,
                 visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStages::FRAGMENT,
                 ty: BindingType::Texture {
                     sample_type: TextureSampleType::Float { filterable: true },
-                    view_dimension: TextureViewDimension::D2,
+                    view_dimension: TextureViewDimension::Cube,
                     multisampled: false,
                 },
                 count: None,
             },
             BindGroupLayoutEntry {
-                binding: 4,
+                binding: 5,
                 visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
+                ty: BindingType::Sampler(SamplerBindingType::Filtering),
                 count: None,
             },
         ]);
-        assert_eq!(id, MATERIAL_UBGL_ID);
+        assert_eq!(id, LIGHT_UBGL_ID);
 
-        let id = manager.add_bind_group_layout(Some("Post-Processing Bind Group Layout"), vec![
+        let id = manager.add_bind_group_layout(Some("Post Effect Bind Group Layout"), vec![
             BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Texture {
-                    sample_type: TextureSampleType::Float { filterable: true },
-                    view_dimension: TextureViewDimension::D2,
-                    multisampled: false,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
                 count: None,
             },
             BindGroupLayoutEntry {
                 binding: 1,
                 visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
                 count: None,
             },
         ]);
-        assert_eq!(id, POST_PROCESS_BGL_ID);
+        assert_eq!(id, POST_EFFECT_BGL_ID);
 
         manager
     }
@@ -0,0 +1,181 @@
+use std::f32::consts::PI;
+
+use image::DynamicImage;
+use nalgebra::Vector3;
+
+// Cube faces in the order GPUs expect them as texture layers: +X, -X, +Y, -Y, +Z, -Z
+pub(crate) const CUBE_FACES: usize = 6;
+
+// Linear colors of the six faces of a cube, each face `size` x `size` texels big
+pub(crate) struct CubeFaces {
+    pub(crate) size: u32,
+    pub(crate) faces: Vec<Vec<[f32; 4]>>,
+}
+
+impl CubeFaces {
+    // Takes the faces from six square images
+    pub(crate) fn from_images(images: &[DynamicImage]) -> Result<Self, String> {
+        let size = images[0].width();
+        if images.iter().any(|img| img.width() != size || img.height() != size) {
+            return Err("All faces of a cube texture have to be square and equally big".to_string());
+        }
+
+        let faces = images.iter().map(linear_texels).collect();
+        Ok(CubeFaces { size, faces })
+    }
+
+    // Projects an equirectangular (latitude-longitude) panorama onto the six faces
+    pub(crate) fn from_equirectangular(image: &DynamicImage, size: u32) -> Self {
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+        let texels = linear_texels(image);
+
+        let sample = |x: f32, y: f32| -> [f32; 4] {
+            let x0 = (x.floor() as isize).rem_euclid(width as isize) as usize;
+            let x1 = (x0 + 1) % width;
+            let y0 = (y.floor().max(0.0) as usize).min(height - 1);
+            let y1 = (y0 + 1).min(height - 1);
+            let fx = x - x.floor();
+            let fy = (y - y.floor()).clamp(0.0, 1.0);
+
+            let mut out = [0.0; 4];
+            for (c, channel) in out.iter_mut().enumerate() {
+                let top = texels[y0 * width + x0][c] * (1.0 - fx) + texels[y0 * width + x1][c] * fx;
+                let bottom = texels[y1 * width + x0][c] * (1.0 - fx) + texels[y1 * width + x1][c] * fx;
+                *channel = top * (1.0 - fy) + bottom * fy;
+            }
+            out
+        };
+
+        let faces = (0..CUBE_FACES)
+            .map(|face| {
+                let mut texels = Vec::with_capacity((size * size) as usize);
+                for y in 0..size {
+                    for x in 0..size {
+                        let dir = face_direction(face, x, y, size);
+                        // longitude 0 looks down -Z, which is forward for cameras
+                        let u = 0.5 + dir.x.atan2(-dir.z) / (2.0 * PI);
+                        let v = 0.5 - dir.y.clamp(-1.0, 1.0).asin() / PI;
+                        texels.push(sample(u * width as f32 - 0.5, v * height as f32 - 0.5));
+                    }
+                }
+                texels
+            })
+            .collect();
+
+        CubeFaces { size, faces }
+    }
+
+    pub(crate) fn mip_levels(&self) -> u32 {
+        self.size.max(1).ilog2() + 1
+    }
+
+    // Encodes the faces with all their mip levels as Rgba16Float, face by face
+    pub(crate) fn to_rgba16f_with_mips(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for face in &self.faces {
+            let mut level = face.clone();
+            let mut size = self.size as usize;
+            loop {
+                for texel in &level {
+                    for channel in texel {
+                        data.extend_from_slice(&f32_to_f16(*channel).to_le_bytes());
+                    }
+                }
+                if size <= 1 {
+                    break;
+                }
+                (level, size) = downsample(&level, size);
+            }
+        }
+        data
+    }
+}
+
+// The direction the center of texel (x, y) of a face points to
+fn face_direction(face: usize, x: u32, y: u32, size: u32) -> Vector3<f32> {
+    let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    let dir = match face {
+        0 => Vector3::new(1.0, -v, -u),
+        1 => Vector3::new(-1.0, -v, u),
+        2 => Vector3::new(u, 1.0, v),
+        3 => Vector3::new(u, -1.0, -v),
+        4 => Vector3::new(u, -v, 1.0),
+        _ => Vector3::new(-u, -v, -1.0),
+    };
+    dir.normalize()
+}
+
+// HDR images are already linear, everything else is taken as sRGB
+fn linear_texels(image: &DynamicImage) -> Vec<[f32; 4]> {
+    let is_hdr = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+    image
+        .to_rgba32f()
+        .pixels()
+        .map(|p| {
+            if is_hdr {
+                p.0
+            } else {
+                [srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2]), p[3]]
+            }
+        })
+        .collect()
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Averages 2x2 texel blocks into the next smaller mip level
+fn downsample(texels: &[[f32; 4]], size: usize) -> (Vec<[f32; 4]>, usize) {
+    let half = (size / 2).max(1);
+    let mut out = Vec::with_capacity(half * half);
+    for y in 0..half {
+        for x in 0..half {
+            let mut sum = [0.0; 4];
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let sx = (x * 2 + dx).min(size - 1);
+                let sy = (y * 2 + dy).min(size - 1);
+                for (c, channel) in sum.iter_mut().enumerate() {
+                    *channel += texels[sy * size + sx][c] * 0.25;
+                }
+            }
+            out.push(sum);
+        }
+    }
+    (out, half)
+}
+
+// Rounds to the nearest half precision float. Values too big for it become the biggest one.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.clamp(-65504.0, 65504.0).to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // only NaN gets here, as everything else was clamped
+        return sign | 0x7e00;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent <= 0 {
+        // subnormal, or too small to be represented at all
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + round) as u16;
+    }
+
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
+}
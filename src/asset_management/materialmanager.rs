@@ -8,13 +8,13 @@ use wgpu::{
     BufferUsages, Device, Queue,
 };
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
-use crate::asset_management::bindgroup_layout_manager::MATERIAL_UBGL_ID;
+use crate::asset_management::bindgroup_layout_manager::{BindGroupLayoutManager, MATERIAL_UBGL_ID};
 use crate::asset_management::shadermanager;
 use crate::asset_management::shadermanager::ShaderId;
 use crate::asset_management::texturemanager::{
     FALLBACK_DIFFUSE_TEXTURE, FALLBACK_NORMAL_TEXTURE, FALLBACK_SHININESS_TEXTURE, TextureId,
 };
-use crate::world::World;
+use crate::asset_management::TextureManager;
 
 pub type MaterialId = usize;
 
@@ -26,11 +26,71 @@ pub struct Material {
     pub diffuse_texture: Option<TextureId>,
     pub normal_texture: Option<TextureId>,
     pub shininess: f32,
+    // Scales the specular highlight per texel, read from the red channel
     pub shininess_texture: Option<TextureId>,
     pub opacity: f32,
+    pub blend_mode: BlendMode,
+    // Light given off by the surface itself, independent of any lights in the world
+    pub emissive: Vector3<f32>,
+    pub emissive_texture: Option<TextureId>,
+    // Shades the material with the metallic-roughness model instead of Blinn-Phong.
+    // The diffuse color and texture are used as the base color then.
+    pub pbr: Option<PbrMaterial>,
     pub shader: ShaderId,
 }
 
+// How the material's alpha, the texture alpha multiplied with the opacity, is used
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum BlendMode {
+    // Fully covers everything behind it
+    #[default]
+    Opaque,
+    // Cuts out every fragment with an alpha below the cutoff, like foliage or fences
+    AlphaTest { cutoff: f32 },
+    // Mixed with what's behind it by alpha, like glass
+    AlphaBlend,
+    // Added on top of what's behind it, like fire or other glowing particles
+    Additive,
+}
+
+impl BlendMode {
+    // Blended materials don't write depth and are drawn after everything opaque, back to front
+    pub fn is_blended(&self) -> bool {
+        matches!(self, BlendMode::AlphaBlend | BlendMode::Additive)
+    }
+
+    fn alpha_cutoff(&self) -> f32 {
+        match self {
+            BlendMode::AlphaTest { cutoff } => *cutoff,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PbrMaterial {
+    pub metallic: f32,
+    pub roughness: f32,
+    // Textures are multiplied with the factors above. Metallic is read from the blue and roughness
+    // from the green channel like glTF packs them, so both can share one texture.
+    pub metallic_texture: Option<TextureId>,
+    pub roughness_texture: Option<TextureId>,
+    // Ambient occlusion, read from the red channel
+    pub occlusion_texture: Option<TextureId>,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        PbrMaterial {
+            metallic: 0.0,
+            roughness: 0.5,
+            metallic_texture: None,
+            roughness_texture: None,
+            occlusion_texture: None,
+        }
+    }
+}
+
 pub struct MaterialItem {
     raw: Material,
     runtime: Option<RuntimeMaterial>,
@@ -39,17 +99,29 @@ pub struct MaterialItem {
 impl Material {
     pub(crate) fn init_runtime(
         &self,
-        world: &mut World,
+        textures: &mut TextureManager,
+        bgls: &BindGroupLayoutManager,
         device: &Device,
         _queue: &Queue,
     ) -> RuntimeMaterial {
+        let pbr = self.pbr.unwrap_or_default();
         let data = RuntimeMaterialData {
             diffuse: self.diffuse,
-            _padding1: 0,
+            use_shininess_texture: self.shininess_texture.is_some() as u32,
             use_diffuse_texture: self.diffuse_texture.is_some() as u32,
             use_normal_texture: self.normal_texture.is_some() as u32,
             shininess: self.shininess,
             opacity: self.opacity,
+            emissive: self.emissive,
+            use_emissive_texture: self.emissive_texture.is_some() as u32,
+            use_pbr: self.pbr.is_some() as u32,
+            metallic: pbr.metallic,
+            roughness: pbr.roughness,
+            use_metallic_texture: pbr.metallic_texture.is_some() as u32,
+            use_roughness_texture: pbr.roughness_texture.is_some() as u32,
+            use_occlusion_texture: pbr.occlusion_texture.is_some() as u32,
+            alpha_cutoff: self.blend_mode.alpha_cutoff(),
+            _padding: 0,
         };
 
         let material_buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -58,64 +130,52 @@ impl Material {
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
-        let world: *mut World = world;
-        unsafe {
-            let diffuse_texture_id = self.diffuse_texture.unwrap_or(FALLBACK_DIFFUSE_TEXTURE);
-            let diffuse_texture = (*world)
-                .assets
-                .textures
-                .get_runtime_texture_ensure_init(diffuse_texture_id)
-                .unwrap();
-
-            let normal_texture_id = self.diffuse_texture.unwrap_or(FALLBACK_NORMAL_TEXTURE);
-            let normal_texture = (*world)
-                .assets
-                .textures
-                .get_runtime_texture_ensure_init(normal_texture_id)
-                .unwrap();
-
-            let shininess_texture_id = self.diffuse_texture.unwrap_or(FALLBACK_SHININESS_TEXTURE);
-            // TODO: Implement shininess texture into bind group
-            let _shininess_texture = (*world)
-                .assets
-                .textures
-                .get_runtime_texture_ensure_init(shininess_texture_id)
-                .unwrap();
-
-            let mat_bgl = (&mut *world).assets.bind_group_layouts.get_bind_group_layout(MATERIAL_UBGL_ID).unwrap();
-
-            let bind_group = device.create_bind_group(&BindGroupDescriptor {
-                label: Some("Material Bind Group"),
-                layout: mat_bgl,
-                entries: &[
-                    BindGroupEntry {
-                        binding: 0,
-                        resource: material_buffer.as_entire_binding(),
-                    },
-                    BindGroupEntry {
-                        binding: 1,
-                        resource: BindingResource::TextureView(&diffuse_texture.view),
-                    },
-                    BindGroupEntry {
-                        binding: 2,
-                        resource: BindingResource::Sampler(&diffuse_texture.sampler),
-                    },
-                    BindGroupEntry {
-                        binding: 3,
-                        resource: BindingResource::TextureView(&normal_texture.view),
-                    },
-                    BindGroupEntry {
-                        binding: 4,
-                        resource: BindingResource::Sampler(&normal_texture.sampler),
-                    },
-                ],
+        // Ordered like the texture bindings of the material bind group layout. Unused slots get a
+        // fallback, which is never sampled as the shader checks the use_*_texture flags first.
+        let texture_ids = [
+            self.diffuse_texture.unwrap_or(FALLBACK_DIFFUSE_TEXTURE),
+            self.normal_texture.unwrap_or(FALLBACK_NORMAL_TEXTURE),
+            self.shininess_texture.unwrap_or(FALLBACK_SHININESS_TEXTURE),
+            pbr.metallic_texture.unwrap_or(FALLBACK_SHININESS_TEXTURE),
+            pbr.roughness_texture.unwrap_or(FALLBACK_SHININESS_TEXTURE),
+            pbr.occlusion_texture.unwrap_or(FALLBACK_SHININESS_TEXTURE),
+            self.emissive_texture.unwrap_or(FALLBACK_SHININESS_TEXTURE),
+        ];
+        for id in texture_ids {
+            textures.get_runtime_texture_ensure_init(id);
+        }
+
+        let mut entries = vec![BindGroupEntry {
+            binding: 0,
+            resource: material_buffer.as_entire_binding(),
+        }];
+        for (i, id) in texture_ids.iter().enumerate() {
+            let texture = textures.get_runtime_texture(*id).unwrap();
+            let binding = 1 + i as u32 * 2;
+            entries.push(BindGroupEntry {
+                binding,
+                resource: BindingResource::TextureView(&texture.view),
+            });
+            entries.push(BindGroupEntry {
+                binding: binding + 1,
+                resource: BindingResource::Sampler(&texture.sampler),
             });
+        }
+
+        let mat_bgl = bgls.get_bind_group_layout(MATERIAL_UBGL_ID).unwrap();
 
-            RuntimeMaterial {
-                data,
-                buffer: material_buffer,
-                bind_group,
-            }
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Material Bind Group"),
+            layout: mat_bgl,
+            entries: &entries,
+        });
+
+        RuntimeMaterial {
+            shader: self.shader,
+            blend_mode: self.blend_mode,
+            data,
+            buffer: material_buffer,
+            bind_group,
         }
     }
 }
@@ -124,11 +184,21 @@ impl Material {
 #[derive(Copy, Clone)]
 pub struct RuntimeMaterialData {
     diffuse: Vector3<f32>,
-    _padding1: u32,
+    use_shininess_texture: u32,
     use_diffuse_texture: u32,
     use_normal_texture: u32,
     shininess: f32,
     opacity: f32,
+    emissive: Vector3<f32>,
+    use_emissive_texture: u32,
+    use_pbr: u32,
+    metallic: f32,
+    roughness: f32,
+    use_metallic_texture: u32,
+    use_roughness_texture: u32,
+    use_occlusion_texture: u32,
+    alpha_cutoff: f32,
+    _padding: u32,
 }
 
 unsafe impl Zeroable for RuntimeMaterialData {}
@@ -136,6 +206,8 @@ unsafe impl Pod for RuntimeMaterialData {}
 
 #[allow(dead_code)]
 pub struct RuntimeMaterial {
+    pub(crate) shader: ShaderId,
+    pub(crate) blend_mode: BlendMode,
     pub(crate) data: RuntimeMaterialData,
     pub(crate) buffer: Buffer,
     pub(crate) bind_group: BindGroup,
@@ -164,9 +236,14 @@ impl MaterialManager {
             diffuse_texture: None,
             normal_texture: None,
             shininess: 0.0,
-            shader: shadermanager::FALLBACK_SHADER_ID,
+            // plain white, the fallback shader is only meant for shaders that fail to compile
+            shader: shadermanager::DIM3_SHADER_ID,
             opacity: 1.0,
+            blend_mode: BlendMode::Opaque,
             shininess_texture: None,
+            emissive: Vector3::zeros(),
+            emissive_texture: None,
+            pbr: None,
         };
         let mut manager = MaterialManager {
             materials: HashMap::new(),
@@ -218,53 +295,68 @@ impl MaterialManager {
         self.materials.get(&id).map(|m| &m.raw)
     }
 
-    pub fn get_runtime_material(&mut self, id: MaterialId) -> Option<&RuntimeMaterial> {
-        let mat = self.materials.get_mut(&id)?;
-
-        if mat.runtime.is_none() {
-            mat.runtime = Some(mat.raw.init_runtime(
-                World::instance(),
-                self.device.as_ref()?.as_ref(),
-                self.queue.as_ref()?.as_ref(),
-            ));
-        }
-        mat.runtime.as_ref()
+    pub fn get_runtime_material(
+        &mut self,
+        id: MaterialId,
+        textures: &mut TextureManager,
+        bgls: &BindGroupLayoutManager,
+    ) -> Option<&RuntimeMaterial> {
+        self.get_runtime_material_mut(id, textures, bgls).map(|mat| &*mat)
     }
 
-    pub fn get_runtime_material_mut(&mut self, id: MaterialId) -> Option<&mut RuntimeMaterial> {
+    pub fn get_runtime_material_mut(
+        &mut self,
+        id: MaterialId,
+        textures: &mut TextureManager,
+        bgls: &BindGroupLayoutManager,
+    ) -> Option<&mut RuntimeMaterial> {
         let mat = self.materials.get_mut(&id)?;
         if mat.runtime.is_none() {
             mat.runtime = Some(mat.raw.init_runtime(
-                World::instance(),
+                textures,
+                bgls,
                 self.device.as_ref()?.as_ref(),
                 self.queue.as_ref()?.as_ref(),
             ));
         }
         mat.runtime.as_mut()
     }
+
     fn init_runtime_material_internal(
-        world: &mut World,
         material: &mut MaterialItem,
+        textures: &mut TextureManager,
+        bgls: &BindGroupLayoutManager,
         device: &Device,
         queue: &Queue,
     ) -> Result<(), MaterialError> {
         material.runtime = Some(material.raw.init_runtime(
-            world,
+            textures,
+            bgls,
             device,
             queue,
         ));
         Ok(())
     }
 
-    pub fn init_runtime_material(&self, world: &mut World, material: &mut MaterialItem) -> Result<(), MaterialError> {
+    pub fn init_runtime_material(
+        &self,
+        material: &mut MaterialItem,
+        textures: &mut TextureManager,
+        bgls: &BindGroupLayoutManager,
+    ) -> Result<(), MaterialError> {
         let device = self.device.as_ref()
             .ok_or(MaterialError::DeviceNotInitialized)?;
         let queue = self.queue.as_ref()
             .ok_or(MaterialError::QueueNotInitialized)?;
-        Self::init_runtime_material_internal(world, material, device, queue)
+        Self::init_runtime_material_internal(material, textures, bgls, device, queue)
     }
     
-    pub fn init_runtime_material_id(&mut self, world: &mut World, id: MaterialId) -> Result<(), MaterialError> {
+    pub fn init_runtime_material_id(
+        &mut self,
+        id: MaterialId,
+        textures: &mut TextureManager,
+        bgls: &BindGroupLayoutManager,
+    ) -> Result<(), MaterialError> {
         let material = self.materials.get_mut(&id)
             .ok_or(MaterialError::MaterialNotFound)?;
         let device = self.device.as_ref()
@@ -272,6 +364,6 @@ impl MaterialManager {
         let queue = self.queue.as_ref()
             .ok_or(MaterialError::QueueNotInitialized)?;
 
-        Self::init_runtime_material_internal(world, material, device, queue)
+        Self::init_runtime_material_internal(material, textures, bgls, device, queue)
     }
 }
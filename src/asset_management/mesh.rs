@@ -2,11 +2,11 @@ use std::ops::Range;
 
 use bytemuck::{Pod, Zeroable};
 use nalgebra::{Point, Vector2, Vector3, Vector4};
-use wgpu::{BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BufferAddress, BufferUsages, Device, VertexAttribute, VertexFormat};
+use wgpu::{BufferAddress, BufferUsages, Device, VertexAttribute, VertexFormat};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
 use crate::asset_management::materialmanager::{FALLBACK_MATERIAL_ID, MaterialId};
-use crate::object::ModelData;
+use crate::utils::bounds::Bounds;
 
 #[derive(Copy, Clone)]
 pub struct SimpleVertex3D {
@@ -45,9 +45,6 @@ pub struct RuntimeMeshData {
     pub(crate) vertices_num: usize,
     pub(crate) indices_buf: Option<wgpu::Buffer>,
     pub(crate) indices_num: usize,
-    pub(crate) model_data: ModelData,
-    pub(crate) model_data_buffer: wgpu::Buffer,
-    pub(crate) model_bind_group: wgpu::BindGroup,
 }
 
 pub struct MeshVertexData<T> {
@@ -59,6 +56,8 @@ pub struct Mesh {                    //         |
     //         here <---------------------------- i forgor why tho :<
     pub(crate) data: MeshVertexData<Vertex3D>,
     pub material_ranges: Vec<(MaterialId, Range<u32>)>,
+    // in the mesh's own space, computed when it's created
+    pub(crate) bounds: Bounds,
 }
 
 pub struct RuntimeMesh {
@@ -81,17 +80,20 @@ impl Mesh {
             }
         }
 
+        let bounds = Bounds::from_points(vertices.iter().map(|v| v.position));
+
         Box::new(Mesh {
             data: MeshVertexData::<Vertex3D> { vertices, indices },
             material_ranges,
+            bounds,
         })
     }
 
-    pub(crate) fn init_runtime(
-        &mut self,
-        device: &Device,
-        model_bind_group_layout: &BindGroupLayout,
-    ) -> RuntimeMesh {
+    pub fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+
+    pub(crate) fn init_runtime(&mut self, device: &Device) -> RuntimeMesh {
         let v_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("3D Object Vertex Buffer"),
             contents: bytemuck::cast_slice(self.data.vertices.as_slice()),
@@ -104,20 +106,6 @@ impl Mesh {
                 usage: BufferUsages::INDEX,
             })
         });
-        let model_data = ModelData::empty();
-        let model_data_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Model Buffer"),
-            contents: bytemuck::cast_slice(&[model_data]),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-        });
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Model Bind Group"),
-            layout: model_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: model_data_buffer.as_entire_binding(),
-            }],
-        });
         let runtime_mesh_data = RuntimeMeshData {
             vertices_buf: v_buffer,
             vertices_num: self.data.vertices.len(),
@@ -128,9 +116,6 @@ impl Mesh {
                 .as_ref()
                 .map(|i| i.len())
                 .unwrap_or_default(),
-            model_data,
-            model_data_buffer,
-            model_bind_group: bind_group,
         };
         RuntimeMesh {
             data: runtime_mesh_data,
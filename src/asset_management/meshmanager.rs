@@ -2,19 +2,19 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use wgpu::Device;
-use crate::asset_management::bindgroup_layout_manager::MODEL_UBGL_ID;
 use crate::asset_management::mesh::{Mesh, RuntimeMesh};
-use crate::world::World;
 
 pub type MeshId = usize;
 
 pub struct MeshItem {
     raw: Box<Mesh>,
     runtime: Option<RuntimeMesh>,
+    path: Option<String>,
 }
 
 pub struct MeshManager {
     meshes: HashMap<MeshId, MeshItem>,
+    paths: HashMap<String, MeshId>,
     next_id: MeshId,
     device: Option<Rc<Device>>,
 }
@@ -24,6 +24,7 @@ impl MeshManager {
     pub(crate) fn new() -> MeshManager {
         MeshManager {
             meshes: HashMap::new(),
+            paths: HashMap::new(),
             next_id: 0,
             device: None,
         }
@@ -48,6 +49,7 @@ impl MeshManager {
             MeshItem {
                 raw: mesh,
                 runtime: None,
+                path: None,
             },
         );
         self.next_id += 1;
@@ -55,6 +57,25 @@ impl MeshManager {
         id
     }
 
+    // Names the mesh with the asset path it came from, so scenes can refer to it
+    pub fn set_mesh_path(&mut self, id: MeshId, path: &str) {
+        let Some(mesh) = self.meshes.get_mut(&id) else {
+            return;
+        };
+        if let Some(old_path) = mesh.path.replace(path.to_owned()) {
+            self.paths.remove(&old_path);
+        }
+        self.paths.insert(path.to_owned(), id);
+    }
+
+    pub fn get_mesh_path(&self, id: MeshId) -> Option<&str> {
+        self.meshes.get(&id)?.path.as_deref()
+    }
+
+    pub fn find_mesh_by_path(&self, path: &str) -> Option<MeshId> {
+        self.paths.get(path).copied()
+    }
+
     pub fn get_mesh_internal_mut(&mut self, id: MeshId) -> Option<&mut MeshItem> {
         self.meshes.get_mut(&id)
     }
@@ -68,7 +89,7 @@ impl MeshManager {
     }
 
     pub fn get_runtime_mesh_mut(&mut self, id: MeshId) -> Option<&mut RuntimeMesh> {
-        self.get_runtime_mesh_or_init_mut(id)
+        self.meshes.get_mut(&id).map(|m| m.runtime.as_mut())?
     }
 
     pub fn init_runtime_mesh(&mut self, id: MeshId) {
@@ -76,18 +97,7 @@ impl MeshManager {
     }
 
     pub fn get_runtime_mesh_or_init(&mut self, id: MeshId) -> Option<&RuntimeMesh> {
-        let mesh = self.meshes.get_mut(&id)?;
-        if mesh.runtime.is_some() {
-            return mesh.runtime.as_ref();
-        }
-
-        let model_bgl = World::instance().assets.bind_group_layouts.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
-        let runtime_mesh = mesh.raw.as_mut().init_runtime(
-            self.device.as_ref().unwrap(),
-            model_bgl
-        );
-        mesh.runtime = Some(runtime_mesh);
-        mesh.runtime.as_ref()
+        self.get_runtime_mesh_or_init_mut(id).map(|mesh| &*mesh)
     }
 
     pub fn get_runtime_mesh_or_init_mut(&mut self, id: MeshId) -> Option<&mut RuntimeMesh> {
@@ -96,11 +106,7 @@ impl MeshManager {
             return mesh.runtime.as_mut();
         }
 
-        let model_bgl = World::instance().assets.bind_group_layouts.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
-        let runtime_mesh = mesh.raw.as_mut().init_runtime(
-            self.device.as_ref().unwrap(),
-            model_bgl,
-        );
+        let runtime_mesh = mesh.raw.as_mut().init_runtime(self.device.as_ref().unwrap());
         mesh.runtime = Some(runtime_mesh);
         mesh.runtime.as_mut()
     }
@@ -3,6 +3,7 @@ pub use materialmanager::MaterialManager;
 pub use texturemanager::TextureManager;
 
 pub mod assetmanager;
+pub mod cubemap;
 pub mod materialmanager;
 pub mod mesh;
 pub mod meshmanager;
@@ -4,11 +4,14 @@ use std::error::Error;
 use std::fs;
 use std::path::Path;
 use std::rc::Rc;
+use futures::executor::block_on;
+use log::error;
 use wgpu::*;
 
-use crate::asset_management::bindgroup_layout_manager::{CAMERA_UBGL_ID, MATERIAL_UBGL_ID, MODEL_UBGL_ID, POST_PROCESS_BGL_ID};
+use crate::asset_management::bindgroup_layout_manager::{BindGroupLayoutManager, CAMERA_UBGL_ID, LIGHT_UBGL_ID, MATERIAL_UBGL_ID, MODEL_UBGL_ID, POST_EFFECT_BGL_ID, POST_PROCESS_BGL_ID};
+use crate::asset_management::materialmanager::BlendMode;
 use crate::asset_management::mesh::Vertex3D;
-use crate::world::World;
+use crate::renderer::HDR_FORMAT;
 
 pub struct ShaderItem {
     raw: Shader,
@@ -18,6 +21,28 @@ pub struct ShaderItem {
 pub struct Shader {
     pub name: String,
     pub code: String,
+    pub kind: ShaderKind,
+}
+
+// Decides which bind groups a shader is compiled against
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShaderKind {
+    // Draws meshes like the default 3D shader
+    Combined,
+    // A post-processing effect, drawn over the whole screen
+    PostProcess,
+    // A post-processing pass on the HDR image before tone mapping, like the bloom passes. Also
+    // gets an additive pipeline, to add onto what's in its target.
+    HdrPostProcess,
+}
+
+impl ShaderKind {
+    fn fallback_code(&self) -> &'static str {
+        match self {
+            ShaderKind::Combined => FALLBACK_SHADER_CODE,
+            ShaderKind::PostProcess | ShaderKind::HdrPostProcess => PASSTHROUGH_SHADER_CODE,
+        }
+    }
 }
 
 pub struct RuntimeShader {
@@ -25,6 +50,10 @@ pub struct RuntimeShader {
     pub module: ShaderModule,
     pub pipeline_layout: PipelineLayout,
     pub pipeline: RenderPipeline,
+    // Variants for blended materials. Only 3D shaders have them, HDR post-processing passes have
+    // an additive one.
+    pub alpha_blend_pipeline: Option<RenderPipeline>,
+    pub additive_pipeline: Option<RenderPipeline>,
 }
 
 pub type ShaderId = usize;
@@ -34,11 +63,35 @@ pub const FALLBACK_SHADER_ID: ShaderId = 0;
 pub const DIM3_SHADER_ID: ShaderId = 1;
 
 pub const POST_PROCESS_SHADER_ID: ShaderId = 2;
+// Renders the depth of meshes from the view of a light
+pub const SHADOW_SHADER_ID: ShaderId = 3;
+
+// Built-in post-processing effects
+pub const TONE_MAPPING_SHADER_ID: ShaderId = 4;
+pub const GAMMA_SHADER_ID: ShaderId = 5;
+pub const FXAA_SHADER_ID: ShaderId = 6;
+pub const VIGNETTE_SHADER_ID: ShaderId = 7;
+pub const COLOR_GRADING_SHADER_ID: ShaderId = 8;
+// Draws the world's skybox behind everything else
+pub const SKY_SHADER_ID: ShaderId = 9;
+// The passes of the bloom, see renderer/bloom.rs
+pub const BLOOM_PREFILTER_SHADER_ID: ShaderId = 10;
+pub const BLOOM_DOWNSAMPLE_SHADER_ID: ShaderId = 11;
+pub const BLOOM_UPSAMPLE_SHADER_ID: ShaderId = 12;
+
+const FALLBACK_SHADER_CODE: &str = include_str!("../shaders/fallback_shader3d.wgsl");
+const PASSTHROUGH_SHADER_CODE: &str = include_str!("../shaders/fullscreen_passhthrough.wgsl");
+// Put in front of every post-processing effect, so effects only need a fs_main
+pub(crate) const POST_EFFECT_PRELUDE: &str = include_str!("../shaders/post/prelude.wgsl");
+// The functions of all bloom passes. Every pass gets its own fs_main calling one of them.
+const BLOOM_SHADER_CODE: &str = include_str!("../shaders/post/bloom.wgsl");
 
 pub struct ShaderManager {
     next_id: ShaderId,
     shaders: HashMap<ShaderId, ShaderItem>,
     device: Option<Rc<Device>>,
+    // MSAA samples of the target 3D shaders draw into
+    sample_count: u32,
 }
 
 impl Shader {
@@ -48,6 +101,8 @@ impl Shader {
         camera_uniform_bind_group_layout: &BindGroupLayout,
         model_uniform_bind_group_layout: &BindGroupLayout,
         material_uniform_bind_group_layout: &BindGroupLayout,
+        light_uniform_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
     ) -> RuntimeShader {
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some(&self.name),
@@ -59,14 +114,58 @@ impl Shader {
                 camera_uniform_bind_group_layout,
                 model_uniform_bind_group_layout,
                 material_uniform_bind_group_layout,
+                light_uniform_bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some(&format!("{} Pipeline", self.name)),
-            layout: Some(&pipeline_layout),
+        let create_pipeline = |blend_mode| {
+            Self::create_combined_pipeline(device, &self.name, &shader, &pipeline_layout, blend_mode, sample_count)
+        };
+        let pipeline = create_pipeline(BlendMode::Opaque);
+        let alpha_blend_pipeline = create_pipeline(BlendMode::AlphaBlend);
+        let additive_pipeline = create_pipeline(BlendMode::Additive);
+
+        RuntimeShader {
+            name: self.name.clone(),
+            module: shader,
+            pipeline_layout,
+            pipeline,
+            alpha_blend_pipeline: Some(alpha_blend_pipeline),
+            additive_pipeline: Some(additive_pipeline),
+        }
+    }
+
+    // Blended variants test against the depth of opaque geometry, but don't write any themselves
+    fn create_combined_pipeline(
+        device: &Device,
+        name: &str,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+        blend_mode: BlendMode,
+        sample_count: u32,
+    ) -> RenderPipeline {
+        let (label, blend) = match blend_mode {
+            BlendMode::Opaque | BlendMode::AlphaTest { .. } => ("Pipeline", None),
+            BlendMode::AlphaBlend => ("Alpha Blend Pipeline", Some(BlendState::ALPHA_BLENDING)),
+            BlendMode::Additive => ("Additive Pipeline", Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            })),
+        };
+
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&format!("{} {}", name, label)),
+            layout: Some(pipeline_layout),
             vertex: VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("vs_main"),
                 compilation_options: PipelineCompilationOptions::default(),
                 buffers: &[Vertex3D::continuous_descriptor()],
@@ -82,68 +181,153 @@ impl Shader {
             },
             depth_stencil: Some(DepthStencilState {
                 format: TextureFormat::Depth32Float,
-                depth_write_enabled: true,
+                depth_write_enabled: !blend_mode.is_blended(),
                 depth_compare: CompareFunction::Less,
                 stencil: StencilState::default(),
                 bias: DepthBiasState::default(),
             }),
-            multisample: MultisampleState::default(),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             fragment: Some(FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("fs_main"),
                 compilation_options: PipelineCompilationOptions::default(),
                 targets: &[Some(ColorTargetState {
-                    format: TextureFormat::Bgra8UnormSrgb,
-                    blend: None,
+                    format: HDR_FORMAT,
+                    blend,
                     write_mask: ColorWrites::all(),
                 })],
             }),
             multiview: None,
             cache: None,
+        })
+    }
+
+    pub fn initialize_shadow_runtime(
+        &mut self,
+        device: &Device,
+        light_view_bind_group_layout: &BindGroupLayout,
+        model_uniform_bind_group_layout: &BindGroupLayout,
+        material_bind_group_layout: &BindGroupLayout,
+    ) -> RuntimeShader {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(&self.name),
+            source: ShaderSource::Wgsl(Cow::Borrowed(&self.code)),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(&format!("{} Shadow Pipeline Layout", self.name)),
+            bind_group_layouts: &[
+                light_view_bind_group_layout,
+                model_uniform_bind_group_layout,
+                material_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&format!("{} Shadow Pipeline", self.name)),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[Vertex3D::continuous_descriptor()],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                // single sided geometry like walls or foliage has to cast shadows from both sides
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                // pushes the depth away from the light a bit against shadow acne
+                bias: DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: MultisampleState::default(),
+            // only there to cut out alpha tested materials, nothing is written but the depth
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[],
+            }),
+            multiview: None,
+            cache: None,
         });
+
         RuntimeShader {
             name: self.name.clone(),
             module: shader,
             pipeline_layout,
             pipeline,
+            alpha_blend_pipeline: None,
+            additive_pipeline: None,
         }
     }
 
-    pub fn initialize_post_process_runtime(
+    // Drawn on the far plane after the opaque geometry, so only uncovered pixels pass the depth test
+    pub fn initialize_sky_runtime(
         &mut self,
         device: &Device,
-        post_process_bind_group_layout: &BindGroupLayout,
+        camera_uniform_bind_group_layout: &BindGroupLayout,
+        light_uniform_bind_group_layout: &BindGroupLayout,
+        sample_count: u32,
     ) -> RuntimeShader {
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some(&self.name),
             source: ShaderSource::Wgsl(Cow::Borrowed(&self.code)),
         });
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some(&format!("{} PostProcess Pipeline Layout", self.name)),
-            bind_group_layouts: &[post_process_bind_group_layout],
+            label: Some(&format!("{} Sky Pipeline Layout", self.name)),
+            bind_group_layouts: &[
+                camera_uniform_bind_group_layout,
+                light_uniform_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
         let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some(&format!("{} PostProcess Pipeline", self.name)),
+            label: Some(&format!("{} Sky Pipeline", self.name)),
             layout: Some(&pipeline_layout),
             vertex: VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
+                compilation_options: PipelineCompilationOptions::default(),
                 buffers: &[],
             },
             primitive: PrimitiveState {
                 topology: PrimitiveTopology::TriangleList,
                 ..Default::default()
             },
-            depth_stencil: None, // No depth for post-processing
-            multisample: MultisampleState::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             fragment: Some(FragmentState {
                 module: &shader,
                 entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
+                compilation_options: PipelineCompilationOptions::default(),
                 targets: &[Some(ColorTargetState {
-                    format: TextureFormat::Bgra8UnormSrgb,
+                    format: HDR_FORMAT,
                     blend: None,
                     write_mask: ColorWrites::all(),
                 })],
@@ -157,8 +341,130 @@ impl Shader {
             module: shader,
             pipeline_layout,
             pipeline,
+            alpha_blend_pipeline: None,
+            additive_pipeline: None,
         }
     }
+
+    pub fn initialize_post_process_runtime(
+        &mut self,
+        device: &Device,
+        post_process_bind_group_layouts: &[&BindGroupLayout],
+    ) -> RuntimeShader {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(&self.name),
+            source: ShaderSource::Wgsl(Cow::Borrowed(&self.code)),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(&format!("{} PostProcess Pipeline Layout", self.name)),
+            bind_group_layouts: post_process_bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        let format = match self.kind {
+            ShaderKind::HdrPostProcess => HDR_FORMAT,
+            _ => TextureFormat::Bgra8UnormSrgb,
+        };
+        let create_pipeline = |label, blend| {
+            Self::create_post_process_pipeline(device, &self.name, label, &shader, &pipeline_layout, format, blend)
+        };
+        let pipeline = create_pipeline("PostProcess Pipeline", None);
+        let additive_pipeline = (self.kind == ShaderKind::HdrPostProcess).then(|| {
+            let additive = BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::REPLACE,
+            };
+            create_pipeline("PostProcess Additive Pipeline", Some(additive))
+        });
+
+        RuntimeShader {
+            name: self.name.clone(),
+            module: shader,
+            pipeline_layout,
+            pipeline,
+            alpha_blend_pipeline: None,
+            additive_pipeline,
+        }
+    }
+
+    fn create_post_process_pipeline(
+        device: &Device,
+        name: &str,
+        label: &str,
+        shader: &ShaderModule,
+        pipeline_layout: &PipelineLayout,
+        format: TextureFormat,
+        blend: Option<BlendState>,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&format!("{name} {label}")),
+            layout: Some(pipeline_layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None, // No depth for post-processing
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+impl Shader {
+    fn initialize_runtime_of_kind(
+        &mut self,
+        device: &Device,
+        bgls: &BindGroupLayoutManager,
+        sample_count: u32,
+    ) -> RuntimeShader {
+        match self.kind {
+            ShaderKind::Combined => {
+                let camera_ubgl = bgls.get_bind_group_layout(CAMERA_UBGL_ID).unwrap();
+                let model_ubgl = bgls.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
+                let material_ubgl = bgls.get_bind_group_layout(MATERIAL_UBGL_ID).unwrap();
+                let light_ubgl = bgls.get_bind_group_layout(LIGHT_UBGL_ID).unwrap();
+                self.initialize_combined_runtime(device, camera_ubgl, model_ubgl, material_ubgl, light_ubgl, sample_count)
+            },
+            ShaderKind::PostProcess | ShaderKind::HdrPostProcess => {
+                let post_process_ubgl = bgls.get_bind_group_layout(POST_PROCESS_BGL_ID).unwrap();
+                let post_effect_ubgl = bgls.get_bind_group_layout(POST_EFFECT_BGL_ID).unwrap();
+                self.initialize_post_process_runtime(device, &[post_process_ubgl, post_effect_ubgl])
+            },
+        }
+    }
+}
+
+impl RuntimeShader {
+    // The pipeline drawing materials of the blend mode, falls back to the main pipeline
+    pub fn pipeline_for(&self, blend_mode: BlendMode) -> &RenderPipeline {
+        let variant = match blend_mode {
+            BlendMode::Opaque | BlendMode::AlphaTest { .. } => None,
+            BlendMode::AlphaBlend => self.alpha_blend_pipeline.as_ref(),
+            BlendMode::Additive => self.additive_pipeline.as_ref(),
+        };
+        variant.unwrap_or(&self.pipeline)
+    }
 }
 
 #[allow(dead_code)]
@@ -170,10 +476,11 @@ impl ShaderManager {
             next_id: 0,
             shaders: HashMap::new(),
             device: None,
+            sample_count: 1,
         };
         shader_manager.add_shader(
             "Fallback".to_string(),
-            include_str!("../shaders/fallback_shader3d.wgsl").to_string(),
+            FALLBACK_SHADER_CODE.to_string(),
         );
         shader_manager.add_shader(
             "3D Default Pipeline".to_string(),
@@ -181,8 +488,31 @@ impl ShaderManager {
         );
         shader_manager.add_shader(
             "PostProcess".to_string(),
-            include_str!("../shaders/fullscreen_passhthrough.wgsl").to_string(),
+            PASSTHROUGH_SHADER_CODE.to_string(),
+        );
+        shader_manager.add_shader(
+            "Shadow".to_string(),
+            include_str!("../shaders/shadow.wgsl").to_string(),
         );
+        shader_manager.add_post_process_shader("Tone Mapping", include_str!("../shaders/post/tone_mapping.wgsl"));
+        shader_manager.add_post_process_shader("Gamma", include_str!("../shaders/post/gamma.wgsl"));
+        shader_manager.add_post_process_shader("FXAA", include_str!("../shaders/post/fxaa.wgsl"));
+        shader_manager.add_post_process_shader("Vignette", include_str!("../shaders/post/vignette.wgsl"));
+        shader_manager.add_post_process_shader("Color Grading", include_str!("../shaders/post/color_grading.wgsl"));
+        shader_manager.add_shader(
+            "Sky".to_string(),
+            include_str!("../shaders/sky.wgsl").to_string(),
+        );
+        for (name, pass) in [
+            ("Bloom Prefilter", "prefilter"),
+            ("Bloom Downsample", "downsample"),
+            ("Bloom Upsample", "upsample"),
+        ] {
+            let code = format!(
+                "{POST_EFFECT_PRELUDE}\n{BLOOM_SHADER_CODE}\n@fragment\nfn fs_main(@location(0) uv: vec2f) -> @location(0) vec4f {{\n    return {pass}(uv);\n}}\n"
+            );
+            shader_manager.add_shader_of_kind(name.to_string(), code, ShaderKind::HdrPostProcess);
+        }
         shader_manager
     }
 
@@ -198,6 +528,21 @@ impl ShaderManager {
         self.init();
     }
 
+    // 3D pipelines have to match the MSAA samples of the scene target, so they're rebuilt when it changes
+    pub(crate) fn set_sample_count(&mut self, sample_count: u32) {
+        if self.sample_count == sample_count {
+            return;
+        }
+        self.sample_count = sample_count;
+        for shader in self.shaders.values_mut() {
+            shader.runtime = None;
+        }
+    }
+
+    // Adds a shader with a vs_main and fs_main entry point, which is used for every material
+    // referencing it. It's bound like the default 3D shader, so it has to declare the same
+    // bind groups (camera, model, material, lights), but may leave out bindings it doesn't use.
+    // The models are an array<ModelData> in a storage buffer, indexed by the instance index.
     pub fn add_combined_shader_file<T>(
         &mut self,
         name: &str,
@@ -214,13 +559,37 @@ impl ShaderManager {
         self.add_shader(name.to_string(), shader.to_string())
     }
 
+    // Adds a post-processing effect, which only has to contain the fs_main entry point. The
+    // bindings of shaders/post/prelude.wgsl are put in front of it, with the image of the
+    // previous effect at postTexture and the effect's parameters in `effect`.
+    pub fn add_post_process_shader_file<T>(
+        &mut self,
+        name: &str,
+        path: T,
+    ) -> Result<ShaderId, Box<dyn Error>>
+    where
+        T: AsRef<Path>,
+    {
+        let content = fs::read_to_string(path)?;
+        Ok(self.add_post_process_shader(name, &content))
+    }
+
+    pub fn add_post_process_shader(&mut self, name: &str, shader: &str) -> ShaderId {
+        let code = format!("{POST_EFFECT_PRELUDE}\n{shader}");
+        self.add_shader_of_kind(name.to_string(), code, ShaderKind::PostProcess)
+    }
+
     pub fn add_shader(&mut self, name: String, code: String) -> ShaderId {
+        self.add_shader_of_kind(name, code, ShaderKind::Combined)
+    }
+
+    fn add_shader_of_kind(&mut self, name: String, code: String, kind: ShaderKind) -> ShaderId {
         let id = self.next_id;
 
         self.shaders.insert(
             self.next_id,
             ShaderItem {
-                raw: Shader { name, code },
+                raw: Shader { name, code, kind },
                 runtime: None,
             },
         );
@@ -229,32 +598,74 @@ impl ShaderManager {
         id
     }
 
-    pub(crate) fn get_shader(&mut self, id: ShaderId) -> Option<&RuntimeShader> {
+    pub(crate) fn get_shader(&mut self, id: ShaderId, bgls: &BindGroupLayoutManager) -> Option<&RuntimeShader> {
         let shader_item = self.shaders.get_mut(&id)?;
         if shader_item.runtime.is_none() {
-            let world = World::instance();
-            let bgls = &world.assets.bind_group_layouts;
 
             let runtime_shader = match id {
                 FALLBACK_SHADER_ID | DIM3_SHADER_ID => {
                     let camera_ubgl = bgls.get_bind_group_layout(CAMERA_UBGL_ID).unwrap();
                     let model_ubgl = bgls.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
                     let material_ubgl = bgls.get_bind_group_layout(MATERIAL_UBGL_ID).unwrap();
+                    let light_ubgl = bgls.get_bind_group_layout(LIGHT_UBGL_ID).unwrap();
                     shader_item.raw.initialize_combined_runtime(
                         self.device.clone().unwrap().as_ref(),
                         camera_ubgl,
                         model_ubgl,
                         material_ubgl,
+                        light_ubgl,
+                        self.sample_count,
                     )
                 },
                 POST_PROCESS_SHADER_ID => {
                     let post_process_ubgl = bgls.get_bind_group_layout(POST_PROCESS_BGL_ID).unwrap();
                     shader_item.raw.initialize_post_process_runtime(
                         self.device.clone().unwrap().as_ref(),
-                        post_process_ubgl,
+                        &[post_process_ubgl],
                     )
                 },
-                _ => panic!("Shader ID not recognized"),
+                SHADOW_SHADER_ID => {
+                    // the light's view uses the same layout as the camera
+                    let light_view_ubgl = bgls.get_bind_group_layout(CAMERA_UBGL_ID).unwrap();
+                    let model_ubgl = bgls.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
+                    let material_ubgl = bgls.get_bind_group_layout(MATERIAL_UBGL_ID).unwrap();
+                    shader_item.raw.initialize_shadow_runtime(
+                        self.device.clone().unwrap().as_ref(),
+                        light_view_ubgl,
+                        model_ubgl,
+                        material_ubgl,
+                    )
+                },
+                SKY_SHADER_ID => {
+                    let camera_ubgl = bgls.get_bind_group_layout(CAMERA_UBGL_ID).unwrap();
+                    let light_ubgl = bgls.get_bind_group_layout(LIGHT_UBGL_ID).unwrap();
+                    shader_item.raw.initialize_sky_runtime(
+                        self.device.clone().unwrap().as_ref(),
+                        camera_ubgl,
+                        light_ubgl,
+                        self.sample_count,
+                    )
+                },
+                // everything else is a user shader or a post-processing effect
+                _ => {
+                    let device = self.device.clone().unwrap();
+
+                    // a broken user shader shouldn't take the whole application down
+                    device.push_error_scope(ErrorFilter::Validation);
+                    let runtime_shader = shader_item.raw.initialize_runtime_of_kind(&device, bgls, self.sample_count);
+                    match block_on(device.pop_error_scope()) {
+                        None => runtime_shader,
+                        Some(e) => {
+                            error!("Shader \"{}\" failed to compile, using the fallback shader: {e}", shader_item.raw.name);
+                            let mut fallback = Shader {
+                                name: format!("{} (Fallback)", shader_item.raw.name),
+                                code: shader_item.raw.kind.fallback_code().to_string(),
+                                kind: shader_item.raw.kind,
+                            };
+                            fallback.initialize_runtime_of_kind(&device, bgls, self.sample_count)
+                        },
+                    }
+                },
             };
 
             shader_item.runtime = Some(runtime_shader);
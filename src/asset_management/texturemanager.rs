@@ -1,16 +1,23 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
 use std::rc::Rc;
 
 use wgpu::{AddressMode, Device, Extent3d, Queue, SamplerDescriptor, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor, TextureViewDimension};
 use wgpu::util::{DeviceExt, TextureDataOrder};
 
+use crate::asset_management::cubemap::{CubeFaces, CUBE_FACES};
+
 pub const FALLBACK_DIFFUSE_TEXTURE: TextureId = 0;
 pub const FALLBACK_NORMAL_TEXTURE: TextureId = 1;
 pub const FALLBACK_SHININESS_TEXTURE: TextureId = 2;
 
+// The format of textures cameras render into. The post-processing pipelines write it.
+pub const RENDER_TEXTURE_FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
+
 #[allow(dead_code)]
 pub struct RuntimeTexture {
-    texture: wgpu::Texture,
+    pub(crate) texture: wgpu::Texture,
     pub(crate) view: wgpu::TextureView,
     pub(crate) sampler: wgpu::Sampler,
 }
@@ -19,7 +26,22 @@ pub struct RawTexture {
     pub width: u32,
     pub height: u32,
     pub format: TextureFormat,
+    // D2 or Cube. Cube textures have six square layers.
+    pub dimension: TextureViewDimension,
+    pub mip_levels: u32,
+    // All mip levels of a layer, followed by the next layer
     pub data: Option<Vec<u8>>,
+    // Whether cameras can render into it, see add_render_texture
+    pub render_target: bool,
+}
+
+impl RawTexture {
+    pub fn layers(&self) -> u32 {
+        match self.dimension {
+            TextureViewDimension::Cube => CUBE_FACES as u32,
+            _ => 1,
+        }
+    }
 }
 
 pub struct Texture {
@@ -71,10 +93,11 @@ impl TextureManager {
         );
         assert_eq!(id, FALLBACK_DIFFUSE_TEXTURE);
 
-        let id = manager.add_texture(1, 1, TextureFormat::Bgra8UnormSrgb, Some(vec![0, 0, 0, 0]));
+        // a flat tangent space normal pointing straight out of the surface
+        let id = manager.add_texture(1, 1, TextureFormat::Bgra8Unorm, Some(vec![255, 128, 128, 255]));
         assert_eq!(id, FALLBACK_NORMAL_TEXTURE);
 
-        let id = manager.add_texture(1, 1, TextureFormat::Bgra8UnormSrgb, Some(vec![0, 0, 0, 0]));
+        let id = manager.add_texture(1, 1, TextureFormat::Bgra8Unorm, Some(vec![255, 255, 255, 255]));
         assert_eq!(id, FALLBACK_SHININESS_TEXTURE);
 
         manager
@@ -101,12 +124,86 @@ impl TextureManager {
         format: TextureFormat,
         data: Option<Vec<u8>>,
     ) -> TextureId {
-        let raw = RawTexture {
+        self.add_raw_texture(RawTexture {
             width,
             height,
             format,
+            dimension: TextureViewDimension::D2,
+            mip_levels: 1,
             data,
-        };
+            render_target: false,
+        })
+    }
+
+    // Adds an empty texture a camera can render into by setting it as the camera's target.
+    // Materials sample it like any other texture and see what the camera rendered last.
+    pub fn add_render_texture(&mut self, width: u32, height: u32) -> TextureId {
+        self.add_raw_texture(RawTexture {
+            width: width.max(1),
+            height: height.max(1),
+            format: RENDER_TEXTURE_FORMAT,
+            dimension: TextureViewDimension::D2,
+            mip_levels: 1,
+            data: None,
+            render_target: true,
+        })
+    }
+
+    // Adds a cube texture from six square faces in the order +X, -X, +Y, -Y, +Z, -Z.
+    // The data holds all mip levels of a face before the next face starts.
+    pub fn add_cube_texture(
+        &mut self,
+        size: u32,
+        mip_levels: u32,
+        format: TextureFormat,
+        data: Option<Vec<u8>>,
+    ) -> TextureId {
+        self.add_raw_texture(RawTexture {
+            width: size,
+            height: size,
+            format,
+            dimension: TextureViewDimension::Cube,
+            mip_levels,
+            data,
+            render_target: false,
+        })
+    }
+
+    // Loads a cube texture from six square images in the order +X, -X, +Y, -Y, +Z, -Z.
+    // Blurrier mip levels are generated for rough reflections.
+    pub fn load_cube_texture<P>(&mut self, faces: [P; CUBE_FACES]) -> Result<TextureId, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let images = faces
+            .iter()
+            .map(image::open)
+            .collect::<Result<Vec<_>, _>>()?;
+        let cube = CubeFaces::from_images(&images)?;
+        Ok(self.add_cube_faces(&cube))
+    }
+
+    // Loads a latitude-longitude panorama, like an .hdr environment map, and projects it onto a
+    // cube texture with faces of `face_size` x `face_size`
+    pub fn load_equirectangular_texture<P>(&mut self, path: P, face_size: u32) -> Result<TextureId, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let image = image::open(path)?;
+        let cube = CubeFaces::from_equirectangular(&image, face_size.max(1));
+        Ok(self.add_cube_faces(&cube))
+    }
+
+    fn add_cube_faces(&mut self, cube: &CubeFaces) -> TextureId {
+        self.add_cube_texture(
+            cube.size,
+            cube.mip_levels(),
+            TextureFormat::Rgba16Float,
+            Some(cube.to_rgba16f_with_mips()),
+        )
+    }
+
+    fn add_raw_texture(&mut self, raw: RawTexture) -> TextureId {
         let id = self.next_id;
 
         let texture = Texture { raw, runtime: None };
@@ -159,8 +256,8 @@ impl Texture {
         };
         let view = gpu_tex.create_view(&TextureViewDescriptor {
             label: Some("Texture View"),
-            format: Some(TextureFormat::Bgra8UnormSrgb),
-            dimension: Some(TextureViewDimension::D2),
+            format: Some(raw.format),
+            dimension: Some(raw.dimension),
             aspect: TextureAspect::All,
             base_mip_level: 0,
             mip_level_count: None,
@@ -192,19 +289,23 @@ impl Texture {
     }
     
     fn initialize_texture_descriptor(&self, raw: &RawTexture) -> TextureDescriptor{
+        let usage = match raw.render_target {
+            true => TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            false => TextureUsages::TEXTURE_BINDING,
+        };
         TextureDescriptor {
             label: Some("Texture"),
             size: Extent3d {
                 width: raw.width,
                 height: raw.height,
-                depth_or_array_layers: 1,
+                depth_or_array_layers: raw.layers(),
             },
-            mip_level_count: 1,
+            mip_level_count: raw.mip_levels,
             sample_count: 1,
             dimension: TextureDimension::D2,
             format: raw.format,
-            usage: TextureUsages::TEXTURE_BINDING,
-            view_formats: &[TextureFormat::Bgra8UnormSrgb],
+            usage,
+            view_formats: &[],
         }
     }
     
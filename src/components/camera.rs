@@ -1,32 +1,263 @@
 use bytemuck::{Pod, Zeroable};
-use nalgebra::{Affine3, Matrix4, Perspective3, Vector3};
+use nalgebra::{Affine3, Matrix4, Orthographic3, Perspective3, Point3, Vector2, Vector3, Vector4};
 
+use crate::asset_management::texturemanager::TextureId;
+use crate::components::registry::{FieldInfo, FieldKind, FieldValue, ReflectComponent};
 use crate::components::Component;
 use crate::object::GameObjectId;
+use crate::reflect_enum;
 use crate::transform::Transform;
-use crate::utils::math::QuaternionEuler;
+use crate::utils::math::{QuaternionEuler, OPENGL_TO_WGPU_MATRIX};
+use crate::world::World;
+
+// How the camera maps what it sees onto the screen. The aspect ratio comes from the screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    // `fov` is the vertical field of view in radians
+    Perspective { fov: f32, near: f32, far: f32 },
+    // Everything keeps its size regardless of the distance. `size` is the height of the view in
+    // world units.
+    Orthographic { size: f32, near: f32, far: f32 },
+}
+
+impl Projection {
+    pub fn perspective(fov_degrees: f32, near: f32, far: f32) -> Self {
+        Projection::Perspective {
+            fov: fov_degrees.to_radians(),
+            near,
+            far,
+        }
+    }
+
+    pub fn orthographic(size: f32, near: f32, far: f32) -> Self {
+        Projection::Orthographic { size, near, far }
+    }
+
+    // The distances of the near and the far plane
+    pub fn clip_planes(&self) -> (f32, f32) {
+        match *self {
+            Projection::Perspective { near, far, .. } | Projection::Orthographic { near, far, .. } => (near, far),
+        }
+    }
+
+    pub fn set_clip_planes(&mut self, new_near: f32, new_far: f32) {
+        match self {
+            Projection::Perspective { near, far, .. } | Projection::Orthographic { near, far, .. } => {
+                *near = new_near;
+                *far = new_far;
+            }
+        }
+    }
+
+    // Maps the view to clip space with depth from 0 at the near plane to 1 at the far plane
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        let projection = match *self {
+            Projection::Perspective { fov, near, far } => Perspective3::new(aspect, fov, near, far).to_homogeneous(),
+            Projection::Orthographic { size, near, far } => {
+                let half_height = size * 0.5;
+                let half_width = half_height * aspect;
+                Orthographic3::new(-half_width, half_width, -half_height, half_height, near, far).to_homogeneous()
+            }
+        };
+        OPENGL_TO_WGPU_MATRIX * projection
+    }
+
+    // The height of the view at `distance` in front of the camera, in world units
+    pub fn view_height_at(&self, distance: f32) -> f32 {
+        match *self {
+            Projection::Perspective { fov, .. } => 2.0 * distance * (fov * 0.5).tan(),
+            Projection::Orthographic { size, .. } => size,
+        }
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::perspective(60.0, 0.01, 1000.0)
+    }
+}
+
+// A half-line starting at `origin`. The direction is normalized.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn point_at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
+// The part of its target a camera renders into, relative to the target's size. (0, 0) is the
+// top left corner, (1, 1) the bottom right one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Viewport = Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport { x, y, width, height }
+    }
+
+    // The viewport in pixels of a target with the given size as (x, y, width, height), cut off at
+    // the target's edges. Always at least one pixel big.
+    pub fn to_pixels(&self, target_width: u32, target_height: u32) -> (u32, u32, u32, u32) {
+        let to_pixels = |value: f32, size: u32| (value.clamp(0.0, 1.0) * size as f32).round() as u32;
+        let x = to_pixels(self.x, target_width).min(target_width - 1);
+        let y = to_pixels(self.y, target_height).min(target_height - 1);
+        let right = to_pixels(self.x + self.width, target_width).max(x + 1);
+        let bottom = to_pixels(self.y + self.height, target_height).max(y + 1);
+        (x, y, right - x, bottom - y)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::FULL
+    }
+}
 
 pub struct CameraComp {
-    pub projection: Perspective3<f32>,
+    pub projection: Projection,
+    // Width divided by height of the camera's viewport, kept up to date by the renderer
+    aspect: f32,
+    pub viewport: Viewport,
+    // Cameras are rendered from the lowest to the highest priority, so cameras with a higher one
+    // draw over the others where their viewports overlap. Cameras rendering into textures are
+    // always rendered before the ones rendering to the window, so the window shows what they
+    // rendered this frame.
+    pub priority: i32,
+    // Renders into this texture instead of the window. It has to be created with
+    // TextureManager::add_render_texture.
+    pub target: Option<TextureId>,
+    // The scene is rendered in HDR. These decide how it's brought into the displayable range.
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+    // Lets everything brighter than the threshold glow. The knee softens the threshold.
+    pub bloom: bool,
+    pub bloom_threshold: f32,
+    pub bloom_knee: f32,
+    pub bloom_intensity: f32,
     parent: GameObjectId,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    // Colors above 1.0 are clipped
+    None,
+    Reinhard,
+    // Filmic curve that keeps more contrast than Reinhard
+    #[default]
+    Aces,
+}
+
+impl ToneMapping {
+    // Has to match the operators in shaders/post/tone_mapping.wgsl
+    pub(crate) fn shader_index(&self) -> f32 {
+        match self {
+            ToneMapping::None => 0.0,
+            ToneMapping::Reinhard => 1.0,
+            ToneMapping::Aces => 2.0,
+        }
+    }
+}
+
 impl CameraComp {
+    // Only changes the aspect ratio, the projection stays as it was set
     pub fn resize(&mut self, width: f32, height: f32) {
-        self.projection = Perspective3::new(width / height, 60f32.to_radians(), 0.01, 1000.0);
+        if width > 0.0 && height > 0.0 {
+            self.aspect = width / height;
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        self.projection.matrix(self.aspect)
+    }
+
+    fn view_proj_matrix(&self) -> Matrix4<f32> {
+        let view = unsafe { self.get_parent() }
+            .transform
+            .get_global_transform_matrix_ext(true)
+            .inverse();
+        self.projection_matrix() * view.to_homogeneous()
+    }
+
+    // The ray from the camera through a pixel, like the mouse position. Screen coordinates
+    // start at the top left corner of the camera's viewport and go to `screen_size`, the size
+    // of the viewport in pixels.
+    pub fn screen_to_world_ray(&self, screen_pos: Vector2<f32>, screen_size: Vector2<f32>) -> Option<Ray> {
+        let inverse = self.view_proj_matrix().try_inverse()?;
+        let ndc_x = screen_pos.x / screen_size.x * 2.0 - 1.0;
+        let ndc_y = 1.0 - screen_pos.y / screen_size.y * 2.0;
+
+        let unproject = |ndc_z: f32| {
+            let point = inverse * Vector4::new(ndc_x, ndc_y, ndc_z, 1.0);
+            point.xyz() / point.w
+        };
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+        Some(Ray {
+            origin: near,
+            direction: (far - near).try_normalize(f32::EPSILON)?,
+        })
+    }
+
+    // Where a point in the world ends up on the screen, in the coordinates screen_to_world_ray
+    // takes. Points behind the camera's near plane have no screen position.
+    pub fn world_to_screen(&self, point: Vector3<f32>, screen_size: Vector2<f32>) -> Option<Vector2<f32>> {
+        let clip = self.view_proj_matrix() * Point3::from(point).to_homogeneous();
+        if clip.w <= 0.0 || clip.z < 0.0 {
+            return None;
+        }
+        let ndc = clip.xy() / clip.w;
+        Some(Vector2::new(
+            (ndc.x + 1.0) * 0.5 * screen_size.x,
+            (1.0 - ndc.y) * 0.5 * screen_size.y,
+        ))
     }
 }
 
 impl Component for CameraComp {
     unsafe fn new(parent: GameObjectId) -> Self {
         CameraComp {
-            projection: Perspective3::new(800.0 / 600.0, 60f32.to_radians(), 0.01, 1000.0),
+            projection: Projection::default(),
+            aspect: 800.0 / 600.0,
+            viewport: Viewport::FULL,
+            priority: 0,
+            target: None,
+            exposure: 1.0,
+            tone_mapping: ToneMapping::Aces,
+            bloom: false,
+            bloom_threshold: 1.0,
+            bloom_knee: 0.5,
+            bloom_intensity: 0.1,
             parent,
         }
     }
 
-    unsafe fn init(&mut self) {
+    unsafe fn init(&mut self, world: &mut World) {
         self.get_parent().transform.set_compound_pos_first(true);
+
+        if world.active_camera.is_none() {
+            world.active_camera = Some(self.parent);
+        }
     }
 
     unsafe fn get_parent(&self) -> GameObjectId {
@@ -34,6 +265,106 @@ impl Component for CameraComp {
     }
 }
 
+reflect_enum!(ToneMapping { None, Reinhard, Aces });
+
+// Written out instead of using reflect_component!, as the projection and the viewport are
+// reflected as separate fields. The fov (in degrees) only exists on perspective cameras and the
+// size only on orthographic ones. Setting either switches the camera to that projection, so
+// the order the fields are loaded in doesn't matter. The target only exists on cameras rendering
+// into a texture, and is saved by its texture id like other texture fields.
+impl ReflectComponent for CameraComp {
+    const NAME: &'static str = "Camera";
+
+    fn fields() -> &'static [FieldInfo] {
+        const fn field(name: &'static str, kind: FieldKind) -> FieldInfo {
+            FieldInfo { name, kind }
+        }
+        const FIELDS: &[FieldInfo] = &[
+            field("projection", FieldKind::Enum),
+            field("fov", FieldKind::Float),
+            field("size", FieldKind::Float),
+            field("near", FieldKind::Float),
+            field("far", FieldKind::Float),
+            field("viewport_x", FieldKind::Float),
+            field("viewport_y", FieldKind::Float),
+            field("viewport_width", FieldKind::Float),
+            field("viewport_height", FieldKind::Float),
+            field("priority", FieldKind::Int),
+            field("target", FieldKind::Texture),
+            field("exposure", FieldKind::Float),
+            field("tone_mapping", FieldKind::Enum),
+            field("bloom", FieldKind::Bool),
+            field("bloom_threshold", FieldKind::Float),
+            field("bloom_knee", FieldKind::Float),
+            field("bloom_intensity", FieldKind::Float),
+        ];
+        FIELDS
+    }
+
+    fn get_field(&self, name: &str) -> Option<FieldValue> {
+        let (near, far) = self.projection.clip_planes();
+        Some(match (name, self.projection) {
+            ("projection", Projection::Perspective { .. }) => FieldValue::Enum("Perspective".to_owned()),
+            ("projection", Projection::Orthographic { .. }) => FieldValue::Enum("Orthographic".to_owned()),
+            ("fov", Projection::Perspective { fov, .. }) => FieldValue::Float(fov.to_degrees()),
+            ("size", Projection::Orthographic { size, .. }) => FieldValue::Float(size),
+            ("near", _) => FieldValue::Float(near),
+            ("far", _) => FieldValue::Float(far),
+            ("viewport_x", _) => FieldValue::Float(self.viewport.x),
+            ("viewport_y", _) => FieldValue::Float(self.viewport.y),
+            ("viewport_width", _) => FieldValue::Float(self.viewport.width),
+            ("viewport_height", _) => FieldValue::Float(self.viewport.height),
+            ("priority", _) => FieldValue::Int(self.priority),
+            ("target", _) => FieldValue::Texture(self.target?),
+            ("exposure", _) => FieldValue::Float(self.exposure),
+            ("tone_mapping", _) => FieldValue::Enum(self.tone_mapping.into()),
+            ("bloom", _) => FieldValue::Bool(self.bloom),
+            ("bloom_threshold", _) => FieldValue::Float(self.bloom_threshold),
+            ("bloom_knee", _) => FieldValue::Float(self.bloom_knee),
+            ("bloom_intensity", _) => FieldValue::Float(self.bloom_intensity),
+            _ => return None,
+        })
+    }
+
+    fn set_field(&mut self, name: &str, value: FieldValue) -> bool {
+        let (near, far) = self.projection.clip_planes();
+        match (name, value) {
+            ("projection", FieldValue::Enum(kind)) => {
+                // switching keeps the clip planes, the fov starts at 60 degrees and the size at 10 units
+                self.projection = match (kind.as_str(), self.projection) {
+                    ("Perspective", Projection::Perspective { .. }) | ("Orthographic", Projection::Orthographic { .. }) => {
+                        return true
+                    }
+                    ("Perspective", _) => Projection::perspective(60.0, near, far),
+                    ("Orthographic", _) => Projection::orthographic(10.0, near, far),
+                    _ => return false,
+                };
+            }
+            ("fov", FieldValue::Float(fov)) => self.projection = Projection::perspective(fov, near, far),
+            ("size", FieldValue::Float(size)) => self.projection = Projection::orthographic(size, near, far),
+            ("near", FieldValue::Float(near)) => self.projection.set_clip_planes(near, far),
+            ("far", FieldValue::Float(far)) => self.projection.set_clip_planes(near, far),
+            ("viewport_x", FieldValue::Float(x)) => self.viewport.x = x,
+            ("viewport_y", FieldValue::Float(y)) => self.viewport.y = y,
+            ("viewport_width", FieldValue::Float(width)) => self.viewport.width = width,
+            ("viewport_height", FieldValue::Float(height)) => self.viewport.height = height,
+            ("priority", FieldValue::Int(priority)) => self.priority = priority,
+            ("target", FieldValue::Texture(target)) => self.target = Some(target),
+            ("exposure", FieldValue::Float(exposure)) => self.exposure = exposure,
+            ("tone_mapping", FieldValue::Enum(tone_mapping)) => match tone_mapping.try_into() {
+                Ok(tone_mapping) => self.tone_mapping = tone_mapping,
+                Err(_) => return false,
+            },
+            ("bloom", FieldValue::Bool(bloom)) => self.bloom = bloom,
+            ("bloom_threshold", FieldValue::Float(threshold)) => self.bloom_threshold = threshold,
+            ("bloom_knee", FieldValue::Float(knee)) => self.bloom_knee = knee,
+            ("bloom_intensity", FieldValue::Float(intensity)) => self.bloom_intensity = intensity,
+            _ => return false,
+        }
+        true
+    }
+}
+
 // TODO: Remove manual padding somehow?
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -63,15 +394,87 @@ impl CameraData {
             proj_view_mat: Matrix4::identity(),
         }
     }
-    pub fn update(&mut self, proj_matrix: &Perspective3<f32>, cam_transform: &Transform) {
+    pub fn update(&mut self, proj_matrix: &Matrix4<f32>, cam_transform: &Transform) {
         self.pos = cam_transform.position();
         self.rot = cam_transform.rotation().euler_vector_deg();
         self.scale = cam_transform.scale();
         self.view_mat = cam_transform.get_global_transform_matrix_ext(true).inverse();
-        self.projection_mat = proj_matrix.to_homogeneous();
+        self.projection_mat = *proj_matrix;
         self.proj_view_mat = self.projection_mat * self.view_mat.to_homogeneous();
     }
 }
 
 unsafe impl Zeroable for CameraData {}
 unsafe impl Pod for CameraData {}
+
+#[cfg(test)]
+mod tests {
+    use super::{CameraComp, Projection, ToneMapping, Viewport};
+    use crate::components::registry::{FieldMap, FieldValue, ReflectComponent};
+    use crate::utils::testing::detached_component;
+    use crate::world::World;
+
+    // Saves the fields like a scene file would, sorted by name
+    fn save(camera: &CameraComp) -> FieldMap {
+        CameraComp::fields()
+            .iter()
+            .filter_map(|field| Some((field.name.to_owned(), camera.get_field(field.name)?)))
+            .collect()
+    }
+
+    fn load(camera: &mut CameraComp, fields: FieldMap) {
+        for (name, value) in fields {
+            assert!(camera.set_field(&name, value), "couldn't set {name}");
+        }
+    }
+
+    #[test]
+    fn orthographic_camera_round_trips_onto_perspective_one() {
+        let mut world = World::new();
+        let mut saved: CameraComp = detached_component(&mut world, "Saved");
+        saved.projection = Projection::orthographic(25.0, 0.5, 200.0);
+        saved.viewport = Viewport::new(0.75, 0.0, 0.25, 0.25);
+        saved.priority = -3;
+        saved.target = Some(7);
+        saved.tone_mapping = ToneMapping::Reinhard;
+
+        let mut loaded: CameraComp = detached_component(&mut world, "Loaded");
+        load(&mut loaded, save(&saved));
+
+        assert_eq!(loaded.projection, saved.projection);
+        assert_eq!(loaded.viewport, saved.viewport);
+        assert_eq!(loaded.priority, -3);
+        assert_eq!(loaded.target, Some(7));
+        assert_eq!(loaded.tone_mapping, ToneMapping::Reinhard);
+    }
+
+    #[test]
+    fn perspective_camera_round_trips_onto_orthographic_one() {
+        let mut world = World::new();
+        let mut saved: CameraComp = detached_component(&mut world, "Saved");
+        saved.projection = Projection::perspective(90.0, 0.1, 50.0);
+
+        let mut loaded: CameraComp = detached_component(&mut world, "Loaded");
+        loaded.projection = Projection::orthographic(25.0, 1.0, 2.0);
+        let fields = save(&saved);
+        // cameras rendering to the window have no target to save
+        assert!(!fields.contains_key("target"));
+        load(&mut loaded, fields);
+
+        let Projection::Perspective { fov, near, far } = loaded.projection else {
+            panic!("expected a perspective projection, got {:?}", loaded.projection);
+        };
+        assert!((fov.to_degrees() - 90.0).abs() < 1e-4);
+        assert_eq!((near, far), (0.1, 50.0));
+    }
+
+    #[test]
+    fn unknown_projection_is_rejected() {
+        let mut world = World::new();
+        let mut camera: CameraComp = detached_component(&mut world, "Camera");
+        let before = camera.projection;
+
+        assert!(!camera.set_field("projection", FieldValue::Enum("Fisheye".to_owned())));
+        assert_eq!(camera.projection, before);
+    }
+}
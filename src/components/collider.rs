@@ -6,9 +6,30 @@ use crate::asset_management::meshmanager::MeshId;
 use crate::components::{Component, RigidBodyComponent};
 use crate::object::GameObjectId;
 use crate::world::World;
+use crate::{reflect_component, reflect_enum};
+
+// The collider shapes that can be saved. Others, like triangle meshes, have to be set up in code
+// through get_collider_mut.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColliderShape {
+    Cuboid,
+    Ball,
+    // upright capsule
+    CapsuleY,
+}
+
+reflect_enum!(ColliderShape { Cuboid, Ball, CapsuleY });
 
 pub struct Collider3D {
     pub phys_handle: ColliderHandle,
+    // Changing these resizes the collider on the next update. Shapes set through
+    // get_collider_mut are copied back here, so both stay in sync.
+    pub shape: ColliderShape,
+    // Half extents of a cuboid, before the scale of the object is applied. Balls use x as the
+    // radius, capsules x as the radius and y as the half height of their segment.
+    pub size: Vector3<f32>,
+    // the shape, size and object scale the rapier collider was last synced with
+    synced: (ColliderShape, Vector3<f32>, Vector3<f32>),
     linked_to_body: Option<RigidBodyHandle>,
     parent: GameObjectId,
 }
@@ -18,59 +39,127 @@ impl Component for Collider3D {
     where
         Self: Sized,
     {
-        let scale = parent.transform.scale();
-        let shape = SharedShape::cuboid(scale.x, scale.y, scale.z);
-        let collider = Self::default_collider(shape);
-        let phys_handle = World::instance()
-            .physics
-            .collider_set
-            .insert(collider.clone());
-
+        // the collider is only created in init, where the world is around
         Collider3D {
-            phys_handle,
+            phys_handle: ColliderHandle::invalid(),
+            shape: ColliderShape::Cuboid,
+            size: Vector3::repeat(1.0),
+            synced: (ColliderShape::Cuboid, Vector3::repeat(1.0), Vector3::repeat(1.0)),
             linked_to_body: None,
             parent,
         }
     }
 
-    unsafe fn update(&mut self) {
-        let body_comp = (*self.parent).get_component::<RigidBodyComponent>();
+    unsafe fn init(&mut self, world: &mut World) {
+        let scale = self.parent.transform.scale();
+        self.synced = (self.shape, self.size, scale);
+        self.phys_handle = world.physics.collider_set.insert(Self::default_collider(self.make_shape(scale)));
+    }
+
+    unsafe fn update(&mut self, world: &mut World) {
+        self.sync_shape(world);
+
+        let body_comp = self.parent.get().and_then(|obj| obj.get_component::<RigidBodyComponent>());
         if let Some(body_comp) = body_comp {
             if self.linked_to_body.is_none() {
-                self.link_to_rigid_body(Some(body_comp.borrow().body_handle));
-                let coll = self.get_collider_mut()
+                self.link_to_rigid_body(world, Some(body_comp.borrow().body_handle));
+                let coll = self.get_collider_mut(world)
                     .unwrap();
                 coll.set_translation(Vector3::zeros());
                 coll.set_rotation(Rotation::identity());
-                // TODO: Sync Scale to coll
             } // the linked rigid body will control the collider or
         } else {
             // the collider just takes on the parent transformations
             let translation = self.parent.transform.position();
             let rotation = self.parent.transform.rotation();
-            let coll = self.get_collider_mut()
+            let coll = self.get_collider_mut(world)
                 .unwrap();
             coll.set_translation(translation);
             coll.set_rotation(rotation);
-            // TODO: Sync Scale to coll
         }
     }
 
+    fn on_enable(&mut self, world: &mut World) {
+        if let Some(coll) = world.physics.collider_set.get_mut(self.phys_handle) {
+            coll.set_enabled(true);
+        }
+    }
+
+    fn on_disable(&mut self, world: &mut World) {
+        if let Some(coll) = world.physics.collider_set.get_mut(self.phys_handle) {
+            coll.set_enabled(false);
+        }
+    }
+
+    fn on_destroy(&mut self, world: &mut World) {
+        world.physics.remove_collider(self.phys_handle);
+    }
+
     unsafe fn get_parent(&self) -> GameObjectId {
         self.parent
     }
 }
 
+reflect_component!(Collider3D, "Collider3D", {
+    shape: Enum,
+    size: Vector3,
+});
+
 impl Collider3D {
-    pub fn get_collider(&self) -> Option<&Collider> {
-        World::instance().physics.collider_set.get(self.phys_handle)
+    pub fn get_collider<'a>(&self, world: &'a World) -> Option<&'a Collider> {
+        world.physics.collider_set.get(self.phys_handle)
+    }
+
+    pub fn get_collider_mut<'a>(&self, world: &'a mut World) -> Option<&'a mut Collider> {
+        world.physics.collider_set.get_mut(self.phys_handle)
     }
 
-    pub fn get_collider_mut(&mut self) -> Option<&mut Collider> {
-        World::instance()
-            .physics
-            .collider_set
-            .get_mut(self.phys_handle)
+    fn make_shape(&self, scale: Vector3<f32>) -> SharedShape {
+        let size = self.size.component_mul(&scale);
+        match self.shape {
+            ColliderShape::Cuboid => SharedShape::cuboid(size.x, size.y, size.z),
+            ColliderShape::Ball => SharedShape::ball(size.x),
+            ColliderShape::CapsuleY => SharedShape::capsule_y(size.y, size.x),
+        }
+    }
+
+    // The saveable shape of a rapier collider with its scaled size
+    fn read_shape(shape: &dyn Shape) -> Option<(ColliderShape, Vector3<f32>)> {
+        if let Some(cuboid) = shape.as_cuboid() {
+            Some((ColliderShape::Cuboid, cuboid.half_extents))
+        } else if let Some(ball) = shape.as_ball() {
+            Some((ColliderShape::Ball, Vector3::repeat(ball.radius)))
+        } else {
+            let capsule = shape.as_capsule()?;
+            Some((ColliderShape::CapsuleY, Vector3::new(capsule.radius, capsule.half_height(), capsule.radius)))
+        }
+    }
+
+    // Pushes changed fields and a changed object scale to the rapier collider, or reads back a
+    // shape that was set on it
+    fn sync_shape(&mut self, world: &mut World) {
+        let scale = self.parent.transform.scale();
+        let (synced_shape, synced_size, synced_scale) = self.synced;
+        let Some(coll) = world.physics.collider_set.get_mut(self.phys_handle) else {
+            return;
+        };
+
+        if (self.shape, self.size) == (synced_shape, synced_size) {
+            let Some((shape, scaled_size)) = Self::read_shape(coll.shape()) else {
+                // shapes that can't be saved, like meshes, are left as they are
+                self.synced.2 = scale;
+                return;
+            };
+            self.shape = shape;
+            self.size = scaled_size.component_div(&synced_scale);
+            if scale == synced_scale {
+                self.synced = (self.shape, self.size, scale);
+                return;
+            }
+        }
+
+        coll.set_shape(self.make_shape(scale));
+        self.synced = (self.shape, self.size, scale);
     }
 
     fn default_collider(shape: SharedShape) -> Collider {
@@ -80,9 +169,7 @@ impl Collider3D {
             .build()
     }
 
-    pub fn link_to_rigid_body(&mut self, h_body: Option<RigidBodyHandle>) {
-        let world = World::instance();
-
+    pub fn link_to_rigid_body(&mut self, world: &mut World, h_body: Option<RigidBodyHandle>) {
         world.physics.collider_set.set_parent(
             self.phys_handle,
             h_body,
@@ -91,24 +178,35 @@ impl Collider3D {
 
         self.linked_to_body = h_body;
     }
+
+    // Detaches the collider from the body, if it's attached to it
+    pub fn unlink_from_rigid_body(&mut self, world: &mut World, h_body: RigidBodyHandle) {
+        if self.linked_to_body == Some(h_body) {
+            self.link_to_rigid_body(world, None);
+        }
+    }
+
+    pub fn linked_rigid_body(&self) -> Option<RigidBodyHandle> {
+        self.linked_to_body
+    }
 }
 
 pub trait MeshShapeExtra<T> {
-    fn mesh(mesh: MeshId) -> Option<T>;
-    fn mesh_convex_hull(mesh: MeshId) -> Option<SharedShape>;
+    fn mesh(mesh: MeshId, world: &World) -> Option<T>;
+    fn mesh_convex_hull(mesh: MeshId, world: &World) -> Option<SharedShape>;
 }
 
 impl MeshShapeExtra<SharedShape> for SharedShape {
-    fn mesh(mesh: MeshId) -> Option<SharedShape> {
-        let mesh = World::instance().assets.meshes.get_raw_mesh(mesh)?;
+    fn mesh(mesh: MeshId, world: &World) -> Option<SharedShape> {
+        let mesh = world.assets.meshes.get_raw_mesh(mesh)?;
         debug!("Loading collider mesh with {} vertices", mesh.data.vertices.len());
         let vertices = mesh.data.make_point_cloud();
         let indices = mesh.data.make_triangle_indices();
         Some(SharedShape::trimesh(vertices, indices))
     }
 
-    fn mesh_convex_hull(mesh: MeshId) -> Option<SharedShape> {
-        let mesh = World::instance().assets.meshes.get_raw_mesh(mesh)?;
+    fn mesh_convex_hull(mesh: MeshId, world: &World) -> Option<SharedShape> {
+        let mesh = world.assets.meshes.get_raw_mesh(mesh)?;
         let vertices = mesh.data.make_point_cloud();
         SharedShape::convex_hull(&vertices)
     }
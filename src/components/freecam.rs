@@ -3,6 +3,7 @@ use num_traits::Zero;
 use winit::keyboard::KeyCode;
 use crate::components::Component;
 use crate::object::GameObjectId;
+use crate::reflect_component;
 use crate::world::World;
 
 pub struct FreecamController {
@@ -27,11 +28,11 @@ impl Component for FreecamController {
 		}
 	}
 
-	unsafe fn update(&mut self) {
-		let delta_time = World::instance().get_delta_time().as_secs_f32();
+	unsafe fn update(&mut self, world: &mut World) {
+		let delta_time = world.get_delta_time().as_secs_f32();
 		let transform = &mut self.get_parent().transform;
 		
-		let input = &World::instance().input;
+		let input = &world.input;
 
 		let mouse_delta = input.get_mouse_delta(); 
 		self.yaw += mouse_delta.x * self.look_sensitivity / 30.0;
@@ -81,3 +82,8 @@ impl Component for FreecamController {
 		self.parent
 	}
 }
+
+reflect_component!(FreecamController, "Freecam", {
+	move_speed: Float,
+	look_sensitivity: Float,
+});
@@ -2,6 +2,7 @@ use nalgebra::Vector3;
 
 use crate::components::Component;
 use crate::object::GameObjectId;
+use crate::reflect_component;
 use crate::world::World;
 
 pub struct GravityComp {
@@ -21,8 +22,8 @@ impl Component for GravityComp {
         }
     }
 
-    unsafe fn update(&mut self) {
-        let delta_time = World::instance().get_delta_time().as_secs_f32();
+    unsafe fn update(&mut self, world: &mut World) {
+        let delta_time = world.get_delta_time().as_secs_f32();
 
         self.velocity = (self.velocity - self.acceleration_per_sec * delta_time)
             .clamp(-self.max_acceleration, self.max_acceleration);
@@ -34,3 +35,8 @@ impl Component for GravityComp {
         self.parent
     }
 }
+
+reflect_component!(GravityComp, "Gravity", {
+    acceleration_per_sec: Float,
+    max_acceleration: Float,
+});
@@ -0,0 +1,315 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Isometry3, Matrix4, Orthographic3, Perspective3, Point3, Vector3};
+
+use crate::components::Component;
+use crate::object::GameObjectId;
+use crate::reflect_component;
+use crate::utils::math::OPENGL_TO_WGPU_MATRIX;
+use crate::world::World;
+
+// Has to match MAX_LIGHTS in shader3d.wgsl
+pub const MAX_LIGHTS: usize = 16;
+
+pub const LIGHT_TYPE_DIRECTIONAL: u32 = 0;
+pub const LIGHT_TYPE_POINT: u32 = 1;
+pub const LIGHT_TYPE_SPOT: u32 = 2;
+
+// How many lights can cast shadows at the same time. Has to match the shadow map layers.
+pub const MAX_SHADOWS: usize = 4;
+
+// Lights the whole world from the direction its object is facing (forward, -Z).
+// Shadows are only rendered within `shadow_distance` around the active camera.
+pub struct DirectionalLight {
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub cast_shadows: bool,
+    pub shadow_distance: f32,
+    parent: GameObjectId,
+}
+
+// Lights everything around its object up to `range`
+pub struct PointLight {
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub range: f32,
+    parent: GameObjectId,
+}
+
+// Lights a cone in the direction its object is facing. Angles are in degrees from the center of
+// the cone. The light fades out between the inner and the outer angle.
+pub struct SpotLight {
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub cast_shadows: bool,
+    parent: GameObjectId,
+}
+
+// A single light like the 3D shader sees it
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct LightData {
+    position: Vector3<f32>,
+    light_type: u32,
+    direction: Vector3<f32>,
+    range: f32,
+    color: Vector3<f32>,
+    intensity: f32,
+    inner_cos: f32,
+    outer_cos: f32,
+    // layer of the shadow map, or -1 if the light doesn't cast shadows
+    shadow_index: i32,
+    _padding: f32,
+    shadow_view_proj: Matrix4<f32>,
+}
+
+// All lights of a frame, bound to group 3 of the 3D pipeline
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct LightUniform {
+    ambient: Vector3<f32>,
+    count: u32,
+    lights: [LightData; MAX_LIGHTS],
+    shadow_count: u32,
+    _padding: [u32; 3],
+}
+
+impl LightData {
+    fn new(parent: GameObjectId, light_type: u32, color: Vector3<f32>, intensity: f32) -> LightData {
+        LightData {
+            position: parent.transform.position(),
+            light_type,
+            direction: parent.transform.forward().normalize(),
+            range: 0.0,
+            color,
+            intensity,
+            inner_cos: 0.0,
+            outer_cos: 0.0,
+            shadow_index: -1,
+            _padding: 0.0,
+            shadow_view_proj: Matrix4::identity(),
+        }
+    }
+}
+
+impl LightUniform {
+    pub fn empty() -> Self {
+        LightUniform {
+            ambient: Vector3::zeros(),
+            count: 0,
+            lights: [LightData::zeroed(); MAX_LIGHTS],
+            shadow_count: 0,
+            _padding: [0; 3],
+        }
+    }
+
+    // Collects all enabled lights of the world. Lights beyond MAX_LIGHTS are ignored, and only
+    // the first MAX_SHADOWS lights that cast shadows get a shadow map.
+    pub fn update(&mut self, world: &World) {
+        self.ambient = world.ambient_light;
+        self.count = 0;
+        self.shadow_count = 0;
+
+        // directional shadows follow the camera, so they are sharp where the player looks
+        let focus = world
+            .active_camera
+            .filter(|camera| camera.exists())
+            .map(|camera| camera.transform.position())
+            .unwrap_or_else(Vector3::zeros);
+
+        for light in world.get_components::<DirectionalLight>() {
+            if !light.is_enabled() {
+                continue;
+            }
+            let light = light.borrow();
+            let shadow = light.cast_shadows.then(|| light.shadow_view_proj(focus));
+            self.push(light.light_data(), shadow);
+        }
+
+        for light in world.get_components::<PointLight>() {
+            if light.is_enabled() {
+                self.push(light.borrow().light_data(), None);
+            }
+        }
+
+        for light in world.get_components::<SpotLight>() {
+            if !light.is_enabled() {
+                continue;
+            }
+            let light = light.borrow();
+            let shadow = light.cast_shadows.then(|| light.shadow_view_proj());
+            self.push(light.light_data(), shadow);
+        }
+    }
+
+    fn push(&mut self, mut light: LightData, shadow_view_proj: Option<Matrix4<f32>>) {
+        if self.light_count() >= MAX_LIGHTS {
+            return;
+        }
+
+        if let Some(view_proj) = shadow_view_proj {
+            if self.shadow_count() < MAX_SHADOWS {
+                light.shadow_index = self.shadow_count as i32;
+                light.shadow_view_proj = view_proj;
+                self.shadow_count += 1;
+            }
+        }
+
+        self.lights[self.count as usize] = light;
+        self.count += 1;
+    }
+
+    pub fn light_count(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn shadow_count(&self) -> usize {
+        self.shadow_count as usize
+    }
+
+    // The view projection matrix of every shadow map layer that's in use, ordered by layer
+    pub fn shadow_view_projs(&self) -> Vec<Matrix4<f32>> {
+        let mut matrices = vec![Matrix4::identity(); self.shadow_count()];
+        for light in &self.lights[..self.light_count()] {
+            if light.shadow_index >= 0 {
+                matrices[light.shadow_index as usize] = light.shadow_view_proj;
+            }
+        }
+        matrices
+    }
+}
+
+// Looks from `eye` into `direction`, picking an up vector that isn't parallel to it
+fn look_in_direction(eye: Vector3<f32>, direction: Vector3<f32>) -> Isometry3<f32> {
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    let eye = Point3::from(eye);
+    Isometry3::look_at_rh(&eye, &(eye + direction), &up)
+}
+
+impl DirectionalLight {
+    pub fn light_data(&self) -> LightData {
+        LightData::new(self.parent, LIGHT_TYPE_DIRECTIONAL, self.color, self.intensity)
+    }
+
+    // Covers a box of shadow_distance around `focus`, with some room for casters behind it
+    pub fn shadow_view_proj(&self, focus: Vector3<f32>) -> Matrix4<f32> {
+        let direction = self.parent.transform.forward().normalize();
+        let distance = self.shadow_distance.max(0.1);
+        let view = look_in_direction(focus - direction * distance * 2.0, direction);
+        let projection = Orthographic3::new(-distance, distance, -distance, distance, 0.0, distance * 4.0);
+
+        OPENGL_TO_WGPU_MATRIX * projection.to_homogeneous() * view.to_homogeneous()
+    }
+}
+
+impl PointLight {
+    pub fn light_data(&self) -> LightData {
+        LightData {
+            range: self.range,
+            ..LightData::new(self.parent, LIGHT_TYPE_POINT, self.color, self.intensity)
+        }
+    }
+}
+
+impl SpotLight {
+    pub fn light_data(&self) -> LightData {
+        LightData {
+            range: self.range,
+            inner_cos: self.inner_angle.min(self.outer_angle).to_radians().cos(),
+            outer_cos: self.outer_angle.to_radians().cos(),
+            ..LightData::new(self.parent, LIGHT_TYPE_SPOT, self.color, self.intensity)
+        }
+    }
+
+    pub fn shadow_view_proj(&self) -> Matrix4<f32> {
+        let transform = &self.parent.transform;
+        let view = look_in_direction(transform.position(), transform.forward().normalize());
+        let fov = (self.outer_angle * 2.0).clamp(1.0, 170.0).to_radians();
+        let projection = Perspective3::new(1.0, fov, 0.05, self.range.max(0.1));
+
+        OPENGL_TO_WGPU_MATRIX * projection.to_homogeneous() * view.to_homogeneous()
+    }
+}
+
+impl Component for DirectionalLight {
+    unsafe fn new(parent: GameObjectId) -> Self {
+        DirectionalLight {
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            cast_shadows: true,
+            shadow_distance: 50.0,
+            parent,
+        }
+    }
+
+    unsafe fn get_parent(&self) -> GameObjectId {
+        self.parent
+    }
+}
+
+impl Component for PointLight {
+    unsafe fn new(parent: GameObjectId) -> Self {
+        PointLight {
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            range: 10.0,
+            parent,
+        }
+    }
+
+    unsafe fn get_parent(&self) -> GameObjectId {
+        self.parent
+    }
+}
+
+impl Component for SpotLight {
+    unsafe fn new(parent: GameObjectId) -> Self {
+        SpotLight {
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            range: 20.0,
+            inner_angle: 25.0,
+            outer_angle: 35.0,
+            cast_shadows: true,
+            parent,
+        }
+    }
+
+    unsafe fn get_parent(&self) -> GameObjectId {
+        self.parent
+    }
+}
+
+reflect_component!(DirectionalLight, "DirectionalLight", {
+    color: Vector3,
+    intensity: Float,
+    cast_shadows: Bool,
+    shadow_distance: Float,
+});
+
+reflect_component!(PointLight, "PointLight", {
+    color: Vector3,
+    intensity: Float,
+    range: Float,
+});
+
+reflect_component!(SpotLight, "SpotLight", {
+    color: Vector3,
+    intensity: Float,
+    range: Float,
+    inner_angle: Float,
+    outer_angle: Float,
+    cast_shadows: Bool,
+});
+
+unsafe impl Zeroable for LightData {}
+unsafe impl Pod for LightData {}
+
+unsafe impl Zeroable for LightUniform {}
+unsafe impl Pod for LightUniform {}
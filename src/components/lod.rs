@@ -0,0 +1,383 @@
+use crate::asset_management::meshmanager::MeshId;
+use crate::components::registry::{FieldInfo, FieldKind, FieldValue, ReflectComponent};
+use crate::components::{CameraComp, Component};
+use crate::object::GameObjectId;
+use crate::reflect_enum;
+use crate::utils::bounds::BoundingSphere;
+use crate::world::World;
+
+// How the distance to the camera is measured against the thresholds of the levels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum LodMetric {
+    // World units between the camera and the object's bounds
+    Distance,
+    // How much of the screen's height the object's bounding sphere covers, from 0 to 1
+    #[default]
+    ScreenSize,
+}
+
+reflect_enum!(LodMetric { Distance, ScreenSize });
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LodLevel {
+    pub mesh: MeshId,
+    // Where this level stops being used: the biggest distance, or the smallest screen size.
+    // Past the threshold of the last level the object isn't drawn at all.
+    pub threshold: f32,
+}
+
+impl LodLevel {
+    pub fn new(mesh: MeshId, threshold: f32) -> Self {
+        LodLevel { mesh, threshold }
+    }
+}
+
+// A level being faded out
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct LodFade {
+    from: usize,
+    progress: f32,
+}
+
+// Switches the mesh of the object's MeshRenderer between the levels, depending on how far away
+// the active camera is. The renderer asks the group for its mesh every frame.
+pub struct LodGroup {
+    // From the most to the least detailed
+    pub levels: Vec<LodLevel>,
+    pub metric: LodMetric,
+    // How far, relative to the threshold, the metric has to go past a threshold before the level
+    // switches back. Keeps objects right at a threshold from flickering between two levels.
+    pub hysteresis: f32,
+    // Seconds two levels are cross-faded while switching, 0 switches at once
+    pub fade_duration: f32,
+    current: Option<usize>,
+    fade: Option<LodFade>,
+    parent: GameObjectId,
+}
+
+impl LodGroup {
+    pub fn set_levels(&mut self, levels: Vec<LodLevel>) {
+        self.levels = levels;
+        self.current = None;
+        self.fade = None;
+    }
+
+    // The index of the level that was selected last, None if the object is hidden
+    pub fn current_level(&self) -> Option<usize> {
+        self.current.filter(|level| *level < self.levels.len())
+    }
+
+    // Picks the level for the active camera, measured against the world space bounds of the most
+    // detailed level, and advances the cross-fade. Returns the mesh to draw, or None if the object
+    // is too far away, and the mesh being faded out with the fade's progress.
+    pub(crate) fn select(&mut self, world: &World, bounds: &BoundingSphere) -> (Option<MeshId>, Option<(MeshId, f32)>) {
+        if self.levels.is_empty() {
+            return (None, None);
+        }
+
+        let level = match self.metric_value(world, bounds) {
+            Some(value) => self.pick_level(value),
+            None => Some(0),
+        };
+
+        if level != self.current {
+            // only switches between two visible levels are faded
+            self.fade = match (self.current, level) {
+                (Some(from), Some(_)) if self.fade_duration > 0.0 && from < self.levels.len() => {
+                    Some(LodFade { from, progress: 0.0 })
+                }
+                _ => None,
+            };
+            self.current = level;
+        }
+
+        if let Some(fade) = &mut self.fade {
+            fade.progress += world.get_delta_time().as_secs_f32() / self.fade_duration;
+            if fade.progress >= 1.0 {
+                self.fade = None;
+            }
+        }
+
+        let mesh = level.map(|level| self.levels[level].mesh);
+        // a fade that just started already shows a bit of the new level, so both never overlap
+        let fading = self
+            .fade
+            .and_then(|fade| Some((self.levels.get(fade.from)?.mesh, fade.progress.max(1.0 / 64.0))));
+        (mesh, fading)
+    }
+
+    fn metric_value(&self, world: &World, bounds: &BoundingSphere) -> Option<f32> {
+        let camera = world.active_camera.filter(|camera| camera.exists())?;
+        let projection = camera.get_component::<CameraComp>()?.borrow().projection;
+        let distance = (bounds.center - camera.transform.position()).norm();
+
+        Some(match self.metric {
+            LodMetric::Distance => (distance - bounds.radius).max(0.0),
+            LodMetric::ScreenSize => {
+                let half_height = projection.view_height_at(distance.max(f32::EPSILON)) * 0.5;
+                bounds.radius / half_height
+            }
+        })
+    }
+
+    // The first level whose threshold the value hasn't passed yet. The hysteresis moves every
+    // threshold away from the side the current level is on. Hidden objects are past all of them.
+    fn pick_level(&self, value: f32) -> Option<usize> {
+        let current = self.current.unwrap_or(self.levels.len());
+        self.levels.iter().enumerate().find_map(|(i, level)| {
+            let current_is_finer = current <= i;
+            let threshold = match (self.metric, current_is_finer) {
+                (LodMetric::Distance, true) | (LodMetric::ScreenSize, false) => level.threshold * (1.0 + self.hysteresis),
+                (LodMetric::Distance, false) | (LodMetric::ScreenSize, true) => level.threshold * (1.0 - self.hysteresis),
+            };
+            let within = match self.metric {
+                LodMetric::Distance => value < threshold,
+                LodMetric::ScreenSize => value >= threshold,
+            };
+            within.then_some(i)
+        })
+    }
+}
+
+impl Component for LodGroup {
+    unsafe fn new(parent: GameObjectId) -> Self {
+        LodGroup {
+            levels: Vec::new(),
+            metric: LodMetric::default(),
+            hysteresis: 0.1,
+            fade_duration: 0.25,
+            current: None,
+            fade: None,
+            parent,
+        }
+    }
+
+    unsafe fn get_parent(&self) -> GameObjectId {
+        self.parent
+    }
+}
+
+// Written out instead of using reflect_component!, as the levels are reflected as two lists, one
+// with the mesh and one with the threshold of every level. Setting the meshes keeps the
+// thresholds of the levels that stay, the thresholds have to match the number of levels.
+impl ReflectComponent for LodGroup {
+    const NAME: &'static str = "LodGroup";
+
+    fn fields() -> &'static [FieldInfo] {
+        const fn field(name: &'static str, kind: FieldKind) -> FieldInfo {
+            FieldInfo { name, kind }
+        }
+        const FIELDS: &[FieldInfo] = &[
+            field("level_meshes", FieldKind::List),
+            field("level_thresholds", FieldKind::List),
+            field("metric", FieldKind::Enum),
+            field("hysteresis", FieldKind::Float),
+            field("fade_duration", FieldKind::Float),
+        ];
+        FIELDS
+    }
+
+    fn get_field(&self, name: &str) -> Option<FieldValue> {
+        Some(match name {
+            "level_meshes" => FieldValue::List(self.levels.iter().map(|level| FieldValue::Mesh(level.mesh)).collect()),
+            "level_thresholds" => {
+                FieldValue::List(self.levels.iter().map(|level| FieldValue::Float(level.threshold)).collect())
+            }
+            "metric" => FieldValue::Enum(self.metric.into()),
+            "hysteresis" => FieldValue::Float(self.hysteresis),
+            "fade_duration" => FieldValue::Float(self.fade_duration),
+            _ => return None,
+        })
+    }
+
+    fn set_field(&mut self, name: &str, value: FieldValue) -> bool {
+        match (name, value) {
+            ("level_meshes", FieldValue::List(values)) => {
+                let meshes: Option<Vec<MeshId>> = values
+                    .iter()
+                    .map(|value| match value {
+                        FieldValue::Mesh(mesh) => Some(*mesh),
+                        _ => None,
+                    })
+                    .collect();
+                let Some(meshes) = meshes else {
+                    return false;
+                };
+                let levels = meshes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, mesh)| LodLevel::new(mesh, self.levels.get(i).map_or(0.0, |level| level.threshold)))
+                    .collect();
+                self.set_levels(levels);
+            }
+            ("level_thresholds", FieldValue::List(values)) => {
+                let thresholds: Option<Vec<f32>> = values.iter().map(FieldValue::as_float).collect();
+                match thresholds {
+                    Some(thresholds) if thresholds.len() == self.levels.len() => {
+                        let levels = self
+                            .levels
+                            .iter()
+                            .zip(thresholds)
+                            .map(|(level, threshold)| LodLevel::new(level.mesh, threshold))
+                            .collect();
+                        self.set_levels(levels);
+                    }
+                    _ => return false,
+                }
+            }
+            ("metric", FieldValue::Enum(metric)) => match metric.try_into() {
+                Ok(metric) => self.metric = metric,
+                Err(_) => return false,
+            },
+            ("hysteresis", FieldValue::Float(hysteresis)) => self.hysteresis = hysteresis,
+            ("fade_duration", FieldValue::Float(duration)) => self.fade_duration = duration,
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nalgebra::Vector3;
+
+    use super::{LodGroup, LodLevel, LodMetric};
+    use crate::asset_management::mesh::Mesh;
+    use crate::buffer::{CUBE, CUBE_INDICES};
+    use crate::scene::SceneDesc;
+    use crate::utils::bounds::BoundingSphere;
+    use crate::utils::testing::{detached_component, world_with_camera};
+    use crate::world::World;
+
+    fn group(world: &mut World, metric: LodMetric, thresholds: [f32; 2]) -> LodGroup {
+        let mut group: LodGroup = detached_component(world, "Lod");
+        group.metric = metric;
+        group.hysteresis = 0.1;
+        group.fade_duration = 0.0;
+        group.set_levels(vec![LodLevel::new(10, thresholds[0]), LodLevel::new(11, thresholds[1])]);
+        group
+    }
+
+    // Picks the level for the value like select does, and keeps it as the current one
+    fn step(group: &mut LodGroup, value: f32) -> Option<usize> {
+        group.current = group.pick_level(value);
+        group.current
+    }
+
+    // A point the given distance in front of the active camera
+    fn at_distance(distance: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: Vector3::new(0.0, 0.0, -distance),
+            radius: 0.0,
+        }
+    }
+
+    #[test]
+    fn distance_switches_past_threshold_with_hysteresis() {
+        let mut world = World::new();
+        let mut group = group(&mut world, LodMetric::Distance, [10.0, 20.0]);
+
+        assert_eq!(step(&mut group, 5.0), Some(0));
+        // within the hysteresis above the threshold, the finer level is kept
+        assert_eq!(step(&mut group, 10.5), Some(0));
+        assert_eq!(step(&mut group, 11.5), Some(1));
+        // and within the hysteresis below it, the coarser one
+        assert_eq!(step(&mut group, 9.5), Some(1));
+        assert_eq!(step(&mut group, 8.5), Some(0));
+
+        assert_eq!(step(&mut group, 25.0), None);
+        assert_eq!(step(&mut group, 19.0), None);
+        assert_eq!(step(&mut group, 17.0), Some(1));
+    }
+
+    #[test]
+    fn screen_size_switches_past_threshold_with_hysteresis() {
+        let mut world = World::new();
+        let mut group = group(&mut world, LodMetric::ScreenSize, [0.5, 0.1]);
+
+        assert_eq!(step(&mut group, 0.6), Some(0));
+        assert_eq!(step(&mut group, 0.47), Some(0));
+        assert_eq!(step(&mut group, 0.44), Some(1));
+        assert_eq!(step(&mut group, 0.52), Some(1));
+        assert_eq!(step(&mut group, 0.56), Some(0));
+
+        assert_eq!(step(&mut group, 0.08), None);
+        assert_eq!(step(&mut group, 0.105), None);
+        assert_eq!(step(&mut group, 0.12), Some(1));
+    }
+
+    #[test]
+    fn select_follows_camera_distance() {
+        let mut world = world_with_camera();
+        let mut group = group(&mut world, LodMetric::Distance, [10.0, 20.0]);
+
+        assert_eq!(group.select(&world, &at_distance(5.0)), (Some(10), None));
+        assert_eq!(group.select(&world, &at_distance(10.5)), (Some(10), None));
+        assert_eq!(group.select(&world, &at_distance(15.0)), (Some(11), None));
+        assert_eq!(group.current_level(), Some(1));
+        assert_eq!(group.select(&world, &at_distance(9.5)), (Some(11), None));
+        assert_eq!(group.select(&world, &at_distance(30.0)), (None, None));
+        assert_eq!(group.current_level(), None);
+        assert_eq!(group.select(&world, &at_distance(5.0)), (Some(10), None));
+    }
+
+    #[test]
+    fn select_by_screen_size_hides_far_objects() {
+        let mut world = world_with_camera();
+        let mut group = group(&mut world, LodMetric::ScreenSize, [0.5, 0.1]);
+        let sphere = |distance| BoundingSphere {
+            radius: 1.0,
+            ..at_distance(distance)
+        };
+
+        assert_eq!(group.select(&world, &sphere(1.0)).0, Some(10));
+        assert_eq!(group.select(&world, &sphere(1000.0)).0, None);
+        assert_eq!(group.select(&world, &sphere(1.0)).0, Some(10));
+    }
+
+    #[test]
+    fn select_cross_fades_between_visible_levels() {
+        let mut world = world_with_camera();
+        let mut group = group(&mut world, LodMetric::Distance, [10.0, 20.0]);
+        group.fade_duration = 0.25;
+        world.tick(Duration::from_millis(100));
+
+        assert_eq!(group.select(&world, &at_distance(5.0)), (Some(10), None));
+        let (mesh, fading) = group.select(&world, &at_distance(15.0));
+        assert_eq!(mesh, Some(11));
+        let (old_mesh, progress) = fading.expect("the old level should be fading out");
+        assert_eq!(old_mesh, 10);
+        assert!((progress - 0.4).abs() < 1e-5);
+
+        group.select(&world, &at_distance(15.0));
+        assert_eq!(group.select(&world, &at_distance(15.0)), (Some(11), None));
+    }
+
+    #[test]
+    fn levels_and_metric_round_trip_through_scene() {
+        let mut world = World::new();
+        let meshes = ["rock.fbx#High", "rock.fbx#Low"].map(|path| {
+            let mesh = Mesh::new(CUBE.to_vec(), Some(CUBE_INDICES.to_vec()), None);
+            let id = world.assets.meshes.add_mesh(mesh);
+            world.assets.meshes.set_mesh_path(id, path);
+            id
+        });
+        let mut obj = world.new_object("Rock");
+        world.add_child(obj);
+        let group = obj.add_component::<LodGroup>();
+        group.metric = LodMetric::Distance;
+        group.set_levels(vec![LodLevel::new(meshes[0], 10.0), LodLevel::new(meshes[1], 20.0)]);
+
+        let text = SceneDesc::from_world(&world).to_ron().unwrap();
+        assert!(text.contains("rock.fbx#Low"), "levels should be saved by mesh path:\n{text}");
+        world.destroy_object(obj);
+        let roots = SceneDesc::from_ron(&text).unwrap().spawn(&mut world).unwrap();
+
+        let group = roots[0].get_component::<LodGroup>().unwrap();
+        let group = group.borrow();
+        assert_eq!(group.metric, LodMetric::Distance);
+        assert_eq!(group.levels, vec![LodLevel::new(meshes[0], 10.0), LodLevel::new(meshes[1], 20.0)]);
+    }
+}
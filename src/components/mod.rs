@@ -1,19 +1,27 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::cell::{BorrowError, BorrowMutError, Cell, Ref, RefCell, RefMut};
+use std::marker::PhantomData;
+use std::rc::Rc;
 
 #[allow(unused_imports)]
 pub use camera::CameraComp;
 #[allow(unused_imports)]
-pub use collider::Collider3D;
+pub use collider::{Collider3D, ColliderShape};
 #[allow(unused_imports)]
 pub use gravity::GravityComp;
 #[allow(unused_imports)]
-pub use rigid_body::RigidBodyComponent;
+pub use rigid_body::{BodyType, RigidBodyComponent};
 #[allow(unused_imports)]
 pub use rotate::RotateComponent;
 #[allow(unused_imports)]
 pub use freecam::FreecamController;
+#[allow(unused_imports)]
+pub use light::{DirectionalLight, PointLight, SpotLight};
+#[allow(unused_imports)]
+pub use lod::{LodGroup, LodLevel, LodMetric};
 
-use crate::object::GameObjectId;
+use crate::object::{GameObject, GameObjectId};
+use crate::world::World;
 
 pub mod camera;
 pub mod collider;
@@ -21,6 +29,9 @@ pub mod gravity;
 pub mod rigid_body;
 pub mod rotate;
 pub mod freecam;
+pub mod light;
+pub mod lod;
+pub mod registry;
 
 // TODO: resolve unsafe hell
 pub trait Component: Any {
@@ -29,17 +40,179 @@ pub trait Component: Any {
         Self: Sized;
     
     // Gets called when the game object is created directly after new
-    unsafe fn init(&mut self) {}
+    unsafe fn init(&mut self, _world: &mut World) {}
+
+    // Gets called after init and whenever the component gets enabled again
+    fn on_enable(&mut self, _world: &mut World) {}
+
+    // Gets called when the component gets disabled, and before on_destroy if it was enabled
+    fn on_disable(&mut self, _world: &mut World) {}
     
     // Gets called when the component should update anything state related
-    unsafe fn update(&mut self) {}
+    unsafe fn update(&mut self, _world: &mut World) {}
     
     // Gets called when the component should update any state that's necessary for physics
-    unsafe fn late_update(&mut self) {}
+    unsafe fn late_update(&mut self, _world: &mut World) {}
+
+    // Gets called once per physics step, right before the step. Might run zero or multiple times per frame.
+    fn fixed_update(&mut self, _world: &mut World) {}
 
     // Gets called after physics have evolved
-    unsafe fn post_update(&mut self) {}
+    unsafe fn post_update(&mut self, _world: &mut World) {}
+
+    // Gets called right before the game object is removed from the world.
+    // Release anything the component owns outside the object, like physics handles, here.
+    fn on_destroy(&mut self, _world: &mut World) {}
 
     #[allow(clippy::mut_from_ref)]
     unsafe fn get_parent(&self) -> GameObjectId;
 }
+
+// A component attached to a game object, together with its enabled state.
+// Clones refer to the same component and state.
+#[derive(Clone)]
+pub struct ComponentSlot {
+    pub component: Rc<RefCell<Box<dyn Component>>>,
+    enabled: Rc<Cell<bool>>,
+}
+
+impl ComponentSlot {
+    pub(crate) fn new(component: Box<dyn Component>) -> Self {
+        ComponentSlot {
+            component: Rc::new(RefCell::new(component)),
+            enabled: Rc::new(Cell::new(true)),
+        }
+    }
+
+    pub fn component_type(&self) -> TypeId {
+        unsafe { (**self.component.as_ptr()).type_id() }
+    }
+
+    pub fn is<C: Component>(&self) -> bool {
+        self.component_type() == TypeId::of::<C>()
+    }
+
+    // Returns a typed handle if the component is a C
+    pub fn downcast<C: Component>(&self) -> Option<ComponentRef<C>> {
+        if !self.is::<C>() {
+            return None;
+        }
+        Some(ComponentRef {
+            slot: self.clone(),
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    // Disables the component and gives it the chance to clean up
+    pub(crate) unsafe fn destroy(&self, world: &mut World) {
+        if self.is_enabled() {
+            self.enabled.set(false);
+            (**self.component.as_ptr()).on_disable(world);
+        }
+        (**self.component.as_ptr()).on_destroy(world);
+    }
+
+    // Disabled components don't receive any update callbacks
+    pub fn set_enabled(&self, enabled: bool) {
+        if self.enabled.get() == enabled {
+            return;
+        }
+        self.enabled.set(enabled);
+
+        unsafe {
+            let comp = &mut **self.component.as_ptr();
+            let parent = comp.get_parent();
+            let world = &mut *parent.world();
+            if enabled {
+                comp.on_enable(world);
+            } else {
+                comp.on_disable(world);
+            }
+        }
+    }
+}
+
+// A typed handle to a component of a game object
+pub struct ComponentRef<C: Component> {
+    slot: ComponentSlot,
+    _marker: PhantomData<C>,
+}
+
+impl<C: Component> Clone for ComponentRef<C> {
+    fn clone(&self) -> Self {
+        ComponentRef {
+            slot: self.slot.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Component> ComponentRef<C> {
+    pub fn borrow(&self) -> Ref<'_, C> {
+        Ref::map(self.slot.component.borrow(), |comp| Self::cast(comp.as_ref()))
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, C> {
+        RefMut::map(self.slot.component.borrow_mut(), |comp| Self::cast_mut(comp.as_mut()))
+    }
+
+    pub fn try_borrow(&self) -> Result<Ref<'_, C>, BorrowError> {
+        Ok(Ref::map(self.slot.component.try_borrow()?, |comp| Self::cast(comp.as_ref())))
+    }
+
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, C>, BorrowMutError> {
+        Ok(RefMut::map(self.slot.component.try_borrow_mut()?, |comp| {
+            Self::cast_mut(comp.as_mut())
+        }))
+    }
+
+    pub fn slot(&self) -> &ComponentSlot {
+        &self.slot
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.slot.is_enabled()
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.slot.set_enabled(enabled)
+    }
+
+    // the type was checked when the handle was made, so these can't fail
+    fn cast(comp: &dyn Component) -> &C {
+        (comp as &dyn Any).downcast_ref::<C>().unwrap()
+    }
+
+    fn cast_mut(comp: &mut dyn Component) -> &mut C {
+        (comp as &mut dyn Any).downcast_mut::<C>().unwrap()
+    }
+}
+
+// A set of component types to look up on game objects at once, like (RigidBodyComponent, Collider3D)
+pub trait ComponentQuery {
+    type Item;
+
+    // None unless the object has every component of the set
+    fn fetch(object: &GameObject) -> Option<Self::Item>;
+}
+
+macro_rules! impl_component_query {
+    ($($comp:ident),+) => {
+        impl<$($comp: Component),+> ComponentQuery for ($($comp,)+) {
+            type Item = ($(ComponentRef<$comp>,)+);
+
+            fn fetch(object: &GameObject) -> Option<Self::Item> {
+                Some(($(object.get_component::<$comp>()?,)+))
+            }
+        }
+    };
+}
+
+impl_component_query!(A);
+impl_component_query!(A, B);
+impl_component_query!(A, B, C);
+impl_component_query!(A, B, C, D);
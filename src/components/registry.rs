@@ -0,0 +1,385 @@
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+
+use log::warn;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+use crate::asset_management::materialmanager::MaterialId;
+use crate::asset_management::meshmanager::MeshId;
+use crate::asset_management::texturemanager::TextureId;
+use crate::components::{
+    CameraComp, Collider3D, Component, ComponentSlot, DirectionalLight, FreecamController,
+    GravityComp, LodGroup, PointLight, RigidBodyComponent, RotateComponent, SpotLight,
+};
+use crate::object::GameObject;
+
+// A single value of an inspectable component field.
+// Asset ids are only valid for the assets of the running world.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Vector3(Vector3<f32>),
+    Mesh(MeshId),
+    Material(MaterialId),
+    Texture(TextureId),
+    // The variant name of an enum field
+    Enum(String),
+    // Several values of the same kind, like the meshes of all levels of a LodGroup
+    List(Vec<FieldValue>),
+    // How mesh fields are stored in scene files
+    MeshPath(String),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum FieldKind {
+    Float,
+    Int,
+    Bool,
+    Vector3,
+    Mesh,
+    Material,
+    Texture,
+    Enum,
+    List,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub kind: FieldKind,
+}
+
+impl FieldValue {
+    pub fn kind(&self) -> Option<FieldKind> {
+        Some(match self {
+            FieldValue::Float(_) => FieldKind::Float,
+            FieldValue::Int(_) => FieldKind::Int,
+            FieldValue::Bool(_) => FieldKind::Bool,
+            FieldValue::Vector3(_) => FieldKind::Vector3,
+            FieldValue::Mesh(_) => FieldKind::Mesh,
+            FieldValue::Material(_) => FieldKind::Material,
+            FieldValue::Texture(_) => FieldKind::Texture,
+            FieldValue::Enum(_) => FieldKind::Enum,
+            FieldValue::List(_) => FieldKind::List,
+            FieldValue::MeshPath(_) => return None,
+        })
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            FieldValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            FieldValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector3(&self) -> Option<Vector3<f32>> {
+        match self {
+            FieldValue::Vector3(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+// Sorted by name, so saved files stay diffable
+pub type FieldMap = BTreeMap<String, FieldValue>;
+
+// Components with a stable name and fields that can be inspected and changed at runtime.
+// Usually implemented with the reflect_component! macro.
+pub trait ReflectComponent: Component {
+    // Stable name the component type is known by. Don't change it once files use it.
+    const NAME: &'static str;
+
+    fn fields() -> &'static [FieldInfo] {
+        &[]
+    }
+
+    fn get_field(&self, _name: &str) -> Option<FieldValue> {
+        None
+    }
+
+    // Returns false if there is no such field or the value has the wrong kind
+    fn set_field(&mut self, _name: &str, _value: FieldValue) -> bool {
+        false
+    }
+}
+
+// Implements ReflectComponent by listing the fields and their kind:
+// reflect_component!(GravityComp, "Gravity", { acceleration_per_sec: Float });
+// Fields of kind Enum need the enum to be declared with reflect_enum!.
+#[macro_export]
+macro_rules! reflect_component {
+    ($comp:ty, $name:literal) => {
+        impl $crate::components::registry::ReflectComponent for $comp {
+            const NAME: &'static str = $name;
+        }
+    };
+    ($comp:ty, $name:literal, { $($field:ident: $kind:ident),+ $(,)? }) => {
+        impl $crate::components::registry::ReflectComponent for $comp {
+            const NAME: &'static str = $name;
+
+            fn fields() -> &'static [$crate::components::registry::FieldInfo] {
+                &[$($crate::components::registry::FieldInfo {
+                    name: stringify!($field),
+                    kind: $crate::components::registry::FieldKind::$kind,
+                }),+]
+            }
+
+            fn get_field(&self, name: &str) -> Option<$crate::components::registry::FieldValue> {
+                match name {
+                    $(stringify!($field) => Some($crate::components::registry::FieldValue::$kind(
+                        self.$field.clone().into(),
+                    )),)+
+                    _ => None,
+                }
+            }
+
+            fn set_field(&mut self, name: &str, value: $crate::components::registry::FieldValue) -> bool {
+                match (name, value) {
+                    $((stringify!($field), $crate::components::registry::FieldValue::$kind(value)) => {
+                        match value.try_into() {
+                            Ok(value) => {
+                                self.$field = value;
+                                true
+                            }
+                            Err(_) => false,
+                        }
+                    })+
+                    _ => false,
+                }
+            }
+        }
+    };
+}
+
+// Lets a fieldless enum be used as a reflected field of kind Enum, stored by variant name:
+// reflect_enum!(BodyType { Dynamic, Fixed });
+#[macro_export]
+macro_rules! reflect_enum {
+    ($enum:ident { $($variant:ident),+ $(,)? }) => {
+        impl From<$enum> for String {
+            fn from(value: $enum) -> String {
+                match value {
+                    $($enum::$variant => stringify!($variant).to_owned(),)+
+                }
+            }
+        }
+
+        impl TryFrom<String> for $enum {
+            type Error = String;
+
+            fn try_from(value: String) -> Result<Self, String> {
+                match value.as_str() {
+                    $(stringify!($variant) => Ok($enum::$variant),)+
+                    _ => Err(value),
+                }
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy)]
+pub struct ComponentRegistration {
+    pub name: &'static str,
+    pub type_id: TypeId,
+    fields: fn() -> &'static [FieldInfo],
+    create: fn(&mut GameObject) -> Option<ComponentSlot>,
+    get: fn(&dyn Component, &str) -> Option<FieldValue>,
+    set: fn(&mut dyn Component, &str, FieldValue) -> bool,
+}
+
+impl ComponentRegistration {
+    pub fn fields(&self) -> &'static [FieldInfo] {
+        (self.fields)()
+    }
+
+    // Adds a default constructed component of this type to the object
+    pub fn create(&self, object: &mut GameObject) -> Option<ComponentSlot> {
+        (self.create)(object)
+    }
+
+    // None while the component is mutably borrowed through a ComponentRef
+    pub fn get_field(&self, slot: &ComponentSlot, name: &str) -> Option<FieldValue> {
+        if slot.component_type() != self.type_id {
+            return None;
+        }
+        let component = slot.component.try_borrow().ok()?;
+        (self.get)(&**component, name)
+    }
+
+    // False while the component is borrowed through a ComponentRef
+    pub fn set_field(&self, slot: &ComponentSlot, name: &str, value: FieldValue) -> bool {
+        if slot.component_type() != self.type_id {
+            return false;
+        }
+        let Ok(mut component) = slot.component.try_borrow_mut() else {
+            return false;
+        };
+        (self.set)(&mut **component, name, value)
+    }
+
+    pub fn save_fields(&self, slot: &ComponentSlot) -> FieldMap {
+        self.fields()
+            .iter()
+            .filter_map(|field| Some((field.name.to_owned(), self.get_field(slot, field.name)?)))
+            .collect()
+    }
+
+    // Unknown fields or values of the wrong kind are skipped, missing ones keep their value
+    pub fn load_fields(&self, slot: &ComponentSlot, fields: &FieldMap) {
+        for (name, value) in fields {
+            if !self.set_field(slot, name, value.clone()) {
+                warn!("Couldn't set field \"{name}\" of component \"{}\" to {value:?}", self.name);
+            }
+        }
+    }
+}
+
+pub struct ComponentRegistry {
+    registrations: HashMap<&'static str, ComponentRegistration>,
+    names: HashMap<TypeId, &'static str>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> ComponentRegistry {
+        let mut registry = ComponentRegistry {
+            registrations: HashMap::new(),
+            names: HashMap::new(),
+        };
+
+        registry.register::<CameraComp>();
+        registry.register::<Collider3D>();
+        registry.register::<RigidBodyComponent>();
+        registry.register::<GravityComp>();
+        registry.register::<RotateComponent>();
+        registry.register::<FreecamController>();
+        registry.register::<DirectionalLight>();
+        registry.register::<PointLight>();
+        registry.register::<SpotLight>();
+        registry.register::<LodGroup>();
+
+        registry
+    }
+
+    pub fn register<C: ReflectComponent>(&mut self) {
+        let registration = ComponentRegistration {
+            name: C::NAME,
+            type_id: TypeId::of::<C>(),
+            fields: C::fields,
+            create: |object| {
+                // looked up by address, as the component's hooks might have added others after it
+                let component: *const C = object.add_component::<C>();
+                object
+                    .components
+                    .iter()
+                    .find(|slot| {
+                        let other: *const dyn Component = unsafe { &**slot.component.as_ptr() };
+                        std::ptr::addr_eq(other, component)
+                    })
+                    .cloned()
+            },
+            get: |component, name| {
+                (component as &dyn Any).downcast_ref::<C>()?.get_field(name)
+            },
+            set: |component, name, value| {
+                (component as &mut dyn Any)
+                    .downcast_mut::<C>()
+                    .is_some_and(|component| component.set_field(name, value))
+            },
+        };
+
+        if let Some(old) = self.registrations.insert(C::NAME, registration) {
+            warn!("Component name \"{}\" was registered twice", C::NAME);
+            self.names.remove(&old.type_id);
+        }
+        self.names.insert(registration.type_id, C::NAME);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ComponentRegistration> {
+        self.registrations.get(name)
+    }
+
+    pub fn get_by_type(&self, type_id: TypeId) -> Option<&ComponentRegistration> {
+        self.registrations.get(self.names.get(&type_id)?)
+    }
+
+    pub fn get_for_slot(&self, slot: &ComponentSlot) -> Option<&ComponentRegistration> {
+        self.get_by_type(slot.component_type())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.registrations.keys().copied()
+    }
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::{ComponentRegistry, FieldValue};
+    use crate::components::{Component, GravityComp};
+    use crate::object::GameObjectId;
+    use crate::reflect_component;
+    use crate::world::World;
+
+    // Adds another component to its object once it's enabled
+    struct Spawner {
+        value: f32,
+        parent: GameObjectId,
+    }
+
+    impl Component for Spawner {
+        unsafe fn new(parent: GameObjectId) -> Self {
+            Spawner { value: 0.0, parent }
+        }
+
+        fn on_enable(&mut self, _world: &mut World) {
+            let mut parent = self.parent;
+            parent.add_component::<GravityComp>();
+        }
+
+        unsafe fn get_parent(&self) -> GameObjectId {
+            self.parent
+        }
+    }
+
+    reflect_component!(Spawner, "Spawner", { value: Float });
+
+    #[test]
+    fn create_returns_the_created_component() {
+        let mut world = World::new();
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Spawner>();
+        let mut obj = world.new_object("Spawner");
+
+        let registration = registry.get("Spawner").unwrap();
+        let slot = registration.create(&mut obj).unwrap();
+        assert_eq!(obj.components.len(), 2);
+        assert_eq!(slot.component_type(), TypeId::of::<Spawner>());
+
+        assert!(registration.set_field(&slot, "value", FieldValue::Float(2.0)));
+        assert_eq!(obj.get_component::<Spawner>().unwrap().borrow().value, 2.0);
+    }
+}
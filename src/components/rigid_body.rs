@@ -1,47 +1,94 @@
 use crate::utils::math::QuaternionEuler;
 use rapier3d::prelude::*;
 
-use crate::components::Component;
+use crate::components::{Collider3D, Component};
 use crate::object::GameObjectId;
 use crate::world::World;
+use crate::{reflect_component, reflect_enum};
+
+// Mirrors rapier's RigidBodyType, so it can be saved
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BodyType {
+    Dynamic,
+    Fixed,
+    KinematicPositionBased,
+    KinematicVelocityBased,
+}
+
+reflect_enum!(BodyType { Dynamic, Fixed, KinematicPositionBased, KinematicVelocityBased });
+
+impl From<BodyType> for RigidBodyType {
+    fn from(value: BodyType) -> Self {
+        match value {
+            BodyType::Dynamic => RigidBodyType::Dynamic,
+            BodyType::Fixed => RigidBodyType::Fixed,
+            BodyType::KinematicPositionBased => RigidBodyType::KinematicPositionBased,
+            BodyType::KinematicVelocityBased => RigidBodyType::KinematicVelocityBased,
+        }
+    }
+}
+
+impl From<RigidBodyType> for BodyType {
+    fn from(value: RigidBodyType) -> Self {
+        match value {
+            RigidBodyType::Dynamic => BodyType::Dynamic,
+            RigidBodyType::Fixed => BodyType::Fixed,
+            RigidBodyType::KinematicPositionBased => BodyType::KinematicPositionBased,
+            RigidBodyType::KinematicVelocityBased => BodyType::KinematicVelocityBased,
+        }
+    }
+}
 
 pub struct RigidBodyComponent {
     parent: GameObjectId,
     pub body_handle: RigidBodyHandle,
+    // Changing these updates the body on the next late update. Changes made through
+    // get_body_mut are copied back here, so both stay in sync.
+    pub body_type: BodyType,
+    // mass on top of what the attached colliders add
+    pub additional_mass: f32,
+    // the values the rapier body was last synced with
+    synced: (BodyType, f32),
 }
 
 impl Component for RigidBodyComponent {
     unsafe fn new(parent: GameObjectId) -> Self {
-        let initial_translation = parent.transform.position();
-        let initial_rotation = parent.transform.rotation().euler_vector();
-        let rigid_body = RigidBodyBuilder::dynamic()
-            .translation(initial_translation)
-            .rotation(initial_rotation)
-            .build();
-
-        let body_handle = World::instance().physics.rigid_body_set.insert(rigid_body);
-
+        // the body is only created in init, where the world is around
         RigidBodyComponent {
             parent,
-            body_handle,
+            body_handle: RigidBodyHandle::invalid(),
+            body_type: BodyType::Dynamic,
+            additional_mass: 0.0,
+            synced: (BodyType::Dynamic, 0.0),
         }
     }
 
-    unsafe fn late_update(&mut self) {
-        let rb = World::instance()
+    unsafe fn init(&mut self, world: &mut World) {
+        let transform = &self.parent.transform;
+        let rigid_body = RigidBodyBuilder::new(self.body_type.into())
+            .translation(transform.position())
+            .rotation(transform.rotation().euler_vector())
+            .build();
+
+        self.body_handle = world.physics.rigid_body_set.insert(rigid_body);
+    }
+
+    unsafe fn late_update(&mut self, world: &mut World) {
+        let rb = world
             .physics
             .rigid_body_set
             .get_mut(self.body_handle);
         if let Some(rb) = rb {
             rb.set_translation(self.parent.transform.position(), false);
             rb.set_rotation(self.parent.transform.rotation(), false);
+            self.sync_properties(rb);
         } else {
             todo!("de-synced - remake_rigid_body();")
         }
     }
 
-    unsafe fn post_update(&mut self) {
-        let rb = World::instance()
+    unsafe fn post_update(&mut self, world: &mut World) {
+        let rb = world
             .physics
             .rigid_body_set
             .get_mut(self.body_handle);
@@ -53,23 +100,96 @@ impl Component for RigidBodyComponent {
         }
     }
 
+    fn on_enable(&mut self, world: &mut World) {
+        if let Some(rb) = world.physics.rigid_body_set.get_mut(self.body_handle) {
+            rb.set_enabled(true);
+        }
+    }
+
+    fn on_disable(&mut self, world: &mut World) {
+        if let Some(rb) = world.physics.rigid_body_set.get_mut(self.body_handle) {
+            rb.set_enabled(false);
+        }
+    }
+
+    fn on_destroy(&mut self, world: &mut World) {
+        // colliders of the object follow its transform again, instead of pointing at a dead body
+        let colliders = self
+            .parent
+            .get()
+            .map(|obj| obj.get_components::<Collider3D>())
+            .unwrap_or_default();
+        for collider in colliders {
+            if let Ok(mut collider) = collider.try_borrow_mut() {
+                collider.unlink_from_rigid_body(world, self.body_handle);
+            }
+        }
+        world.physics.remove_rigid_body(self.body_handle);
+    }
+
     unsafe fn get_parent(&self) -> GameObjectId {
         self.parent
     }
 }
 
+reflect_component!(RigidBodyComponent, "RigidBody", {
+    body_type: Enum,
+    additional_mass: Float,
+});
+
 impl RigidBodyComponent {
-    pub fn get_body(&self) -> Option<&RigidBody> {
-        World::instance()
-            .physics
-            .rigid_body_set
-            .get(self.body_handle)
+    pub fn get_body<'a>(&self, world: &'a World) -> Option<&'a RigidBody> {
+        world.physics.rigid_body_set.get(self.body_handle)
     }
 
-    pub fn get_body_mut(&mut self) -> Option<&mut RigidBody> {
-        World::instance()
-            .physics
-            .rigid_body_set
-            .get_mut(self.body_handle)
+    pub fn get_body_mut<'a>(&self, world: &'a mut World) -> Option<&'a mut RigidBody> {
+        world.physics.rigid_body_set.get_mut(self.body_handle)
+    }
+
+    // Pushes changed fields to the rapier body, or reads back what was changed on it
+    fn sync_properties(&mut self, rb: &mut RigidBody) {
+        if self.synced != (self.body_type, self.additional_mass) {
+            rb.set_body_type(self.body_type.into(), true);
+            rb.set_additional_mass(self.additional_mass, true);
+        } else {
+            self.body_type = rb.body_type().into();
+            self.additional_mass = match rb.mass_properties().additional_local_mprops.as_deref() {
+                Some(RigidBodyAdditionalMassProps::Mass(mass)) => *mass,
+                Some(RigidBodyAdditionalMassProps::MassProps(mprops)) => mprops.mass(),
+                None => 0.0,
+            };
+        }
+        self.synced = (self.body_type, self.additional_mass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nalgebra::Vector3;
+
+    use crate::components::{Collider3D, RigidBodyComponent};
+    use crate::world::World;
+
+    #[test]
+    fn collider_follows_transform_after_body_is_removed() {
+        let mut world = World::new();
+        let mut obj = world.new_object("Body");
+        world.add_child(obj);
+        obj.add_component::<RigidBodyComponent>();
+        obj.add_component::<Collider3D>();
+        world.tick(Duration::from_millis(10));
+        let collider = obj.get_component::<Collider3D>().unwrap();
+        assert!(collider.borrow().linked_rigid_body().is_some());
+
+        assert!(obj.remove_component::<RigidBodyComponent>());
+        assert_eq!(collider.borrow().linked_rigid_body(), None);
+        assert_eq!(collider.borrow().get_collider(&world).unwrap().parent(), None);
+
+        obj.transform.set_position(Vector3::new(3.0, 4.0, 5.0));
+        world.tick(Duration::from_millis(10));
+        let translation = *collider.borrow().get_collider(&world).unwrap().translation();
+        assert_eq!(translation, Vector3::new(3.0, 4.0, 5.0));
     }
 }
@@ -3,6 +3,7 @@ use rand::random;
 
 use crate::components::Component;
 use crate::object::GameObjectId;
+use crate::reflect_component;
 use crate::world::World;
 
 pub struct RotateComponent {
@@ -25,9 +26,9 @@ impl Component for RotateComponent {
         }
     }
 
-    unsafe fn update(&mut self) {
+    unsafe fn update(&mut self, world: &mut World) {
         let transform = &mut self.get_parent().transform;
-        let delta_time = World::instance().get_delta_time().as_secs_f32();
+        let delta_time = world.get_delta_time().as_secs_f32();
 
         let x_angle_radians = (self.iteration / 100.0).sin() * 45.0f32.to_radians();
         let x_rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), x_angle_radians);
@@ -45,3 +46,7 @@ impl Component for RotateComponent {
         self.parent
     }
 }
+
+reflect_component!(RotateComponent, "Rotate", {
+    rotate_speed: Float,
+});
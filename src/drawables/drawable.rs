@@ -1,11 +1,16 @@
 use std::any::Any;
 
-use nalgebra::Matrix4;
 use wgpu::{Device, Queue, RenderPass};
 
-use crate::object::GameObjectId;
+use crate::asset_management::meshmanager::MeshId;
+use crate::asset_management::shadermanager::{ShaderId, DIM3_SHADER_ID};
+use crate::object::{GameObjectId, ModelData};
+use crate::utils::bounds::Bounds;
 use crate::world::World;
 
+// Every drawable gets one slot in the renderer's model buffer per frame, which is bound at
+// group 1. The `instance` passed to the draw functions is that slot, so drawables draw the
+// instances `instance..instance + 1`.
 pub trait Drawable: Any {
     fn setup(
         &mut self,
@@ -18,7 +23,62 @@ pub trait Drawable: Any {
         world: &mut World,
         parent: GameObjectId,
         queue: &Queue,
-        outer_transform: &Matrix4<f32>,
     );
-    unsafe fn draw(&self, world: &mut World, rpass: &mut RenderPass);
+    // The model data written into the drawable's slot, after update
+    fn model_data(&self, parent: GameObjectId) -> ModelData {
+        ModelData::new(parent, true)
+    }
+
+    // Drawables that only show a mesh return it here. The renderer then draws the opaque parts
+    // and shadows of all drawables showing the same mesh with one instanced call, so draw and
+    // draw_shadow aren't called for them.
+    fn instanced_mesh(&self) -> Option<MeshId> {
+        None
+    }
+
+    // The world space bounds, which the renderer tests against the camera's view to skip what
+    // can't be seen. Drawables without bounds are always drawn.
+    fn bounds(&self, _world: &World, _parent: GameObjectId) -> Option<Bounds> {
+        None
+    }
+
+    // A mesh the instanced mesh is cross-fading from, and how far the fade is from 0 to 1.
+    // The renderer draws both with complementary dither patterns, only the new one casts shadows.
+    fn fading_mesh(&self) -> Option<(MeshId, f32)> {
+        None
+    }
+
+    // Hidden drawables are skipped like the ones outside the camera's view, and cast no shadows
+    fn hidden(&self) -> bool {
+        false
+    }
+
+    // Whether the instances of instanced_mesh are drawn into the shadow maps
+    fn casts_shadows(&self) -> bool {
+        true
+    }
+
+    // The shaders of everything that isn't blended. The renderer draws all drawables of a shader
+    // together, so every pipeline is only bound once per frame.
+    fn shaders(&self, _world: &World) -> Vec<ShaderId> {
+        vec![DIM3_SHADER_ID]
+    }
+
+    // Draws everything that isn't blended and uses `shader`. The pipeline of the shader, the
+    // camera, the models and the lights are already bound.
+    unsafe fn draw(&self, world: &mut World, rpass: &mut RenderPass, shader: ShaderId, instance: u32);
+
+    // Whether draw_transparent has anything to draw. Transparent drawables are drawn after all
+    // opaque ones, sorted back to front by their distance to the camera.
+    fn has_transparency(&self, _world: &World) -> bool {
+        false
+    }
+
+    // Draws the blended parts, binding the blend pipeline of their material's shader
+    unsafe fn draw_transparent(&self, _world: &mut World, _rpass: &mut RenderPass, _instance: u32) {}
+
+    // Draws only the depth of the drawable from the view of a light. The shadow pipeline, the
+    // models and the light's view are already bound, materials go into group 2 like for draw.
+    // Drawables that don't cast shadows can skip this.
+    unsafe fn draw_shadow(&self, _world: &mut World, _rpass: &mut RenderPass, _instance: u32) {}
 }
@@ -1,25 +1,185 @@
-use nalgebra::Matrix4;
+use std::ops::Range;
+
 use wgpu::{Device, IndexFormat, Queue, RenderPass};
 
 use crate::asset_management::materialmanager::RuntimeMaterial;
 use crate::asset_management::mesh::RuntimeMesh;
 use crate::asset_management::meshmanager::MeshId;
+use crate::asset_management::shadermanager::ShaderId;
+use crate::components::LodGroup;
 use crate::drawables::drawable::Drawable;
-use crate::object::GameObjectId;
+use crate::object::{GameObjectId, ModelData};
+use crate::utils::bounds::Bounds;
 use crate::world::World;
 
 pub struct MeshRenderer {
     mesh: MeshId,
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    // set by the object's LodGroup
+    lod_hidden: bool,
+    lod_fade: Option<(MeshId, f32)>,
 }
 
 impl MeshRenderer {
     pub fn new(mesh: MeshId) -> Box<MeshRenderer> {
-        Box::new(MeshRenderer { mesh })
+        Box::new(MeshRenderer {
+            mesh,
+            cast_shadows: true,
+            receive_shadows: true,
+            lod_hidden: false,
+            lod_fade: None,
+        })
     }
     
     pub fn mesh(&self) -> MeshId {
         self.mesh
     }
+
+    // Lets the object's LodGroup pick the mesh, if it has an enabled one
+    fn update_lod(&mut self, world: &mut World, parent: GameObjectId) {
+        let Some(parent) = parent.get() else {
+            return;
+        };
+        let Some(lod) = parent.get_component::<LodGroup>().filter(|lod| lod.is_enabled()) else {
+            self.lod_hidden = false;
+            self.lod_fade = None;
+            return;
+        };
+        let mut lod = lod.borrow_mut();
+
+        let Some(bounds) = lod
+            .levels
+            .first()
+            .and_then(|level| world.assets.meshes.get_raw_mesh(level.mesh))
+            .map(|mesh| {
+                let matrix = parent.transform.get_global_transform_matrix().to_homogeneous();
+                mesh.bounds().sphere.transformed(&matrix)
+            })
+        else {
+            return;
+        };
+
+        let (mesh, fade) = lod.select(world, &bounds);
+        self.lod_hidden = mesh.is_none();
+        self.lod_fade = fade;
+        if let Some(mesh) = mesh {
+            self.mesh = mesh;
+        }
+
+        for mesh in mesh.into_iter().chain(fade.map(|(mesh, _)| mesh)) {
+            Self::init_mesh(world, mesh);
+        }
+    }
+
+    fn init_mesh(world: &mut World, mesh_id: MeshId) {
+        let assets = &mut world.assets;
+        assets.meshes.init_runtime_mesh(mesh_id);
+        let Some(mesh) = assets.meshes.get_raw_mesh(mesh_id) else {
+            return;
+        };
+
+        for (mat_id, _) in &mesh.material_ranges {
+            assets.materials.init_runtime_material_id(
+                *mat_id,
+                &mut assets.textures,
+                &assets.bind_group_layouts,
+            ).expect("Runtime material should be initialized..");
+        }
+    }
+
+    // Draws `instances` of the mesh. The opaque ranges are drawn using the shader if there is one,
+    // or all blended ranges with the blend pipelines of their material's shader otherwise.
+    pub(crate) unsafe fn draw_mesh(
+        world: &mut World,
+        rpass: &mut RenderPass,
+        mesh_id: MeshId,
+        opaque_shader: Option<ShaderId>,
+        instances: Range<u32>,
+    ) {
+        let assets = &mut world.assets;
+        let runtime_mesh: *const RuntimeMesh = assets
+            .meshes
+            .get_runtime_mesh(mesh_id)
+            .expect("Runtime mesh should be initialized before calling draw.");
+
+        let mesh = assets
+            .meshes
+            .get_raw_mesh(mesh_id)
+            .expect("Normal mesh should be set");
+
+        rpass.set_vertex_buffer(0, (*runtime_mesh).data.vertices_buf.slice(..));
+        let i_buffer = (*runtime_mesh).data.indices_buf.as_ref();
+        if let Some(i_buffer) = i_buffer {
+            rpass.set_index_buffer(i_buffer.slice(..), IndexFormat::Uint32);
+        }
+
+        for (mat_id, range) in &mesh.material_ranges {
+            let material: *const RuntimeMaterial = assets
+                .materials
+                .get_runtime_material(*mat_id, &mut assets.textures, &assets.bind_group_layouts)
+                .unwrap();
+
+            let blend_mode = (*material).blend_mode;
+            match opaque_shader {
+                Some(shader) if !blend_mode.is_blended() && (*material).shader == shader => (),
+                None if blend_mode.is_blended() => {
+                    let Some(shader) = assets
+                        .shaders
+                        .get_shader((*material).shader, &assets.bind_group_layouts)
+                    else {
+                        continue;
+                    };
+                    rpass.set_pipeline(shader.pipeline_for(blend_mode));
+                }
+                _ => continue,
+            }
+
+            rpass.set_bind_group(2, &(*material).bind_group, &[]);
+            if i_buffer.is_some() {
+                rpass.draw_indexed(range.clone(), 0, instances.clone());
+            } else {
+                rpass.draw(range.clone(), instances.clone());
+            }
+        }
+    }
+
+    // Draws the depth of `instances` of the mesh into a shadow map. Blended ranges don't cast
+    // shadows, alpha tested ones only where they aren't cut out.
+    pub(crate) unsafe fn draw_mesh_shadow(world: &mut World, rpass: &mut RenderPass, mesh_id: MeshId, instances: Range<u32>) {
+        let assets = &mut world.assets;
+        let runtime_mesh: *const RuntimeMesh = assets
+            .meshes
+            .get_runtime_mesh(mesh_id)
+            .expect("Runtime mesh should be initialized before calling draw_shadow.");
+        let mesh = assets
+            .meshes
+            .get_raw_mesh(mesh_id)
+            .expect("Normal mesh should be set");
+
+        rpass.set_vertex_buffer(0, (*runtime_mesh).data.vertices_buf.slice(..));
+        let i_buffer = (*runtime_mesh).data.indices_buf.as_ref();
+        if let Some(i_buffer) = i_buffer {
+            rpass.set_index_buffer(i_buffer.slice(..), IndexFormat::Uint32);
+        }
+
+        for (mat_id, range) in &mesh.material_ranges {
+            let material: *const RuntimeMaterial = assets
+                .materials
+                .get_runtime_material(*mat_id, &mut assets.textures, &assets.bind_group_layouts)
+                .unwrap();
+            if (*material).blend_mode.is_blended() {
+                continue;
+            }
+
+            rpass.set_bind_group(2, &(*material).bind_group, &[]);
+            if i_buffer.is_some() {
+                rpass.draw_indexed(range.clone(), 0, instances.clone());
+            } else {
+                rpass.draw(range.clone(), instances.clone());
+            }
+        }
+    }
 }
 
 impl Drawable for MeshRenderer {
@@ -29,89 +189,87 @@ impl Drawable for MeshRenderer {
         _queue: &Queue,
         world: &mut World,
     ) {
-        unsafe {
-            let world: *mut World = world;
-            (*world)
-                .assets
-                .meshes
-                .init_runtime_mesh(self.mesh);
-            let mesh = (*world)
-                .assets
-                .meshes
-                .get_raw_mesh(self.mesh)
-                .expect("Normal mesh should be set");
-
-            for (mat_id, _) in &mesh.material_ranges {
-                (*world).assets.materials.init_runtime_material_id(
-                    &mut *world,
-                    *mat_id,
-                ).expect("Runtime material should be initialized..");
-            }
-        }
+        Self::init_mesh(world, self.mesh);
     }
 
     fn update(
         &mut self,
         world: &mut World,
         parent: GameObjectId,
-        queue: &Queue,
-        outer_transform: &Matrix4<f32>,
+        _queue: &Queue,
     ) {
-        // TODO: Meshes should be able to be shared. Give ModelData to the MeshRenderer
-        let runtime_mesh = world
-            .assets
-            .meshes
-            .get_runtime_mesh_mut(self.mesh)
-            .expect("Runtime mesh should be initialized before calling update.");
-        runtime_mesh
-            .data
-            .model_data
-            .update(parent, outer_transform);
-        queue.write_buffer(
-            &runtime_mesh.data.model_data_buffer,
-            0,
-            bytemuck::cast_slice(&[runtime_mesh.data.model_data]),
-        )
-    }
-
-    unsafe fn draw(&self, world: &mut World, rpass: &mut RenderPass) {
-        let runtime_mesh: *const RuntimeMesh = world
-            .assets
-            .meshes
-            .get_runtime_mesh(self.mesh)
-            .expect("Runtime mesh should be initialized before calling draw.");
+        // the model data lives in the renderer's model buffer, only the mesh is shared
+        world.assets.meshes.init_runtime_mesh(self.mesh);
+        self.update_lod(world, parent);
+    }
 
-        let mesh = world
-            .assets
-            .meshes
-            .get_raw_mesh(self.mesh)
-            .expect("Normal mesh should be set");
+    fn model_data(&self, parent: GameObjectId) -> ModelData {
+        ModelData::new(parent, self.receive_shadows)
+    }
 
-        rpass.set_vertex_buffer(0, (*runtime_mesh).data.vertices_buf.slice(..));
-        rpass.set_bind_group(1, &(*runtime_mesh).data.model_bind_group, &[]);
-        if let Some(i_buffer) = (*runtime_mesh).data.indices_buf.as_ref() {
-            for (mat_id, range) in &(*mesh).material_ranges {
-                let material: *const RuntimeMaterial = world
-                    .assets
-                    .materials
-                    .get_runtime_material(*mat_id)
-                    .unwrap();
-
-                rpass.set_bind_group(2, &(*material).bind_group, &[]);
-                rpass.set_index_buffer(i_buffer.slice(..), IndexFormat::Uint32);
-                rpass.draw_indexed(range.clone(), 0, 0..1);
-            }
-        } else {
-            for (mat_id, range) in &(*mesh).material_ranges {
-                let material: *const RuntimeMaterial = world
-                    .assets
-                    .materials
-                    .get_runtime_material(*mat_id)
-                    .unwrap();
-
-                rpass.set_bind_group(2, &(*material).bind_group, &[]);
-                rpass.draw(range.clone(), 0..1);
+    fn instanced_mesh(&self) -> Option<MeshId> {
+        Some(self.mesh)
+    }
+
+    fn bounds(&self, world: &World, parent: GameObjectId) -> Option<Bounds> {
+        let mesh = world.assets.meshes.get_raw_mesh(self.mesh)?;
+        let matrix = parent.get()?.transform.get_global_transform_matrix().to_homogeneous();
+        Some(mesh.bounds().transformed(&matrix))
+    }
+
+    fn fading_mesh(&self) -> Option<(MeshId, f32)> {
+        self.lod_fade
+    }
+
+    fn hidden(&self) -> bool {
+        self.lod_hidden
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.cast_shadows
+    }
+
+    fn shaders(&self, world: &World) -> Vec<ShaderId> {
+        let Some(mesh) = world.assets.meshes.get_raw_mesh(self.mesh) else {
+            return vec![];
+        };
+        let mut shaders = Vec::new();
+        for (mat_id, _) in &mesh.material_ranges {
+            let Some(material) = world.assets.materials.get_raw_material(*mat_id) else {
+                continue;
+            };
+            if !material.blend_mode.is_blended() && !shaders.contains(&material.shader) {
+                shaders.push(material.shader);
             }
         }
+        shaders
+    }
+
+    unsafe fn draw(&self, world: &mut World, rpass: &mut RenderPass, shader: ShaderId, instance: u32) {
+        Self::draw_mesh(world, rpass, self.mesh, Some(shader), instance..instance + 1);
+    }
+
+    fn has_transparency(&self, world: &World) -> bool {
+        let Some(mesh) = world.assets.meshes.get_raw_mesh(self.mesh) else {
+            return false;
+        };
+        mesh.material_ranges.iter().any(|(mat_id, _)| {
+            world
+                .assets
+                .materials
+                .get_raw_material(*mat_id)
+                .is_some_and(|mat| mat.blend_mode.is_blended())
+        })
+    }
+
+    unsafe fn draw_transparent(&self, world: &mut World, rpass: &mut RenderPass, instance: u32) {
+        Self::draw_mesh(world, rpass, self.mesh, None, instance..instance + 1);
+    }
+
+    unsafe fn draw_shadow(&self, world: &mut World, rpass: &mut RenderPass, instance: u32) {
+        if !self.cast_shadows {
+            return;
+        }
+        Self::draw_mesh_shadow(world, rpass, self.mesh, instance..instance + 1);
     }
 }
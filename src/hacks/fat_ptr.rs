@@ -1,5 +0,0 @@
-#[repr(C)]
-pub struct FatPtr<'a, I> {
-    pub data: &'a mut I,
-    pub v_table: *const (),
-}
@@ -1,3 +0,0 @@
-mod fat_ptr;
-
-pub use fat_ptr::FatPtr;
\ No newline at end of file
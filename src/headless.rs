@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::time::Duration;
+
+use log::info;
+
+use crate::logichooks::{HeadlessHookFunc, HeadlessLogicHooks};
+use crate::world::World;
+
+/// Runs a [`World`] without a window, renderer or GPU surface.
+///
+/// Every frame advances the world by the same fixed time step, so components and physics
+/// behave the same on every run. This makes it usable for servers, CI and tests.
+pub struct HeadlessApp {
+    world: Box<World>,
+    frame_time: Duration,
+    frame: u64,
+    pub hook_funcs: HeadlessLogicHooks,
+}
+
+impl HeadlessApp {
+    pub fn create(tick_rate: u32) -> HeadlessApp {
+        let world = World::new();
+
+        HeadlessApp {
+            world,
+            frame_time: Duration::from_secs_f64(1.0 / tick_rate.max(1) as f64),
+            frame: 0,
+            hook_funcs: HeadlessLogicHooks {
+                init: None,
+                update: None,
+                deinit: None,
+            },
+        }
+    }
+
+    pub fn with_init(&mut self, init: Option<HeadlessHookFunc>) {
+        self.hook_funcs.init = init;
+    }
+
+    pub fn with_update(&mut self, update: Option<HeadlessHookFunc>) {
+        self.hook_funcs.update = update;
+    }
+
+    pub fn with_deinit(&mut self, deinit: Option<HeadlessHookFunc>) {
+        self.hook_funcs.deinit = deinit;
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    pub fn frames_run(&self) -> u64 {
+        self.frame
+    }
+
+    /// Runs exactly `frames` frames and hands back the world for inspection.
+    pub fn run_frames(self, frames: u64) -> Result<Box<World>, Box<dyn Error>> {
+        self.run_until(|_| false, Some(frames))
+    }
+
+    /// Runs until `condition` returns true after a frame, or until `max_frames` frames have passed.
+    pub fn run_until<F>(
+        mut self,
+        mut condition: F,
+        max_frames: Option<u64>,
+    ) -> Result<Box<World>, Box<dyn Error>>
+    where
+        F: FnMut(&World) -> bool,
+    {
+        if let Some(init) = self.hook_funcs.init {
+            init(&mut self.world)?;
+        }
+
+        while max_frames.is_none_or(|max| self.frame < max) {
+            self.step()?;
+
+            if condition(&self.world) {
+                break;
+            }
+        }
+
+        info!("Headless run finished after {} frames", self.frame);
+
+        if let Some(deinit) = self.hook_funcs.deinit {
+            deinit(&mut self.world)?;
+        }
+
+        Ok(self.world)
+    }
+
+    fn step(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(update) = self.hook_funcs.update {
+            update(&mut self.world)?;
+        }
+
+        self.world.tick(self.frame_time);
+        self.frame += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::HeadlessApp;
+
+    #[test]
+    fn every_frame_at_60_hz_runs_one_physics_step() {
+        let app = HeadlessApp::create(60);
+        let mut frames = 0;
+        app.run_until(
+            |world| {
+                frames += 1;
+                // a single step used up all the time the frame added, nothing is left for a second one
+                assert_eq!(world.physics.time_accumulator, Duration::ZERO, "time left after frame {frames}");
+                false
+            },
+            Some(120),
+        )
+        .unwrap();
+        assert_eq!(frames, 120);
+    }
+}
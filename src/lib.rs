@@ -4,11 +4,13 @@ pub mod app;
 pub mod asset_management;
 pub mod buffer;
 pub mod components;
-pub mod hacks;
+pub mod headless;
 pub mod logichooks;
 pub mod object;
 pub mod physics;
+pub mod prefab;
 pub mod renderer;
+pub mod scene;
 pub mod scene_loader;
 pub mod state;
 pub mod transform;
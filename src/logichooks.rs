@@ -4,9 +4,16 @@ use winit::window::Window;
 use crate::world::World;
 
 pub type HookFunc = fn(world: &mut World, window: &Window) -> Result<(), Box<dyn Error>>;
+pub type HeadlessHookFunc = fn(world: &mut World) -> Result<(), Box<dyn Error>>;
 
 pub struct LogicHooks {
     pub init: Option<HookFunc>,
     pub update: Option<HookFunc>,
     pub deinit: Option<HookFunc>,
 }
+
+pub struct HeadlessLogicHooks {
+    pub init: Option<HeadlessHookFunc>,
+    pub update: Option<HeadlessHookFunc>,
+    pub deinit: Option<HeadlessHookFunc>,
+}
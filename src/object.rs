@@ -1,47 +1,66 @@
-use std::any::TypeId;
-use std::cell::RefCell;
-use std::mem;
-use std::ops::{AddAssign, Deref, DerefMut};
+use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 
 use bytemuck::{Pod, Zeroable};
 use itertools::Itertools;
 use nalgebra::Matrix4;
 
-use crate::components::Component;
+use crate::components::{Component, ComponentRef, ComponentSlot};
 use crate::drawables::drawable::Drawable;
-use crate::hacks;
 use crate::transform::Transform;
 use crate::world::World;
 
+// A handle to a game object. It remembers the world it was created in, so objects of
+// different worlds can be used side by side. Destroyed objects free their index for reuse;
+// the generation tells apart handles to the old and the new object living at the same index.
 #[derive(Debug, Copy, Clone, Eq, Ord, PartialOrd, PartialEq, Hash)]
-#[repr(transparent)]
-pub struct GameObjectId(pub usize);
+pub struct GameObjectId {
+    pub(crate) index: usize,
+    pub(crate) generation: u32,
+    world: *mut World,
+}
 
 #[allow(dead_code)]
 impl GameObjectId {
-    pub(crate) fn exists(&self) -> bool {
-        World::instance().objects.contains_key(self)
+    pub(crate) fn new(index: usize, generation: u32, world: *mut World) -> Self {
+        GameObjectId { index, generation, world }
+    }
+
+    // false once the object was destroyed
+    pub fn exists(&self) -> bool {
+        self.get().is_some()
+    }
+
+    pub fn get(&self) -> Option<&GameObject> {
+        unsafe { (*self.world).get_object(self) }
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut GameObject> {
+        unsafe { (*self.world).get_object_mut(self) }
     }
-}
 
-impl AddAssign<usize> for GameObjectId {
-    fn add_assign(&mut self, other: usize) {
-        self.0 += other;
+    // The world this object lives in. Components get the world passed into their callbacks and
+    // should use that one instead.
+    pub(crate) fn world(&self) -> *mut World {
+        self.world
     }
 }
 
+// Only for handles known to be alive. Use get() and get_mut() for ones that might have been
+// destroyed in the meantime.
 impl Deref for GameObjectId {
-    type Target = Box<GameObject>;
+    type Target = GameObject;
 
     fn deref(&self) -> &Self::Target {
-        World::instance().get_object(self).unwrap()
+        self.get()
+            .expect("Game object was destroyed or belongs to another world")
     }
 }
 
 impl DerefMut for GameObjectId {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        World::instance().get_object_mut(self).unwrap()
+        self.get_mut()
+            .expect("Game object was destroyed or belongs to another world")
     }
 }
 
@@ -52,14 +71,16 @@ pub struct GameObject {
     pub parent: Option<GameObjectId>,
     pub transform: Transform,
     pub drawable: Option<Box<dyn Drawable>>,
-    pub components: Vec<Rc<RefCell<Box<dyn Component>>>>,
+    pub components: Vec<ComponentSlot>,
+    // counts up with every object created in the world. Unlike the index of the id, it's never reused.
+    pub(crate) creation_order: u64,
 }
 
 impl GameObject {
     pub fn add_child(&mut self, mut child: GameObjectId) {
         // if child had a parent, remove it from there
         if let Some(mut parent) = child.parent {
-            let pos_opt = parent.children.iter().find_position(|other| child.0 == other.0).map(|(id, _)| id);
+            let pos_opt = parent.children.iter().find_position(|other| child == **other).map(|(id, _)| id);
             if let Some(pos) = pos_opt {
                 parent.children.remove(pos);
             }
@@ -67,6 +88,7 @@ impl GameObject {
 
         self.children.push(child);
         child.parent = Some(self.id);
+        child.transform.mark_dirty();
     }
 
     pub fn set_drawable(&mut self, drawable: Option<Box<dyn Drawable>>) {
@@ -75,37 +97,89 @@ impl GameObject {
 
     pub fn add_component<'b, C: Component + 'static>(&mut self) -> &'b mut C {
         unsafe {
-            let mut comp: Box<dyn Component> = Box::new(C::new(self.id));
-            let comp_inner_ptr: hacks::FatPtr<C> =
-                mem::transmute(comp.as_mut() as *mut dyn Component);
-            let comp_inner_ref: &mut C = &mut *comp_inner_ptr.data;
+            let mut comp: Box<C> = Box::new(C::new(self.id));
+            // the box keeps the component in place, so this stays valid after moving the box
+            let comp_inner_ref: &mut C = &mut *(comp.as_mut() as *mut C);
+            let mut comp: Box<dyn Component> = comp;
 
-            comp.init();
+            comp.init(&mut *self.id.world());
 
-            let comp: Rc<RefCell<Box<dyn Component>>> = Rc::new(RefCell::new(comp));
-            let comp_dyn: Rc<RefCell<Box<dyn Component>>> = comp;
+            let slot = ComponentSlot::new(comp);
+            self.components.push(slot.clone());
 
-            self.components.push(comp_dyn);
+            (**slot.component.as_ptr()).on_enable(&mut *self.id.world());
 
             comp_inner_ref
         }
     }
 
-    // FIXME: this works for now but is stupidly fucked up.
-    //   only change this if entity ids are used for Components in the future :>>
-    pub fn get_component<C: Component + 'static>(&self) -> Option<Rc<RefCell<Box<C>>>> {
-        for component in &self.components {
-            let raw_ptr: *const Box<dyn Component> = component.as_ptr();
-            let type_id = unsafe { (**raw_ptr).type_id() };
+    // Returns the first component of type C
+    pub fn get_component<C: Component + 'static>(&self) -> Option<ComponentRef<C>> {
+        self.components.iter().find_map(ComponentSlot::downcast)
+    }
+
+    pub fn get_components<C: Component + 'static>(&self) -> Vec<ComponentRef<C>> {
+        self.components
+            .iter()
+            .filter_map(ComponentSlot::downcast)
+            .collect()
+    }
+
+    pub fn has_component<C: Component + 'static>(&self) -> bool {
+        self.components.iter().any(ComponentSlot::is::<C>)
+    }
+
+    pub fn get_or_add_component<C: Component + 'static>(&mut self) -> ComponentRef<C> {
+        if let Some(comp) = self.get_component::<C>() {
+            return comp;
+        }
+        self.add_component::<C>();
+        self.get_component::<C>()
+            .expect("Component was just added")
+    }
+
+    // Removes the first component of type C. It gets disabled and destroyed first.
+    // Returns false if there was none.
+    pub fn remove_component<C: Component + 'static>(&mut self) -> bool {
+        let Some(pos) = self.components.iter().position(ComponentSlot::is::<C>) else {
+            return false;
+        };
+
+        let slot = self.components[pos].clone();
+        unsafe {
+            slot.destroy(&mut *self.id.world());
+        }
+
+        // on_destroy could have touched the component list already
+        if let Some(pos) = self
+            .components
+            .iter()
+            .position(|other| Rc::ptr_eq(&other.component, &slot.component))
+        {
+            self.components.remove(pos);
+        }
+        true
+    }
 
-            if type_id == TypeId::of::<C>() {
-                return Some(unsafe {
-                    let rc_clone = Rc::clone(component);
-                    mem::transmute(rc_clone)
-                });
+    // Enables or disables the first component of type C. Returns false if there is none.
+    pub fn set_component_enabled<C: Component + 'static>(&self, enabled: bool) -> bool {
+        match self.find_component_slot::<C>() {
+            Some(slot) => {
+                slot.set_enabled(enabled);
+                true
             }
+            None => false,
         }
-        None
+    }
+
+    pub fn is_component_enabled<C: Component + 'static>(&self) -> Option<bool> {
+        self.find_component_slot::<C>().map(ComponentSlot::is_enabled)
+    }
+
+    fn find_component_slot<C: Component + 'static>(&self) -> Option<&ComponentSlot> {
+        self.components
+            .iter()
+            .find(|slot| slot.is::<C>())
     }
 }
 
@@ -113,18 +187,34 @@ impl GameObject {
 #[derive(Debug, Copy, Clone)]
 pub struct ModelData {
     pub model_mat: Matrix4<f32>,
+    pub receive_shadows: u32,
+    // Dithers the drawable in or out while a LodGroup cross-fades: 0 draws it fully, a positive
+    // value only that part of it, and a negative one everything but that part
+    pub lod_fade: f32,
+    _padding: [u32; 2],
 }
 
 impl ModelData {
     pub fn empty() -> Self {
         ModelData {
             model_mat: Matrix4::identity(),
+            receive_shadows: 1,
+            lod_fade: 0.0,
+            _padding: [0; 2],
         }
     }
 
-    pub fn update(&mut self, object: GameObjectId, outer_transform: &Matrix4<f32>) {
-        self.model_mat =
-            outer_transform * object.transform.full_matrix().to_homogeneous();
+    pub fn new(object: GameObjectId, receive_shadows: bool) -> Self {
+        let mut data = ModelData::empty();
+        data.update(object);
+        data.receive_shadows = receive_shadows as u32;
+        data
+    }
+
+    pub fn update(&mut self, object: GameObjectId) {
+        if let Some(object) = object.get() {
+            self.model_mat = object.transform.get_global_transform_matrix().to_homogeneous();
+        }
     }
 }
 
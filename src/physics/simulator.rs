@@ -1,4 +1,4 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use nalgebra::Vector3;
 use rapier3d::prelude::*;
 
@@ -17,7 +17,7 @@ pub struct PhysicsSimulator {
     pub query_pipeline: QueryPipeline,
     pub physics_hooks: (),
     pub event_handler: (),
-    pub last_update: Instant,
+    pub time_accumulator: Duration,
     pub timestep: Duration,
 }
 
@@ -40,8 +40,8 @@ impl Default for PhysicsSimulator {
             query_pipeline: QueryPipeline::default(),
             physics_hooks: (),
             event_handler: (),
-            last_update: Instant::now(),
-            timestep: Duration::from_millis(1000 / 60),
+            time_accumulator: Duration::ZERO,
+            timestep: Duration::from_secs_f64(1.0 / 60.0),
         }
     }
 }
@@ -65,4 +65,25 @@ impl PhysicsSimulator {
         );
         self.query_pipeline.update(&self.collider_set)
     }
+
+    // Removes the body, but keeps its colliders around so their owners can remove them on their own
+    pub fn remove_rigid_body(&mut self, handle: RigidBodyHandle) -> Option<RigidBody> {
+        self.rigid_body_set.remove(
+            handle,
+            &mut self.island_manager,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            false,
+        )
+    }
+
+    pub fn remove_collider(&mut self, handle: ColliderHandle) -> Option<Collider> {
+        self.collider_set.remove(
+            handle,
+            &mut self.island_manager,
+            &mut self.rigid_body_set,
+            true,
+        )
+    }
 }
\ No newline at end of file
@@ -0,0 +1,401 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+
+use log::warn;
+
+use crate::asset_management::meshmanager::MeshId;
+use crate::components::registry::{FieldMap, FieldValue};
+use crate::drawables::mesh_renderer::MeshRenderer;
+use crate::object::GameObjectId;
+use crate::scene::{ComponentDesc, DrawableDesc, ObjectDesc, TransformDesc};
+use crate::scene_loader::SceneLoader;
+use crate::world::World;
+
+pub type PrefabId = usize;
+
+// An object hierarchy that can be instantiated many times. Unlike an ObjectDesc, all assets are
+// already resolved, so every instance shares the same meshes and materials.
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    pub name: String,
+    pub root: PrefabNode,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefabNode {
+    pub name: String,
+    pub transform: TransformDesc,
+    pub mesh: Option<MeshId>,
+    pub components: Vec<ComponentDesc>,
+    pub children: Vec<PrefabNode>,
+}
+
+// Changes applied to a single instance. Objects are addressed by their path of names below the
+// prefab root, like "Body/Arm". The root itself is "".
+#[derive(Debug, Clone, Default)]
+pub struct PrefabOverrides {
+    name: Option<String>,
+    meshes: HashMap<String, MeshId>,
+    fields: HashMap<(String, String), FieldMap>,
+}
+
+pub struct PrefabManager {
+    prefabs: HashMap<PrefabId, Prefab>,
+    next_id: PrefabId,
+}
+
+impl Prefab {
+    pub fn new(name: &str, root: PrefabNode) -> Prefab {
+        Prefab {
+            name: name.to_owned(),
+            root,
+        }
+    }
+
+    // Captures an existing object and its children
+    pub fn from_object(world: &World, obj: GameObjectId) -> Prefab {
+        Prefab::new(&obj.name, PrefabNode::from_object(world, obj))
+    }
+
+    // Resolves all mesh paths of the description, importing model files where needed
+    pub fn from_desc(world: &mut World, desc: &ObjectDesc) -> Result<Prefab, Box<dyn Error>> {
+        Ok(Prefab::new(&desc.name, PrefabNode::from_desc(world, desc)?))
+    }
+}
+
+impl PrefabNode {
+    pub fn from_object(world: &World, obj: GameObjectId) -> PrefabNode {
+        let transform = &obj.transform;
+
+        let mesh = obj.drawable.as_ref().and_then(|drawable| {
+            let renderer = (drawable.as_ref() as &dyn Any).downcast_ref::<MeshRenderer>();
+            if renderer.is_none() {
+                warn!("The drawable of \"{}\" is not a mesh renderer and won't be copied", obj.name);
+            }
+            renderer.map(MeshRenderer::mesh)
+        });
+
+        let mut components = Vec::new();
+        for slot in &obj.components {
+            let Some(registration) = world.component_registry.get_for_slot(slot) else {
+                warn!("A component of \"{}\" is not registered and won't be copied", obj.name);
+                continue;
+            };
+
+            components.push(ComponentDesc {
+                type_name: registration.name.to_owned(),
+                enabled: slot.is_enabled(),
+                fields: registration.save_fields(slot),
+            });
+        }
+
+        PrefabNode {
+            name: obj.name.clone(),
+            transform: TransformDesc {
+                position: *transform.local_position(),
+                rotation: *transform.local_rotation(),
+                scale: *transform.local_scale(),
+                compound_pos_first: transform.compound_pos_first(),
+            },
+            mesh,
+            components,
+            children: obj
+                .children
+                .iter()
+                .map(|child| Self::from_object(world, *child))
+                .collect(),
+        }
+    }
+
+    pub fn from_desc(world: &mut World, desc: &ObjectDesc) -> Result<PrefabNode, Box<dyn Error>> {
+        let mesh = match &desc.drawable {
+            Some(DrawableDesc::MeshRenderer { mesh }) => Some(SceneLoader::find_or_load_mesh(world, mesh)?),
+            None => None,
+        };
+
+        // checked here already, so nothing is created when a scene can't be loaded
+        let mut components = Vec::new();
+        for comp in &desc.components {
+            if world.component_registry.get(&comp.type_name).is_none() {
+                return Err(format!("Component type \"{}\" is not registered", comp.type_name).into());
+            }
+            components.push(comp.resolve_assets(world)?);
+        }
+
+        let mut children = Vec::new();
+        for child in &desc.children {
+            children.push(Self::from_desc(world, child)?);
+        }
+
+        Ok(PrefabNode {
+            name: desc.name.clone(),
+            transform: desc.transform.clone(),
+            mesh,
+            components,
+            children,
+        })
+    }
+
+    // Turns asset ids back into paths, so the node can be written to a scene file
+    pub fn to_desc(&self, world: &World) -> ObjectDesc {
+        let drawable = self.mesh.and_then(|mesh| match world.assets.meshes.get_mesh_path(mesh) {
+            Some(path) => Some(DrawableDesc::MeshRenderer {
+                mesh: path.to_owned(),
+            }),
+            None => {
+                warn!("The mesh of \"{}\" has no path and can't be saved to a scene", self.name);
+                None
+            }
+        });
+
+        let components = self
+            .components
+            .iter()
+            .map(|comp| comp.save_assets(world, &self.name))
+            .collect();
+
+        ObjectDesc {
+            name: self.name.clone(),
+            transform: self.transform.clone(),
+            drawable,
+            components,
+            children: self.children.iter().map(|child| child.to_desc(world)).collect(),
+        }
+    }
+
+    // Creates the object and its children, with the object placed at `transform` instead of the
+    // node's own transform. The object is not added to the world root.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        transform: &TransformDesc,
+        overrides: &PrefabOverrides,
+    ) -> Result<GameObjectId, Box<dyn Error>> {
+        self.spawn_rec(world, overrides, "", transform, None)
+    }
+
+    fn spawn_rec(
+        &self,
+        world: &mut World,
+        overrides: &PrefabOverrides,
+        path: &str,
+        transform: &TransformDesc,
+        parent: Option<GameObjectId>,
+    ) -> Result<GameObjectId, Box<dyn Error>> {
+        let name = match overrides.name.as_ref() {
+            Some(name) if path.is_empty() => name,
+            _ => &self.name,
+        };
+        let mut obj = world.new_object(name);
+
+        let local = &mut obj.transform;
+        local.set_compound_pos_first(transform.compound_pos_first);
+        local.set_local_position(transform.position);
+        local.set_local_rotation(transform.rotation);
+        local.set_nonuniform_local_scale(transform.scale);
+
+        // attached right away, so the global transform is final before any component is created
+        if let Some(mut parent) = parent {
+            parent.add_child(obj);
+        }
+
+        if let Some(mesh) = overrides.meshes.get(path).copied().or(self.mesh) {
+            obj.drawable = Some(MeshRenderer::new(mesh));
+        }
+
+        for child in &self.children {
+            let child_path = if path.is_empty() {
+                child.name.clone()
+            } else {
+                format!("{path}/{}", child.name)
+            };
+            child.spawn_rec(world, overrides, &child_path, &child.transform, Some(obj))?;
+        }
+
+        // components last, so they can find their children in place
+        for desc in &self.components {
+            let Some(registration) = world.component_registry.get(&desc.type_name).copied() else {
+                return Err(format!("Component type \"{}\" is not registered", desc.type_name).into());
+            };
+
+            let Some(slot) = registration.create(&mut obj) else {
+                continue;
+            };
+            registration.load_fields(&slot, &desc.fields);
+            if let Some(fields) = overrides.fields.get(&(path.to_owned(), desc.type_name.clone())) {
+                registration.load_fields(&slot, fields);
+            }
+            if !desc.enabled {
+                slot.set_enabled(false);
+            }
+        }
+
+        Ok(obj)
+    }
+}
+
+impl PrefabOverrides {
+    pub fn new() -> PrefabOverrides {
+        PrefabOverrides::default()
+    }
+
+    // Renames the root object of the instance
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    pub fn mesh(mut self, object_path: &str, mesh: MeshId) -> Self {
+        self.meshes.insert(object_path.to_owned(), mesh);
+        self
+    }
+
+    // Sets a field of the registered component `component` on the object at `object_path`
+    pub fn field(mut self, object_path: &str, component: &str, field: &str, value: FieldValue) -> Self {
+        self.fields
+            .entry((object_path.to_owned(), component.to_owned()))
+            .or_default()
+            .insert(field.to_owned(), value);
+        self
+    }
+}
+
+#[allow(dead_code)]
+impl PrefabManager {
+    pub(crate) fn new() -> PrefabManager {
+        PrefabManager {
+            prefabs: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn add_prefab(&mut self, prefab: Prefab) -> PrefabId {
+        let id = self.next_id;
+        self.prefabs.insert(id, prefab);
+        self.next_id += 1;
+        id
+    }
+
+    pub fn get_prefab(&self, id: PrefabId) -> Option<&Prefab> {
+        self.prefabs.get(&id)
+    }
+
+    pub fn get_prefab_mut(&mut self, id: PrefabId) -> Option<&mut Prefab> {
+        self.prefabs.get_mut(&id)
+    }
+
+    pub fn find_prefab_by_name(&self, name: &str) -> Option<PrefabId> {
+        self.prefabs
+            .iter()
+            .find(|(_, prefab)| prefab.name == name)
+            .map(|(id, _)| *id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use super::{Prefab, PrefabId, PrefabOverrides};
+    use crate::components::registry::FieldValue;
+    use crate::components::{Component, GravityComp};
+    use crate::object::GameObjectId;
+    use crate::reflect_component;
+    use crate::scene::TransformDesc;
+    use crate::world::World;
+
+    // Remembers where its object was when it was created
+    struct PositionProbe {
+        created_at: Vector3<f32>,
+        parent: GameObjectId,
+    }
+
+    impl Component for PositionProbe {
+        unsafe fn new(parent: GameObjectId) -> Self {
+            PositionProbe {
+                created_at: Vector3::zeros(),
+                parent,
+            }
+        }
+
+        unsafe fn init(&mut self, _world: &mut World) {
+            self.created_at = self.parent.transform.position();
+        }
+
+        unsafe fn get_parent(&self) -> GameObjectId {
+            self.parent
+        }
+    }
+
+    reflect_component!(PositionProbe, "PositionProbe");
+
+    // A "Robot" root scaled by 2, with an "Arm" one unit to its right
+    fn robot_prefab(world: &mut World) -> PrefabId {
+        world.component_registry.register::<PositionProbe>();
+        let mut root = world.new_object("Robot");
+        let mut arm = world.new_object("Arm");
+        root.add_child(arm);
+        root.transform.set_uniform_local_scale(2.0);
+        arm.transform.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+        arm.add_component::<GravityComp>().acceleration_per_sec = 1.0;
+        arm.add_component::<PositionProbe>();
+
+        let prefab = Prefab::from_object(world, root);
+        world.destroy_object(root);
+        world.prefabs.add_prefab(prefab)
+    }
+
+    fn at(position: Vector3<f32>) -> TransformDesc {
+        TransformDesc {
+            position,
+            ..TransformDesc::default()
+        }
+    }
+
+    #[test]
+    fn instance_is_placed_before_components_are_created() {
+        let mut world = World::new();
+        let prefab = robot_prefab(&mut world);
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2);
+        let transform = TransformDesc {
+            rotation,
+            ..at(Vector3::new(10.0, 0.0, 0.0))
+        };
+
+        let robot = world.instantiate(prefab, &transform).unwrap();
+
+        assert_eq!(world.children, vec![robot]);
+        assert_eq!(robot.name, "Robot");
+        assert_eq!(*robot.transform.local_scale(), Vector3::repeat(2.0));
+        let [arm] = robot.children[..] else {
+            panic!("expected a single child, got {:?}", robot.children);
+        };
+        assert_eq!(arm.name, "Arm");
+        assert_eq!(arm.parent, Some(robot));
+
+        // the arm is rotated up with the root, and twice as far away because of its scale
+        let expected = Vector3::new(10.0, 2.0, 0.0);
+        assert!((arm.transform.position() - expected).norm() < 1e-5);
+        let probe = arm.get_component::<PositionProbe>().unwrap();
+        assert!((probe.borrow().created_at - expected).norm() < 1e-5);
+    }
+
+    #[test]
+    fn overrides_only_change_their_instance() {
+        let mut world = World::new();
+        let prefab = robot_prefab(&mut world);
+        let overrides = PrefabOverrides::new()
+            .name("Heavy Robot")
+            .field("Arm", "Gravity", "acceleration_per_sec", FieldValue::Float(5.0));
+
+        let heavy = world.instantiate_with(prefab, &at(Vector3::zeros()), &overrides).unwrap();
+        let plain = world.instantiate(prefab, &at(Vector3::zeros())).unwrap();
+
+        assert_eq!(heavy.name, "Heavy Robot");
+        assert_eq!(plain.name, "Robot");
+        let acceleration = |robot: GameObjectId| world.get_component_field(robot.children[0], "Gravity", "acceleration_per_sec");
+        assert_eq!(acceleration(heavy), Some(FieldValue::Float(5.0)));
+        assert_eq!(acceleration(plain), Some(FieldValue::Float(1.0)));
+    }
+}
@@ -0,0 +1,203 @@
+use nalgebra::Vector4;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::asset_management::assetmanager::AssetManager;
+use crate::asset_management::bindgroup_layout_manager::{POST_EFFECT_BGL_ID, POST_PROCESS_BGL_ID};
+use crate::asset_management::shadermanager::{BLOOM_DOWNSAMPLE_SHADER_ID, BLOOM_PREFILTER_SHADER_ID, BLOOM_UPSAMPLE_SHADER_ID};
+use crate::asset_management::texturemanager::FALLBACK_SHININESS_TEXTURE;
+use crate::renderer::post_process::{PostEffectUniform, PostProcessPass};
+use crate::renderer::HDR_FORMAT;
+
+// Most mip levels the bloom is blurred over. Each level doubles the radius of the glow.
+pub const BLOOM_MIP_LEVELS: u32 = 6;
+
+struct BloomMip {
+    view: TextureView,
+    input: PostProcessPass,
+    width: u32,
+    height: u32,
+}
+
+// One render pass of the bloom, with its own parameters
+struct BloomPass {
+    uniform_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+// Blurs the bright parts of the HDR image by downsampling them into a chain of mip levels, and
+// adding them back up again, level by level. The result ends up in the first mip level, which
+// has half the size of the image.
+pub(crate) struct BloomRenderData {
+    #[allow(dead_code)]
+    texture: Texture,
+    mips: Vec<BloomMip>,
+    // the prefilter pass, followed by all downsample passes, followed by all upsample passes
+    passes: Vec<BloomPass>,
+}
+
+impl BloomRenderData {
+    pub(crate) fn new(device: &Device, assets: &mut AssetManager, width: u32, height: u32) -> Self {
+        let input_layout = assets.bind_group_layouts.get_bind_group_layout(POST_PROCESS_BGL_ID).unwrap();
+        let effect_layout = assets.bind_group_layouts.get_bind_group_layout(POST_EFFECT_BGL_ID).unwrap();
+
+        let mip_width = (width / 2).max(1);
+        let mip_height = (height / 2).max(1);
+        let mip_count = BLOOM_MIP_LEVELS.min(mip_width.min(mip_height).ilog2() + 1);
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Bloom Texture"),
+            size: Extent3d { width: mip_width, height: mip_height, depth_or_array_layers: 1 },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let mips: Vec<BloomMip> = (0..mip_count)
+            .map(|level| {
+                let view = texture.create_view(&TextureViewDescriptor {
+                    label: Some("Bloom Mip View"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+                let input = PostProcessPass::new(device, input_layout, &view);
+                BloomMip {
+                    view,
+                    input,
+                    width: (mip_width >> level).max(1),
+                    height: (mip_height >> level).max(1),
+                }
+            })
+            .collect();
+
+        // the passes don't read the effect texture, but the layout needs one
+        let effect_texture = &assets
+            .textures
+            .get_runtime_texture_ensure_init(FALLBACK_SHININESS_TEXTURE)
+            .expect("Fallback texture should be initialized")
+            .view;
+        let pass_count = 1 + 2 * (mip_count as usize - 1);
+        let passes = (0..pass_count)
+            .map(|_| {
+                let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Bloom Uniform Buffer"),
+                    contents: bytemuck::bytes_of(&PostEffectUniform::new(Vector4::zeros(), 1, 1)),
+                    usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                });
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Bloom Bind Group"),
+                    layout: effect_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: uniform_buffer.as_entire_binding(),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(effect_texture),
+                        },
+                    ],
+                });
+                BloomPass { uniform_buffer, bind_group }
+            })
+            .collect();
+
+        BloomRenderData { texture, mips, passes }
+    }
+
+    // The blurred bright parts, half the size of the image
+    pub(crate) fn view(&self) -> &TextureView {
+        &self.mips[0].view
+    }
+
+    // Renders the bloom of the HDR image bound by `source`, which is `source_size` texels big.
+    // params.x is the threshold, params.y the knee.
+    pub(crate) fn render(
+        &self,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        assets: &mut AssetManager,
+        source: &BindGroup,
+        source_size: (u32, u32),
+        params: Vector4<f32>,
+    ) {
+        let mip_count = self.mips.len();
+        let mut passes = self.passes.iter();
+
+        // prefilter into the first level, then down the chain
+        let mut input = source;
+        let mut input_size = source_size;
+        for (level, mip) in self.mips.iter().enumerate() {
+            let pass = passes.next().unwrap();
+            let shader_id = if level == 0 {
+                BLOOM_PREFILTER_SHADER_ID
+            } else {
+                BLOOM_DOWNSAMPLE_SHADER_ID
+            };
+            let pipeline = &assets
+                .shaders
+                .get_shader(shader_id, &assets.bind_group_layouts)
+                .expect("Bloom shaders should be initialized")
+                .pipeline;
+            let uniform = PostEffectUniform::new(params, input_size.0, input_size.1);
+            queue.write_buffer(&pass.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+            Self::draw_pass(encoder, pipeline, input, &pass.bind_group, &mip.view, LoadOp::Clear(Color::BLACK));
+
+            input = &mip.input.bind_group;
+            input_size = (mip.width, mip.height);
+        }
+
+        // and back up, adding every level onto the next bigger one
+        let upsample_pipeline = assets
+            .shaders
+            .get_shader(BLOOM_UPSAMPLE_SHADER_ID, &assets.bind_group_layouts)
+            .and_then(|shader| shader.additive_pipeline.as_ref())
+            .expect("Bloom upsample shader should be initialized");
+        for level in (1..mip_count).rev() {
+            let pass = passes.next().unwrap();
+            let source_mip = &self.mips[level];
+            let target_mip = &self.mips[level - 1];
+            let uniform = PostEffectUniform::new(params, source_mip.width, source_mip.height);
+            queue.write_buffer(&pass.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+            Self::draw_pass(
+                encoder,
+                upsample_pipeline,
+                &source_mip.input.bind_group,
+                &pass.bind_group,
+                &target_mip.view,
+                LoadOp::Load,
+            );
+        }
+    }
+
+    fn draw_pass(
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        input: &BindGroup,
+        params: &BindGroup,
+        target: &TextureView,
+        load: LoadOp<Color>,
+    ) {
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Bloom Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, input, &[]);
+        rpass.set_bind_group(1, params, &[]);
+        rpass.draw(0..6, 0..1);
+    }
+}
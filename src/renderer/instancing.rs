@@ -0,0 +1,193 @@
+use std::ops::Range;
+
+use wgpu::*;
+
+use crate::asset_management::meshmanager::MeshId;
+use crate::object::{GameObjectId, ModelData};
+use crate::renderer::RenderStats;
+use crate::utils::bounds::Frustum;
+use crate::world::World;
+
+// Slots the model buffer has room for before it has to grow
+const INITIAL_MODEL_CAPACITY: usize = 64;
+
+// The model data of every drawable of a frame, in one storage buffer bound at group 1.
+// Shaders read it with the instance index.
+pub(crate) struct ModelRenderData {
+    buffer: Buffer,
+    capacity: usize,
+    pub(crate) bind_group: BindGroup,
+}
+
+impl ModelRenderData {
+    pub(crate) fn new(device: &Device, layout: &BindGroupLayout) -> Self {
+        Self::with_capacity(device, layout, INITIAL_MODEL_CAPACITY)
+    }
+
+    fn with_capacity(device: &Device, layout: &BindGroupLayout, capacity: usize) -> Self {
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Model Storage Buffer"),
+            size: (capacity * size_of::<ModelData>()) as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Model Bind Group"),
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        ModelRenderData {
+            buffer,
+            capacity,
+            bind_group,
+        }
+    }
+
+    // Uploads the models, growing the buffer to the next power of two if they don't fit
+    pub(crate) fn write(&mut self, device: &Device, queue: &Queue, layout: &BindGroupLayout, models: &[ModelData]) {
+        if models.len() > self.capacity {
+            *self = Self::with_capacity(device, layout, models.len().next_power_of_two());
+        }
+        if !models.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(models));
+        }
+    }
+}
+
+// Drawables showing the same mesh, drawn together with one instanced call per material range
+pub(crate) struct MeshBatch {
+    pub(crate) mesh: MeshId,
+    // all drawables of a mesh use the same shaders, so they're taken from the first one
+    pub(crate) first: GameObjectId,
+    // the instances in the camera's view
+    pub(crate) visible_instances: Range<u32>,
+    // the instances that cast shadows, seen by the camera or not
+    pub(crate) shadow_instances: Range<u32>,
+}
+
+// Every drawable of a frame with the slot of its model data. Drawables sharing a mesh get
+// neighbouring slots, ordered so the visible ones and the shadow casters each form one range:
+// visible non-casters, visible casters, culled casters, culled non-casters.
+#[derive(Default)]
+pub(crate) struct FrameDrawables {
+    pub(crate) models: Vec<ModelData>,
+    pub(crate) batches: Vec<MeshBatch>,
+    // drawables that draw themselves, and whether they're visible
+    pub(crate) singles: Vec<(GameObjectId, u32, bool)>,
+    // only the visible ones
+    pub(crate) transparent: Vec<(GameObjectId, u32)>,
+    pub(crate) stats: RenderStats,
+}
+
+impl FrameDrawables {
+    pub(crate) fn collect(world: &World, frustum: &Frustum) -> Self {
+        let mut frame = FrameDrawables::default();
+        let mut instanced = Vec::new();
+        let mut singles = Vec::new();
+        frame.collect_objects(world, frustum, &world.children, &mut instanced, &mut singles);
+
+        // stable, so the drawables of a batch stay in hierarchy order
+        instanced.sort_by_key(|(mesh, rank, _, _)| (*mesh, *rank));
+
+        for (mesh, rank, obj, lod_fade) in instanced {
+            // the mesh being faded out doesn't draw the drawable's blended parts a second time
+            let slot = frame.push_model(world, obj, rank <= 1 && lod_fade >= 0.0, lod_fade);
+            let batch = match frame.batches.last_mut() {
+                Some(batch) if batch.mesh == mesh => batch,
+                _ => {
+                    frame.batches.push(MeshBatch {
+                        mesh,
+                        first: obj,
+                        visible_instances: slot..slot,
+                        shadow_instances: slot..slot,
+                    });
+                    frame.batches.last_mut().unwrap()
+                }
+            };
+            if rank <= 1 {
+                batch.visible_instances.end = slot + 1;
+            }
+            match rank {
+                0 => batch.shadow_instances = slot + 1..slot + 1,
+                1 | 2 => batch.shadow_instances.end = slot + 1,
+                _ => (),
+            }
+        }
+        for (obj, visible) in singles {
+            let slot = frame.push_model(world, obj, visible, 0.0);
+            frame.singles.push((obj, slot, visible));
+        }
+        frame
+    }
+
+    fn collect_objects(
+        &mut self,
+        world: &World,
+        frustum: &Frustum,
+        children: &[GameObjectId],
+        instanced: &mut Vec<(MeshId, u8, GameObjectId, f32)>,
+        singles: &mut Vec<(GameObjectId, bool)>,
+    ) {
+        for child in children {
+            let Some(obj) = child.get() else {
+                continue;
+            };
+            if !obj.children.is_empty() {
+                self.collect_objects(world, frustum, &obj.children, instanced, singles);
+            }
+            let Some(drawable) = &obj.drawable else {
+                continue;
+            };
+            if drawable.hidden() {
+                self.stats.culled_objects += 1;
+                continue;
+            }
+
+            let visible = drawable
+                .bounds(world, *child)
+                .is_none_or(|bounds| frustum.intersects(&bounds));
+            if visible {
+                self.stats.drawn_objects += 1;
+            } else {
+                self.stats.culled_objects += 1;
+            }
+
+            match drawable.instanced_mesh() {
+                Some(mesh) => {
+                    let rank = |casts_shadows| match (visible, casts_shadows) {
+                        (true, false) => 0,
+                        (true, true) => 1,
+                        (false, true) => 2,
+                        (false, false) => 3,
+                    };
+                    match drawable.fading_mesh() {
+                        Some((old_mesh, progress)) => {
+                            instanced.push((mesh, rank(drawable.casts_shadows()), *child, progress));
+                            instanced.push((old_mesh, rank(false), *child, -progress));
+                        }
+                        None => instanced.push((mesh, rank(drawable.casts_shadows()), *child, 0.0)),
+                    }
+                }
+                None => singles.push((*child, visible)),
+            }
+        }
+    }
+
+    // Blended parts are only drawn for the visible drawables, once each
+    fn push_model(&mut self, world: &World, obj: GameObjectId, draws_blended: bool, lod_fade: f32) -> u32 {
+        let slot = self.models.len() as u32;
+        // collected this frame, so the object is alive and has a drawable
+        let drawable = obj.get().and_then(|obj| obj.drawable.as_ref()).unwrap();
+        let mut model = drawable.model_data(obj);
+        model.lod_fade = lod_fade;
+        self.models.push(model);
+        if draws_blended && drawable.has_transparency(world) {
+            self.transparent.push((obj, slot));
+        }
+        slot
+    }
+}
@@ -0,0 +1,1149 @@
+use std::error::Error;
+
+use image::RgbaImage;
+use log::{debug, error};
+use nalgebra::{Matrix4, Vector4};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+use winit::window::Window;
+use crate::asset_management::bindgroup_layout_manager::{CAMERA_UBGL_ID, LIGHT_UBGL_ID, MODEL_UBGL_ID, POST_EFFECT_BGL_ID};
+use crate::asset_management::shadermanager::{POST_PROCESS_SHADER_ID, SHADOW_SHADER_ID, SKY_SHADER_ID, TONE_MAPPING_SHADER_ID};
+use crate::asset_management::texturemanager::FALLBACK_SHININESS_TEXTURE;
+use crate::components::camera::CameraData;
+use crate::components::light::LightUniform;
+use crate::components::{CameraComp, Component, ComponentRef};
+use crate::drawables::mesh_renderer::MeshRenderer;
+use crate::object::GameObjectId;
+use crate::state::State;
+use crate::utils::bounds::Frustum;
+use crate::world::World;
+use bloom::BloomRenderData;
+use instancing::{FrameDrawables, ModelRenderData};
+use post_process::{PostEffectUniform, PostProcessStack};
+use shadows::ShadowRenderData;
+use sky::SkyRenderData;
+use view_targets::ViewTargets;
+
+pub mod bloom;
+pub mod instancing;
+pub mod post_process;
+pub mod shadows;
+pub mod sky;
+pub mod view_targets;
+
+// The scene is rendered into a floating point target, so lights and emissive surfaces can go
+// brighter than white. The camera's tone mapping brings it back into range at the end.
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Where the final, post-processed image of a frame ends up.
+pub enum RenderTarget {
+    /// The swapchain of the window the renderer was created with
+    Surface,
+    /// A plain texture that can be copied back to the CPU afterward
+    Texture(Texture),
+}
+
+pub struct RenderContext {
+    pub output: Option<SurfaceTexture>,
+    pub color_view: TextureView,
+    pub encoder: CommandEncoder,
+}
+
+// Where a post-processing pass writes to. Only the last pass of a camera writes into the camera's
+// viewport, the ones before fill their whole target.
+#[derive(Copy, Clone)]
+struct PostOutput<'a> {
+    view: &'a TextureView,
+    // x, y, width and height in pixels
+    viewport: Option<(u32, u32, u32, u32)>,
+    load: LoadOp<Color>,
+}
+
+impl<'a> PostOutput<'a> {
+    fn full(view: &'a TextureView) -> Self {
+        PostOutput {
+            view,
+            viewport: None,
+            load: LoadOp::Clear(Color::BLACK),
+        }
+    }
+}
+
+pub struct CameraRenderData {
+    camera_uniform_data: Box<CameraData>,
+    camera_uniform_buffer: Buffer,
+    camera_uniform_bind_group: BindGroup,
+}
+
+pub struct LightRenderData {
+    light_uniform_data: Box<LightUniform>,
+    light_uniform_buffer: Buffer,
+    light_uniform_bind_group: BindGroup,
+}
+
+// What the last frame drew, for profiling. Drawables seen by several cameras count once per camera.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    // drawables in a camera's view, or without bounds
+    pub drawn_objects: usize,
+    // drawables skipped because a camera can't see them. They still cast shadows.
+    pub culled_objects: usize,
+}
+
+// One entry of the opaque draws of a frame, sorted by shader
+enum OpaqueDraw {
+    // an index into FrameDrawables::batches
+    Batch(usize),
+    // a drawable that draws itself, with its model slot
+    Single(GameObjectId, u32),
+}
+
+#[allow(dead_code)]
+pub struct Renderer {
+    pub(crate) state: Box<State>,
+    window: Option<Window>,
+    target: RenderTarget,
+    camera_render_data: Option<CameraRenderData>,
+    light_render_data: Option<LightRenderData>,
+    shadow_render_data: Option<ShadowRenderData>,
+    sky_render_data: Option<SkyRenderData>,
+    model_render_data: Option<ModelRenderData>,
+    stats: RenderStats,
+
+    // The targets the cameras render the scene into and post-process it in, one per viewport size
+    view_targets: Vec<ViewTargets>,
+    post_process: PostProcessStack,
+}
+
+impl Renderer {
+    pub fn create_uniform_init(
+        bind_group_layout: &BindGroupLayout,
+        state: &State,
+        data: &'_ [u8],
+    ) -> (Buffer, BindGroup) {
+        let uniform_buffer = state.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Uniform Buffer"),
+            contents: data,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let uniform_bind_group = state.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Uniform Bind Group"),
+            layout: bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        (uniform_buffer, uniform_bind_group)
+    }
+
+    pub fn create_uniform_buffer(
+        bind_group_layout: &BindGroupLayout,
+        state: &State,
+        data_size: usize,
+    ) -> (Buffer, BindGroup) {
+        let uniform_buffer = state.device.create_buffer(&BufferDescriptor {
+            label: Some("Uniform Buffer"),
+            size: data_size as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: true,
+        });
+
+        let uniform_bind_group = state.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Uniform Bind Group"),
+            layout: bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        (uniform_buffer, uniform_bind_group)
+    }
+
+    pub(crate) async fn new(window: Window) -> Self {
+        let state = Box::new(State::new(&window).await);
+
+        Renderer {
+            state,
+            window: Some(window),
+            target: RenderTarget::Surface,
+            camera_render_data: None,
+            light_render_data: None,
+            shadow_render_data: None,
+            sky_render_data: None,
+            model_render_data: None,
+            stats: RenderStats::default(),
+            view_targets: Vec::new(),
+            post_process: PostProcessStack::default(),
+        }
+    }
+
+    // Creates a renderer that has no window and renders every frame into a texture instead.
+    // Use `render_to_image` to get the result back.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        let state = Box::new(State::new_headless(width, height).await?);
+
+        let target = Self::create_target_texture(&state.device, state.config.width, state.config.height, state.config.format);
+
+        Ok(Renderer {
+            state,
+            window: None,
+            target: RenderTarget::Texture(target),
+            camera_render_data: None,
+            light_render_data: None,
+            shadow_render_data: None,
+            sky_render_data: None,
+            model_render_data: None,
+            stats: RenderStats::default(),
+            view_targets: Vec::new(),
+            post_process: PostProcessStack::default(),
+        })
+    }
+
+    fn create_target_texture(device: &Device, width: u32, height: u32, format: TextureFormat) -> Texture {
+        device.create_texture(&TextureDescriptor {
+            label: Some("Render Target Texture"),
+            size: Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    // Gives the world's assets access to this renderer's device and prepares everything needed to draw it
+    pub fn init_for_world(&mut self, world: &mut World) {
+        world.assets.invalidate();
+        world.assets.init_runtime(self.state.device.clone(), self.state.queue.clone());
+        world.assets.shaders.set_sample_count(self.state.sample_count);
+        self.init(world);
+    }
+
+    // Sets the MSAA samples of the scene, 1 turns it off. Counts the adapter doesn't support are
+    // lowered to the next supported one, which is returned.
+    pub fn set_msaa_samples(&mut self, samples: u32, world: &mut World) -> u32 {
+        let samples = self
+            .state
+            .supported_sample_counts
+            .iter()
+            .copied()
+            .filter(|&count| count <= samples)
+            .max()
+            .unwrap_or(1);
+        if samples != self.state.sample_count {
+            self.state.set_sample_count(samples);
+            // re-created with the new sample count when the cameras use them next
+            self.view_targets.clear();
+            world.assets.shaders.set_sample_count(samples);
+        }
+        samples
+    }
+
+    // How many drawables the cameras of the last frame drew and culled
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    pub fn msaa_samples(&self) -> u32 {
+        self.state.sample_count
+    }
+
+    // The MSAA sample counts the adapter supports, always including 1
+    pub fn supported_msaa_samples(&self) -> &[u32] {
+        &self.state.supported_sample_counts
+    }
+
+    pub fn init(&mut self, world: &World) {
+        let camera_data = Box::new(CameraData::empty());
+        let camera_bgl = world.assets.bind_group_layouts.get_bind_group_layout(CAMERA_UBGL_ID).unwrap();
+        let (camera_uniform_buffer, camera_uniform_bind_group) = Self::create_uniform_init(
+            &camera_bgl,
+            &self.state,
+            bytemuck::cast_slice(&[*camera_data]),
+        );
+        self.camera_render_data = Some(CameraRenderData {
+            camera_uniform_data: camera_data,
+            camera_uniform_buffer,
+            camera_uniform_bind_group,
+        });
+
+        // light views for the shadow maps use the same layout as the camera
+        let shadows = ShadowRenderData::new(&self.state.device, camera_bgl);
+
+        let light_data = Box::new(LightUniform::empty());
+        let light_bgl = world.assets.bind_group_layouts.get_bind_group_layout(LIGHT_UBGL_ID).unwrap();
+        let light_uniform_buffer = self.state.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Light Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[*light_data]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let sky = SkyRenderData::new(&self.state.device);
+        let light_uniform_bind_group = Self::create_light_bind_group(
+            &self.state.device,
+            light_bgl,
+            &light_uniform_buffer,
+            &shadows,
+            &sky,
+            &sky.fallback_view,
+        );
+        self.shadow_render_data = Some(shadows);
+        self.sky_render_data = Some(sky);
+        let model_bgl = world.assets.bind_group_layouts.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
+        self.model_render_data = Some(ModelRenderData::new(&self.state.device, model_bgl));
+        self.light_render_data = Some(LightRenderData {
+            light_uniform_data: light_data,
+            light_uniform_buffer,
+            light_uniform_bind_group,
+        });
+    }
+
+    fn create_light_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        light_uniform_buffer: &Buffer,
+        shadows: &ShadowRenderData,
+        sky: &SkyRenderData,
+        sky_view: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Light Uniform Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: light_uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&shadows.array_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&shadows.sampler),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: sky.uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(sky_view),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: BindingResource::Sampler(&sky.sampler),
+                },
+            ],
+        })
+    }
+
+    // Writes the sky's parameters and re-creates the light bind group when the sky's texture changed
+    fn update_sky(&mut self, world: &mut World) {
+        let sky = self
+            .sky_render_data
+            .as_mut()
+            .expect("Sky render data should be initialized");
+        let texture = sky.update(&self.state.queue, world.skybox.as_ref(), &world.assets.textures);
+        if texture == sky.bound_texture {
+            return;
+        }
+
+        let light_data = self
+            .light_render_data
+            .as_mut()
+            .expect("Light render data should be initialized");
+        let shadows = self
+            .shadow_render_data
+            .as_ref()
+            .expect("Shadow render data should be initialized");
+        let light_bgl = world.assets.bind_group_layouts.get_bind_group_layout(LIGHT_UBGL_ID).unwrap();
+        let sky_view = match texture {
+            Some(id) => &world
+                .assets
+                .textures
+                .get_runtime_texture_ensure_init(id)
+                .expect("Sky texture should exist")
+                .view,
+            None => &sky.fallback_view,
+        };
+        light_data.light_uniform_bind_group = Self::create_light_bind_group(
+            &self.state.device,
+            light_bgl,
+            &light_data.light_uniform_buffer,
+            shadows,
+            sky,
+            sky_view,
+        );
+        sky.bound_texture = texture;
+    }
+
+    pub fn render_world(&mut self, world: &mut World) -> bool {
+        let mut ctx = match self.begin_render() {
+            Ok(ctx) => ctx,
+            Err(SurfaceError::Lost) => {
+                self.state.resize(self.state.size);
+                return false;
+            }
+            Err(SurfaceError::OutOfMemory) => {
+                error!("The application ran out of GPU memory!");
+                return false;
+            }
+            Err(e) => {
+                error!("Surface error: {:?}", e);
+                return false;
+            }
+        };
+
+        self.render(&mut ctx, world);
+
+        self.end_render(ctx);
+
+        true
+    }
+
+    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.state.resize(new_size);
+
+        // the cameras' viewports change with the window, so the view targets are created again
+        // with their new sizes
+        self.view_targets.clear();
+
+        if let RenderTarget::Texture(target) = &mut self.target {
+            *target = Self::create_target_texture(
+                &self.state.device,
+                self.state.config.width,
+                self.state.config.height,
+                self.state.config.format,
+            );
+        }
+    }
+
+    fn begin_render(&mut self) -> Result<RenderContext, SurfaceError> {
+        let (output, color_view) = match &self.target {
+            RenderTarget::Surface => {
+                let output = self.acquire_surface_texture()?;
+                let color_view = output
+                    .texture
+                    .create_view(&TextureViewDescriptor::default());
+                (Some(output), color_view)
+            }
+            RenderTarget::Texture(target) => {
+                (None, target.create_view(&TextureViewDescriptor::default()))
+            }
+        };
+        let encoder = self.create_encoder();
+
+        Ok(RenderContext {
+            output,
+            color_view,
+            encoder,
+        })
+    }
+
+    fn create_encoder(&self) -> CommandEncoder {
+        self.state.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Main Encoder"),
+        })
+    }
+
+    fn acquire_surface_texture(&mut self) -> Result<SurfaceTexture, SurfaceError> {
+        let surface = self.state.surface.as_ref().ok_or(SurfaceError::Lost)?;
+        let output = surface.get_current_texture()?;
+        if !output.suboptimal {
+            return Ok(output);
+        }
+
+        drop(output);
+        self.state.recreate_surface();
+        self.state
+            .surface
+            .as_ref()
+            .ok_or(SurfaceError::Lost)?
+            .get_current_texture()
+    }
+
+    fn render(&mut self, ctx: &mut RenderContext, world: &mut World) {
+        let world_ptr: *mut World = world;
+
+        let light_data = self
+            .light_render_data
+            .as_mut()
+            .expect("Light render data should be initialized");
+        light_data.light_uniform_data.update(world);
+        self.state.queue.write_buffer(
+            &light_data.light_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[*light_data.light_uniform_data]),
+        );
+        let shadow_view_projs = light_data.light_uniform_data.shadow_view_projs();
+        self.update_sky(world);
+
+        let world_children: *const Vec<GameObjectId> = &world.children;
+        unsafe {
+            // all drawables are updated once, before any camera reads their buffers
+            self.traverse_and_update(&mut *world_ptr, &*world_children);
+        }
+
+        let cameras = Self::cameras_in_render_order(world);
+        if cameras.is_empty() {
+            debug!("No camera active");
+        }
+
+        self.stats = RenderStats::default();
+        // every target is cleared by the first camera rendering into it
+        let mut cleared_targets = Vec::new();
+        for camera in cameras {
+            let target = camera.borrow().target;
+            let clear = !cleared_targets.contains(&target);
+            // the shadow maps don't depend on the camera, so they're only rendered once
+            let shadow_view_projs = match cleared_targets.is_empty() {
+                true => Some(shadow_view_projs.as_slice()),
+                false => None,
+            };
+            let Some(stats) = self.render_camera(ctx, world, &camera, shadow_view_projs, clear) else {
+                continue;
+            };
+            if clear {
+                cleared_targets.push(target);
+            }
+            self.stats.drawn_objects += stats.drawn_objects;
+            self.stats.culled_objects += stats.culled_objects;
+
+            // all cameras write the same buffers, so every camera is submitted before the next one
+            // writes its own
+            let encoder = std::mem::replace(&mut ctx.encoder, self.create_encoder());
+            self.state.queue.submit(Some(encoder.finish()));
+        }
+
+        // without a camera, the window is left black
+        if !cleared_targets.contains(&None) {
+            Self::render_clear_pass(&mut ctx.encoder, &ctx.color_view);
+        }
+
+        // keeps the targets a camera used this frame, and resets them for the next one
+        self.view_targets.retain_mut(|targets| std::mem::take(&mut targets.used));
+    }
+
+    // Every enabled camera, the ones rendering into textures first, and then by priority. Cameras
+    // with the same priority are rendered in the order they were created in.
+    fn cameras_in_render_order(world: &World) -> Vec<ComponentRef<CameraComp>> {
+        let mut cameras: Vec<_> = world
+            .get_components::<CameraComp>()
+            .into_iter()
+            .filter(|camera| camera.is_enabled())
+            .collect();
+        cameras.sort_by_key(|camera| {
+            let camera = camera.borrow();
+            let creation_order = unsafe { camera.get_parent() }.creation_order;
+            (camera.target.is_none(), camera.priority, creation_order)
+        });
+        cameras
+    }
+
+    // The view targets for a viewport of the given size, created if no camera used that size yet
+    fn view_targets_index(&mut self, world: &World, width: u32, height: u32) -> usize {
+        let sample_count = self.state.sample_count;
+        let index = match self
+            .view_targets
+            .iter()
+            .position(|targets| targets.width == width && targets.height == height && targets.sample_count == sample_count)
+        {
+            Some(index) => index,
+            None => {
+                self.view_targets.push(ViewTargets::new(
+                    &self.state.device,
+                    &world.assets.bind_group_layouts,
+                    width,
+                    height,
+                    sample_count,
+                    self.state.config.format,
+                ));
+                self.view_targets.len() - 1
+            }
+        };
+        self.view_targets[index].used = true;
+        index
+    }
+
+    // Renders the scene as the camera sees it into the camera's viewport of its target. The
+    // shadow maps are rendered first if there are view projections for them. Returns None if
+    // the camera's target doesn't exist or isn't a render texture.
+    fn render_camera(
+        &mut self,
+        ctx: &mut RenderContext,
+        world: &mut World,
+        camera: &ComponentRef<CameraComp>,
+        shadow_view_projs: Option<&[Matrix4<f32>]>,
+        clear: bool,
+    ) -> Option<RenderStats> {
+        let world_ptr: *mut World = world;
+        let (target, viewport) = {
+            let camera = camera.borrow();
+            (camera.target, camera.viewport)
+        };
+
+        let target_view;
+        let (output_view, target_width, target_height) = match target {
+            None => (&ctx.color_view, self.state.config.width, self.state.config.height),
+            Some(id) => {
+                if !world.assets.textures.get_raw_texture(id).is_some_and(|raw| raw.render_target) {
+                    debug!("Camera target {id} isn't a render texture");
+                    return None;
+                }
+                let texture = &world.assets.textures.get_runtime_texture_ensure_init(id)?.texture;
+                target_view = texture.create_view(&TextureViewDescriptor::default());
+                (&target_view, texture.width(), texture.height())
+            }
+        };
+        let (x, y, width, height) = viewport.to_pixels(target_width, target_height);
+        let targets_index = self.view_targets_index(world, width, height);
+
+        // copied, as drawables may look at the camera while updating
+        let projection_matrix = {
+            let mut camera = camera.borrow_mut();
+            camera.resize(width as f32, height as f32);
+            camera.projection_matrix()
+        };
+        let camera_obj = unsafe { camera.borrow().get_parent() };
+        let render_data = self
+            .camera_render_data
+            .as_mut()
+            .expect("Camera render data should be initialized");
+        render_data
+            .camera_uniform_data
+            .update(&projection_matrix, &camera_obj.transform);
+        self.state.queue.write_buffer(
+            &render_data.camera_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[*render_data.camera_uniform_data]),
+        );
+        let frustum = Frustum::from_view_proj(&render_data.camera_uniform_data.proj_view_mat);
+
+        let frame = FrameDrawables::collect(world, &frustum);
+        let stats = frame.stats;
+        let model_bgl = world.assets.bind_group_layouts.get_bind_group_layout(MODEL_UBGL_ID).unwrap();
+        let model_data = self
+            .model_render_data
+            .as_mut()
+            .expect("Model render data should be initialized");
+        model_data.write(&self.state.device, &self.state.queue, model_bgl, &frame.models);
+
+        if let Some(shadow_view_projs) = shadow_view_projs {
+            self.render_shadows(&mut ctx.encoder, world, &frame, shadow_view_projs);
+        }
+
+        let model_data = self
+            .model_render_data
+            .as_ref()
+            .expect("Model render data should be initialized");
+        let light_data = self
+            .light_render_data
+            .as_ref()
+            .expect("Light render data should be initialized");
+        let render_data = self
+            .camera_render_data
+            .as_ref()
+            .expect("Camera render data should be initialized");
+        let targets = &self.view_targets[targets_index];
+
+        // with MSAA, only the resolved image is kept
+        let (view, resolve_target, store) = match &targets.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&targets.offscreen_view), StoreOp::Discard),
+            None => (&targets.offscreen_view, None, StoreOp::Store),
+        };
+        let mut rpass = ctx.encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Offscreen Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &targets.depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+
+        rpass.set_bind_group(0, &render_data.camera_uniform_bind_group, &[]);
+        rpass.set_bind_group(1, &model_data.bind_group, &[]);
+        rpass.set_bind_group(3, &light_data.light_uniform_bind_group, &[]);
+
+        let mut opaque = Vec::new();
+        for (i, batch) in frame.batches.iter().enumerate() {
+            if batch.visible_instances.is_empty() {
+                continue;
+            }
+            if let Some(drawable) = batch.first.get().and_then(|obj| obj.drawable.as_ref()) {
+                for shader in drawable.shaders(world) {
+                    opaque.push((shader, OpaqueDraw::Batch(i)));
+                }
+            }
+        }
+        for (obj, slot, _) in frame.singles.iter().filter(|(_, _, visible)| *visible) {
+            if let Some(drawable) = obj.get().and_then(|obj| obj.drawable.as_ref()) {
+                for shader in drawable.shaders(world) {
+                    opaque.push((shader, OpaqueDraw::Single(*obj, *slot)));
+                }
+            }
+        }
+
+        // grouped by shader, so every pipeline is bound once. Within a group the draws keep
+        // their order, as the sort is stable.
+        opaque.sort_by_key(|(shader, _)| *shader);
+        let mut bound_shader = None;
+        for (shader_id, draw) in opaque {
+            if bound_shader != Some(shader_id) {
+                let Some(shader) = world
+                    .assets
+                    .shaders
+                    .get_shader(shader_id, &world.assets.bind_group_layouts)
+                else {
+                    continue;
+                };
+                rpass.set_pipeline(&shader.pipeline);
+                bound_shader = Some(shader_id);
+            }
+            match draw {
+                OpaqueDraw::Batch(i) => {
+                    let batch = &frame.batches[i];
+                    unsafe {
+                        MeshRenderer::draw_mesh(&mut *world_ptr, &mut rpass, batch.mesh, Some(shader_id), batch.visible_instances.clone());
+                    }
+                }
+                OpaqueDraw::Single(obj, slot) => {
+                    if let Some(drawable) = obj.get().and_then(|obj| obj.drawable.as_ref()) {
+                        unsafe {
+                            drawable.draw(&mut *world_ptr, &mut rpass, shader_id, slot);
+                        }
+                    }
+                }
+            }
+        }
+
+        // the sky only covers what the opaque geometry left empty, and is seen through blended drawables
+        if world.skybox.is_some() {
+            if let Some(sky_shader) = world
+                .assets
+                .shaders
+                .get_shader(SKY_SHADER_ID, &world.assets.bind_group_layouts)
+            {
+                rpass.set_pipeline(&sky_shader.pipeline);
+                rpass.set_bind_group(1, &light_data.light_uniform_bind_group, &[]);
+                rpass.draw(0..3, 0..1);
+                rpass.set_bind_group(1, &model_data.bind_group, &[]);
+            }
+        }
+
+        // blended drawables are drawn last, back to front, so everything behind them shows through
+        let camera_pos = camera_obj.transform.position();
+        let mut transparent: Vec<_> = frame
+            .transparent
+            .iter()
+            .filter_map(|(obj, slot)| Some((obj.get()?, *slot)))
+            .collect();
+        transparent.sort_by(|(a, _), (b, _)| {
+            let dist_a = (a.transform.position() - camera_pos).norm_squared();
+            let dist_b = (b.transform.position() - camera_pos).norm_squared();
+            dist_b.total_cmp(&dist_a)
+        });
+        for (obj, slot) in transparent {
+            if let Some(drawable) = &obj.drawable {
+                unsafe {
+                    drawable.draw_transparent(&mut *world_ptr, &mut rpass, slot);
+                }
+            }
+        }
+        drop(rpass);
+
+        let output = PostOutput {
+            view: output_view,
+            viewport: Some((x, y, width, height)),
+            load: match clear {
+                true => LoadOp::Clear(Color::BLACK),
+                false => LoadOp::Load,
+            },
+        };
+        self.render_final_pass(world, &mut ctx.encoder, &camera.borrow(), targets_index, output);
+
+        Some(stats)
+    }
+
+    // Renders the depth of all shadow casters into one shadow map layer per light
+    fn render_shadows(&self, encoder: &mut CommandEncoder, world: &mut World, frame: &FrameDrawables, view_projs: &[Matrix4<f32>]) {
+        let shadows = self
+            .shadow_render_data
+            .as_ref()
+            .expect("Shadow render data should be initialized");
+        let model_data = self
+            .model_render_data
+            .as_ref()
+            .expect("Model render data should be initialized");
+        shadows.write_light_views(&self.state.queue, view_projs);
+
+        let world_ptr: *mut World = world;
+        let shader = world
+            .assets
+            .shaders
+            .get_shader(SHADOW_SHADER_ID, &world.assets.bind_group_layouts)
+            .expect("Shadow pipeline should be initialized");
+
+        for layer in 0..view_projs.len() {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Shadow Render Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: shadows.layer_view(layer),
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+
+            rpass.set_pipeline(&shader.pipeline);
+            rpass.set_bind_group(0, shadows.light_view_bind_group(layer), &[]);
+            rpass.set_bind_group(1, &model_data.bind_group, &[]);
+
+            unsafe {
+                for batch in &frame.batches {
+                    if !batch.shadow_instances.is_empty() {
+                        MeshRenderer::draw_mesh_shadow(&mut *world_ptr, &mut rpass, batch.mesh, batch.shadow_instances.clone());
+                    }
+                }
+                for (obj, slot, _) in &frame.singles {
+                    if let Some(drawable) = obj.get().and_then(|obj| obj.drawable.as_ref()) {
+                        drawable.draw_shadow(&mut *world_ptr, &mut rpass, *slot);
+                    }
+                }
+            }
+        }
+    }
+
+    unsafe fn traverse_and_update(&self, world: &mut World, children: &[GameObjectId]) {
+        let world_ptr: *mut World = world;
+        for child in children {
+            let Some(obj) = child.get() else {
+                continue;
+            };
+            if !obj.children.is_empty() {
+                self.traverse_and_update(&mut *world_ptr, &obj.children);
+            }
+            let mut obj = *child;
+            if let Some(drawable) = obj.get_mut().and_then(|obj| obj.drawable.as_mut()) {
+                drawable.update(&mut *world_ptr, *child, &self.state.queue);
+            }
+        }
+    }
+
+    // Tone maps the HDR image the camera rendered into the view targets with the camera's
+    // settings, and runs the post effects on the result. The last pass writes straight into the
+    // camera's viewport of its target.
+    fn render_final_pass(
+        &mut self,
+        world: &mut World,
+        encoder: &mut CommandEncoder,
+        camera: &CameraComp,
+        targets_index: usize,
+        final_output: PostOutput,
+    ) {
+        let exposure = camera.exposure;
+        let tone_mapping = camera.tone_mapping;
+        let bloom = camera
+            .bloom
+            .then_some((camera.bloom_threshold, camera.bloom_knee, camera.bloom_intensity));
+
+        let device = &self.state.device;
+        let targets = &mut self.view_targets[targets_index];
+        let width = targets.width;
+        let height = targets.height;
+        let scene_input = &targets.scene_input.bind_group;
+        let data = &mut targets.post_process;
+        let assets = &mut world.assets;
+        if let Some((threshold, knee, _)) = bloom {
+            // the bloom textures are only created once a camera with bloom renders at this size
+            let bloom_data = targets
+                .bloom
+                .get_or_insert_with(|| BloomRenderData::new(device, assets, width, height));
+            let params = Vector4::new(threshold, knee, 0.0, 0.0);
+            bloom_data.render(&self.state.queue, encoder, assets, scene_input, (width, height), params);
+        }
+
+        let effect_bgl = assets.bind_group_layouts.get_bind_group_layout(POST_EFFECT_BGL_ID).unwrap();
+        let effects = self.post_process.enabled_effects();
+
+        // without bloom, the tone mapping reads a white texel that's added with an intensity of 0
+        let fallback_texture = assets
+            .textures
+            .get_runtime_texture_ensure_init(FALLBACK_SHININESS_TEXTURE)
+            .expect("Fallback texture should be initialized");
+        let (bloom_view, bloom_intensity) = match (bloom, &targets.bloom) {
+            (Some((_, _, intensity)), Some(bloom_data)) => (bloom_data.view(), intensity),
+            _ => (&fallback_texture.view, 0.0),
+        };
+        let params = Vector4::new(exposure, tone_mapping.shader_index(), bloom_intensity, 0.0);
+        let uniform_buffer = data.uniform_buffer(device, 0);
+        self.state.queue.write_buffer(
+            uniform_buffer,
+            0,
+            bytemuck::bytes_of(&PostEffectUniform::new(params, width, height)),
+        );
+        let tone_mapping_bind_group = Self::create_post_effect_bind_group(device, effect_bgl, uniform_buffer, bloom_view);
+        let tone_mapping_shader = assets
+            .shaders
+            .get_shader(TONE_MAPPING_SHADER_ID, &assets.bind_group_layouts)
+            .expect("Tone mapping shader should be initialized");
+        let output = if effects.is_empty() {
+            final_output
+        } else {
+            PostOutput::full(&data.targets[0].view)
+        };
+        Self::render_post_pass(
+            encoder,
+            &tone_mapping_shader.pipeline,
+            scene_input,
+            Some(&tone_mapping_bind_group),
+            output,
+        );
+
+        // every effect reads the output of the one before
+        for (i, effect) in effects.iter().enumerate() {
+            let uniform = PostEffectUniform::new(effect.params(), width, height);
+            let uniform_buffer = data.uniform_buffer(device, i + 1);
+            self.state.queue.write_buffer(uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+            // effects without a texture of their own, or with a missing one, get a white texel
+            let texture_id = effect
+                .texture()
+                .filter(|id| assets.textures.get_raw_texture(*id).is_some())
+                .unwrap_or(FALLBACK_SHININESS_TEXTURE);
+            let texture = assets
+                .textures
+                .get_runtime_texture_ensure_init(texture_id)
+                .expect("Post effect texture should be initialized");
+            let effect_bind_group = Self::create_post_effect_bind_group(device, effect_bgl, uniform_buffer, &texture.view);
+
+            let input = &data.targets[i % 2].input.bind_group;
+            let output = if i == effects.len() - 1 {
+                final_output
+            } else {
+                PostOutput::full(&data.targets[(i + 1) % 2].view)
+            };
+
+            // unknown effect shaders just pass the image on
+            match assets.shaders.get_shader(effect.shader(), &assets.bind_group_layouts) {
+                Some(shader) => {
+                    Self::render_post_pass(encoder, &shader.pipeline, input, Some(&effect_bind_group), output);
+                }
+                None => {
+                    let shader = assets
+                        .shaders
+                        .get_shader(POST_PROCESS_SHADER_ID, &assets.bind_group_layouts)
+                        .expect("PostProcess shader should be initialized");
+                    Self::render_post_pass(encoder, &shader.pipeline, input, None, output);
+                }
+            }
+        }
+    }
+
+    fn create_post_effect_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        uniform_buffer: &Buffer,
+        texture: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Post Effect Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(texture),
+                },
+            ],
+        })
+    }
+
+    fn render_post_pass(
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        input: &BindGroup,
+        effect: Option<&BindGroup>,
+        output: PostOutput,
+    ) {
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("PostProcess Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: output.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: output.load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        if let Some((x, y, width, height)) = output.viewport {
+            rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+        }
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, input, &[]);
+        if let Some(effect) = effect {
+            rpass.set_bind_group(1, effect, &[]);
+        }
+        rpass.draw(0..6, 0..1);
+    }
+
+    fn render_clear_pass(encoder: &mut CommandEncoder, output: &TextureView) {
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Clear Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+    }
+
+    fn end_render(&mut self, ctx: RenderContext) {
+        self.state.queue.submit(Some(ctx.encoder.finish()));
+        if let Some(output) = ctx.output {
+            output.present();
+        }
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+    }
+
+    // Renders the world into the texture target and copies the result back into an image.
+    // Only works for renderers created with `new_headless`.
+    pub fn render_to_image(&mut self, world: &mut World) -> Result<RgbaImage, Box<dyn Error>> {
+        if !matches!(self.target, RenderTarget::Texture(_)) {
+            return Err("Renderer doesn't render into a texture target".into());
+        }
+
+        if !self.render_world(world) {
+            return Err("Rendering the world failed".into());
+        }
+
+        self.read_target_texture()
+    }
+
+    fn read_target_texture(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let RenderTarget::Texture(target) = &self.target else {
+            return Err("Renderer doesn't render into a texture target".into());
+        };
+
+        let width = target.width();
+        let height = target.height();
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let device = &self.state.device;
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: Some("Render Target Readback Buffer"),
+            size: (padded_bytes_per_row * height) as BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: target,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &readback,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            target.size(),
+        );
+        self.state.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(Maintain::Wait);
+        receiver.recv()??;
+
+        let is_bgra = matches!(
+            target.format(),
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        );
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks_exact(padded_bytes_per_row as usize) {
+                for pixel in row[..unpadded_bytes_per_row as usize].chunks_exact(4) {
+                    if is_bgra {
+                        pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+                    } else {
+                        pixels.extend_from_slice(pixel);
+                    }
+                }
+            }
+        }
+        readback.unmap();
+
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| "Read back texture data didn't match the target size".into())
+    }
+
+    // The post-processing effects applied to every frame, in order
+    pub fn post_process(&self) -> &PostProcessStack {
+        &self.post_process
+    }
+
+    pub fn post_process_mut(&mut self) -> &mut PostProcessStack {
+        &mut self.post_process
+    }
+
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    pub fn window(&self) -> &Window {
+        self.window.as_ref().expect("Renderer has no window")
+    }
+
+    pub fn window_mut(&mut self) -> &mut Window {
+        self.window.as_mut().expect("Renderer has no window")
+    }
+}
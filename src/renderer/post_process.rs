@@ -0,0 +1,253 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Vector2, Vector4};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::asset_management::shadermanager::{
+    ShaderId, COLOR_GRADING_SHADER_ID, FXAA_SHADER_ID, GAMMA_SHADER_ID, TONE_MAPPING_SHADER_ID,
+    VIGNETTE_SHADER_ID,
+};
+use crate::asset_management::texturemanager::TextureId;
+use crate::components::camera::ToneMapping;
+
+// A single step of the post-processing chain
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+    // Applies the ACES filmic curve once more. The camera already tone maps the HDR image before
+    // any effect runs, so this is only useful with the camera's tone mapping turned off.
+    ToneMapping { exposure: f32 },
+    // Raises colors to the power of 1 / gamma. The targets already convert to sRGB, so 1.0 keeps
+    // the image as it is.
+    Gamma { gamma: f32 },
+    // Fast approximate anti-aliasing, smooths jagged edges
+    Fxaa,
+    // Darkens the image towards the corners. Smoothness is the part of the way from the center
+    // to the corners that fades.
+    Vignette { intensity: f32, smoothness: f32 },
+    // Remaps colors with a lookup table, see shaders/post/color_grading.wgsl for its layout.
+    // The table has to be a non-sRGB texture.
+    ColorGrading { lut: TextureId, intensity: f32 },
+    // An effect added with ShaderManager::add_post_process_shader. The params end up in
+    // effect.params, the texture in effectTexture.
+    Custom {
+        shader: ShaderId,
+        params: Vector4<f32>,
+        texture: Option<TextureId>,
+    },
+}
+
+impl PostEffect {
+    pub fn shader(&self) -> ShaderId {
+        match self {
+            PostEffect::ToneMapping { .. } => TONE_MAPPING_SHADER_ID,
+            PostEffect::Gamma { .. } => GAMMA_SHADER_ID,
+            PostEffect::Fxaa => FXAA_SHADER_ID,
+            PostEffect::Vignette { .. } => VIGNETTE_SHADER_ID,
+            PostEffect::ColorGrading { .. } => COLOR_GRADING_SHADER_ID,
+            PostEffect::Custom { shader, .. } => *shader,
+        }
+    }
+
+    pub fn params(&self) -> Vector4<f32> {
+        match self {
+            PostEffect::ToneMapping { exposure } => {
+                Vector4::new(*exposure, ToneMapping::Aces.shader_index(), 0.0, 0.0)
+            }
+            PostEffect::Gamma { gamma } => Vector4::new(*gamma, 0.0, 0.0, 0.0),
+            PostEffect::Fxaa => Vector4::zeros(),
+            PostEffect::Vignette { intensity, smoothness } => Vector4::new(*intensity, *smoothness, 0.0, 0.0),
+            PostEffect::ColorGrading { intensity, .. } => Vector4::new(*intensity, 0.0, 0.0, 0.0),
+            PostEffect::Custom { params, .. } => *params,
+        }
+    }
+
+    pub fn texture(&self) -> Option<TextureId> {
+        match self {
+            PostEffect::ColorGrading { lut, .. } => Some(*lut),
+            PostEffect::Custom { texture, .. } => *texture,
+            _ => None,
+        }
+    }
+}
+
+pub struct PostEffectSlot {
+    pub effect: PostEffect,
+    pub enabled: bool,
+}
+
+// The post effects of a renderer, applied in order to the rendered image. Without any enabled
+// effect, the image is shown as it is.
+#[derive(Default)]
+pub struct PostProcessStack {
+    effects: Vec<PostEffectSlot>,
+}
+
+impl PostProcessStack {
+    // Adds the effect at the end of the chain and returns its index
+    pub fn push(&mut self, effect: PostEffect) -> usize {
+        self.effects.push(PostEffectSlot { effect, enabled: true });
+        self.effects.len() - 1
+    }
+
+    pub fn insert(&mut self, index: usize, effect: PostEffect) {
+        self.effects.insert(index, PostEffectSlot { effect, enabled: true });
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<PostEffect> {
+        (index < self.effects.len()).then(|| self.effects.remove(index).effect)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&PostEffect> {
+        self.effects.get(index).map(|slot| &slot.effect)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut PostEffect> {
+        self.effects.get_mut(index).map(|slot| &mut slot.effect)
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(slot) = self.effects.get_mut(index) {
+            slot.enabled = enabled;
+        }
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.effects.get(index).is_some_and(|slot| slot.enabled)
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PostEffectSlot> {
+        self.effects.iter()
+    }
+
+    pub(crate) fn enabled_effects(&self) -> Vec<PostEffect> {
+        self.effects
+            .iter()
+            .filter(|slot| slot.enabled)
+            .map(|slot| slot.effect.clone())
+            .collect()
+    }
+}
+
+// Has to match PostEffectData in shaders/post/prelude.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PostEffectUniform {
+    params: Vector4<f32>,
+    texel_size: Vector2<f32>,
+    _padding: [f32; 2],
+}
+
+impl PostEffectUniform {
+    pub(crate) fn new(params: Vector4<f32>, width: u32, height: u32) -> Self {
+        PostEffectUniform {
+            params,
+            texel_size: Vector2::new(1.0 / width.max(1) as f32, 1.0 / height.max(1) as f32),
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+unsafe impl Zeroable for PostEffectUniform {}
+unsafe impl Pod for PostEffectUniform {}
+
+// An image as input of a post-processing pass
+pub(crate) struct PostProcessPass {
+    pub(crate) bind_group: BindGroup,
+}
+
+impl PostProcessPass {
+    pub(crate) fn new(device: &Device, layout: &BindGroupLayout, view: &TextureView) -> Self {
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("PostProcess Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("PostProcess Bind Group"),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                }
+            ],
+        });
+
+        Self { bind_group }
+    }
+}
+
+// An intermediate image between two effects
+pub(crate) struct PingPongTarget {
+    #[allow(dead_code)]
+    texture: Texture,
+    pub(crate) view: TextureView,
+    pub(crate) input: PostProcessPass,
+}
+
+// Effects take turns writing into one of two targets while reading from the other
+pub(crate) struct PostProcessRenderData {
+    pub(crate) targets: [PingPongTarget; 2],
+    uniform_buffers: Vec<Buffer>,
+}
+
+impl PostProcessRenderData {
+    pub(crate) fn new(device: &Device, input_layout: &BindGroupLayout, width: u32, height: u32, format: TextureFormat) -> Self {
+        let create_target = || {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("Post Process Target Texture"),
+                size: Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            let input = PostProcessPass::new(device, input_layout, &view);
+            PingPongTarget { texture, view, input }
+        };
+
+        PostProcessRenderData {
+            targets: [create_target(), create_target()],
+            uniform_buffers: vec![],
+        }
+    }
+
+    // The uniform buffer of the effect at `index` in the enabled chain. Every effect has its own,
+    // as all of them are written before the frame is submitted.
+    pub(crate) fn uniform_buffer(&mut self, device: &Device, index: usize) -> &Buffer {
+        while self.uniform_buffers.len() <= index {
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Post Effect Uniform Buffer"),
+                contents: bytemuck::bytes_of(&PostEffectUniform::zeroed()),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+            self.uniform_buffers.push(buffer);
+        }
+        &self.uniform_buffers[index]
+    }
+}
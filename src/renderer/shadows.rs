@@ -0,0 +1,112 @@
+use nalgebra::Matrix4;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::components::light::MAX_SHADOWS;
+
+// Width and height of every shadow map layer
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+
+// One depth layer per shadow casting light, together with the light's view to render it
+pub(crate) struct ShadowRenderData {
+    #[allow(dead_code)]
+    texture: Texture,
+    pub(crate) array_view: TextureView,
+    layer_views: Vec<TextureView>,
+    pub(crate) sampler: Sampler,
+    light_view_buffers: Vec<Buffer>,
+    light_view_bind_groups: Vec<BindGroup>,
+}
+
+impl ShadowRenderData {
+    pub(crate) fn new(device: &Device, light_view_bgl: &BindGroupLayout) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Shadow Map Texture"),
+            size: Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: MAX_SHADOWS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Depth32Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let array_view = texture.create_view(&TextureViewDescriptor {
+            label: Some("Shadow Map Array View"),
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let layer_views = (0..MAX_SHADOWS as u32)
+            .map(|layer| {
+                texture.create_view(&TextureViewDescriptor {
+                    label: Some("Shadow Map Layer View"),
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        // linear filtering compares the four closest texels, which smooths the PCF a bit further
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Shadow Map Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let mut light_view_buffers = Vec::with_capacity(MAX_SHADOWS);
+        let mut light_view_bind_groups = Vec::with_capacity(MAX_SHADOWS);
+        for _ in 0..MAX_SHADOWS {
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Light View Buffer"),
+                contents: bytemuck::cast_slice(Matrix4::<f32>::identity().as_slice()),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Light View Bind Group"),
+                layout: light_view_bgl,
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+            light_view_buffers.push(buffer);
+            light_view_bind_groups.push(bind_group);
+        }
+
+        ShadowRenderData {
+            texture,
+            array_view,
+            layer_views,
+            sampler,
+            light_view_buffers,
+            light_view_bind_groups,
+        }
+    }
+
+    pub(crate) fn write_light_views(&self, queue: &Queue, view_projs: &[Matrix4<f32>]) {
+        for (buffer, view_proj) in self.light_view_buffers.iter().zip(view_projs) {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(view_proj.as_slice()));
+        }
+    }
+
+    pub(crate) fn layer_view(&self, layer: usize) -> &TextureView {
+        &self.layer_views[layer]
+    }
+
+    pub(crate) fn light_view_bind_group(&self, layer: usize) -> &BindGroup {
+        &self.light_view_bind_groups[layer]
+    }
+}
@@ -0,0 +1,168 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::Vector3;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::asset_management::texturemanager::{TextureId, TextureManager};
+
+// What is drawn behind everything else
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkySource {
+    // Fades from the zenith color at the top, over the horizon color, to the ground color below
+    Gradient {
+        zenith: Vector3<f32>,
+        horizon: Vector3<f32>,
+        ground: Vector3<f32>,
+    },
+    // A cube texture, see TextureManager::load_cube_texture and load_equirectangular_texture
+    Cubemap(TextureId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skybox {
+    pub source: SkySource,
+    // Scales the sky's colors, in the same units as light colors
+    pub intensity: f32,
+    // Lit shaders take their ambient light and reflections from the sky instead of using
+    // World::ambient_light
+    pub lighting: bool,
+}
+
+impl Skybox {
+    pub fn gradient(zenith: Vector3<f32>, horizon: Vector3<f32>, ground: Vector3<f32>) -> Self {
+        Skybox {
+            source: SkySource::Gradient { zenith, horizon, ground },
+            intensity: 1.0,
+            lighting: false,
+        }
+    }
+
+    pub fn cubemap(texture: TextureId) -> Self {
+        Skybox {
+            source: SkySource::Cubemap(texture),
+            intensity: 1.0,
+            lighting: false,
+        }
+    }
+}
+
+impl Default for Skybox {
+    // A clear day
+    fn default() -> Self {
+        Skybox::gradient(
+            Vector3::new(0.15, 0.35, 0.8),
+            Vector3::new(0.7, 0.8, 0.9),
+            Vector3::new(0.25, 0.22, 0.2),
+        )
+    }
+}
+
+const SKY_MODE_NONE: u32 = 0;
+const SKY_MODE_GRADIENT: u32 = 1;
+const SKY_MODE_CUBEMAP: u32 = 2;
+
+// Has to match Sky in shader3d.wgsl and sky.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SkyUniform {
+    zenith: Vector3<f32>,
+    mode: u32,
+    horizon: Vector3<f32>,
+    intensity: f32,
+    ground: Vector3<f32>,
+    lighting: u32,
+    // the blurriest mip level of the cube texture
+    max_lod: f32,
+    _padding: [u32; 3],
+}
+
+unsafe impl Zeroable for SkyUniform {}
+unsafe impl Pod for SkyUniform {}
+
+pub(crate) struct SkyRenderData {
+    pub(crate) uniform_buffer: Buffer,
+    pub(crate) sampler: Sampler,
+    // bound while the sky has no cube texture
+    #[allow(dead_code)]
+    fallback_texture: Texture,
+    pub(crate) fallback_view: TextureView,
+    // the cube texture the light bind group was created with
+    pub(crate) bound_texture: Option<TextureId>,
+}
+
+impl SkyRenderData {
+    pub(crate) fn new(device: &Device) -> Self {
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sky Uniform Buffer"),
+            contents: bytemuck::bytes_of(&SkyUniform::zeroed()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Sky Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let fallback_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Sky Fallback Texture"),
+            size: Extent3d { width: 1, height: 1, depth_or_array_layers: 6 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let fallback_view = fallback_texture.create_view(&TextureViewDescriptor {
+            label: Some("Sky Fallback View"),
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        SkyRenderData {
+            uniform_buffer,
+            sampler,
+            fallback_texture,
+            fallback_view,
+            bound_texture: None,
+        }
+    }
+
+    // Writes the sky's parameters and returns the cube texture it needs, if any.
+    // Cube textures that don't exist or aren't cubes leave the sky black.
+    pub(crate) fn update(&self, queue: &Queue, skybox: Option<&Skybox>, textures: &TextureManager) -> Option<TextureId> {
+        let mut uniform = SkyUniform::zeroed();
+        let mut texture = None;
+
+        if let Some(skybox) = skybox {
+            uniform.intensity = skybox.intensity;
+            uniform.lighting = skybox.lighting as u32;
+            match &skybox.source {
+                SkySource::Gradient { zenith, horizon, ground } => {
+                    uniform.mode = SKY_MODE_GRADIENT;
+                    uniform.zenith = *zenith;
+                    uniform.horizon = *horizon;
+                    uniform.ground = *ground;
+                }
+                SkySource::Cubemap(id) => {
+                    if let Some(raw) = textures
+                        .get_raw_texture(*id)
+                        .filter(|raw| raw.dimension == TextureViewDimension::Cube)
+                    {
+                        uniform.mode = SKY_MODE_CUBEMAP;
+                        uniform.max_lod = raw.mip_levels.saturating_sub(1) as f32;
+                        texture = Some(*id);
+                    }
+                }
+            }
+        }
+
+        if uniform.mode == SKY_MODE_NONE {
+            uniform.lighting = 0;
+        }
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        texture
+    }
+}
@@ -0,0 +1,105 @@
+use wgpu::*;
+
+use crate::asset_management::bindgroup_layout_manager::{BindGroupLayoutManager, POST_PROCESS_BGL_ID};
+use crate::renderer::bloom::BloomRenderData;
+use crate::renderer::post_process::{PostProcessPass, PostProcessRenderData};
+use crate::renderer::HDR_FORMAT;
+
+// Everything a camera renders into before its image ends up in the camera's target, with the
+// size of the camera's viewport. Cameras with viewports of the same size share them, as every
+// camera is submitted before the next one starts.
+pub(crate) struct ViewTargets {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) sample_count: u32,
+    // the HDR image of the scene before post-processing
+    #[allow(dead_code)]
+    offscreen_texture: Texture,
+    pub(crate) offscreen_view: TextureView,
+    // multisampled target the scene is drawn into with MSAA, resolved into the offscreen texture
+    #[allow(dead_code)]
+    msaa_texture: Option<Texture>,
+    pub(crate) msaa_view: Option<TextureView>,
+    #[allow(dead_code)]
+    depth_texture: Texture,
+    pub(crate) depth_view: TextureView,
+    // the offscreen image as input of the first post-processing pass
+    pub(crate) scene_input: PostProcessPass,
+    pub(crate) post_process: PostProcessRenderData,
+    // created when a camera with bloom first renders into them
+    pub(crate) bloom: Option<BloomRenderData>,
+    // whether a camera rendered into them this frame. Unused ones are dropped after the frame.
+    pub(crate) used: bool,
+}
+
+impl ViewTargets {
+    pub(crate) fn new(
+        device: &Device,
+        bgls: &BindGroupLayoutManager,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        format: TextureFormat,
+    ) -> Self {
+        let size = Extent3d { width, height, depth_or_array_layers: 1 };
+        let offscreen_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let offscreen_view = offscreen_texture.create_view(&TextureViewDescriptor::default());
+
+        let msaa_texture = (sample_count > 1).then(|| {
+            device.create_texture(&TextureDescriptor {
+                label: Some("MSAA Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+        });
+        let msaa_view = msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&TextureViewDescriptor::default()));
+
+        let depth_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Depth Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Depth32Float,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[TextureFormat::Depth32Float],
+        });
+        let depth_view = depth_texture.create_view(&TextureViewDescriptor::default());
+
+        let input_layout = bgls.get_bind_group_layout(POST_PROCESS_BGL_ID).unwrap();
+        let scene_input = PostProcessPass::new(device, input_layout, &offscreen_view);
+        let post_process = PostProcessRenderData::new(device, input_layout, width, height, format);
+
+        ViewTargets {
+            width,
+            height,
+            sample_count,
+            offscreen_texture,
+            offscreen_view,
+            msaa_texture,
+            msaa_view,
+            depth_texture,
+            depth_view,
+            scene_input,
+            post_process,
+            bloom: None,
+            used: false,
+        }
+    }
+}
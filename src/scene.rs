@@ -0,0 +1,255 @@
+use std::error::Error;
+use std::fs;
+
+use nalgebra::{UnitQuaternion, Vector3};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use log::warn;
+
+use crate::components::registry::{FieldMap, FieldValue};
+use crate::object::GameObjectId;
+use crate::prefab::{PrefabNode, PrefabOverrides};
+use crate::scene_loader::SceneLoader;
+use crate::world::World;
+
+// A world stored as text. Objects keep their hierarchy, local transforms, mesh drawables and
+// every component type that is registered in the world's component registry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SceneDesc {
+    pub objects: Vec<ObjectDesc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectDesc {
+    pub name: String,
+    #[serde(default)]
+    pub transform: TransformDesc,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drawable: Option<DrawableDesc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<ComponentDesc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ObjectDesc>,
+}
+
+// Local transformation relative to the parent object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransformDesc {
+    pub position: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+    #[serde(default)]
+    pub compound_pos_first: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DrawableDesc {
+    // Refers to the mesh by its mesh path, like "models/crate.fbx#Cube"
+    MeshRenderer { mesh: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentDesc {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "FieldMap::is_empty")]
+    pub fields: FieldMap,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        TransformDesc {
+            position: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            compound_pos_first: false,
+        }
+    }
+}
+
+impl SceneDesc {
+    pub fn from_world(world: &World) -> SceneDesc {
+        SceneDesc {
+            objects: world
+                .children
+                .iter()
+                .map(|child| ObjectDesc::from_object(world, *child))
+                .collect(),
+        }
+    }
+
+    pub fn from_ron(text: &str) -> Result<SceneDesc, Box<dyn Error>> {
+        Ok(ron::from_str(text)?)
+    }
+
+    pub fn to_ron(&self) -> Result<String, Box<dyn Error>> {
+        Ok(ron::ser::to_string_pretty(self, PrettyConfig::default())?)
+    }
+
+    pub fn load(path: &str) -> Result<SceneDesc, Box<dyn Error>> {
+        Self::from_ron(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    // Creates all objects of the scene in the world and returns the root objects
+    pub fn spawn(&self, world: &mut World) -> Result<Vec<GameObjectId>, Box<dyn Error>> {
+        let mut roots = Vec::new();
+        for desc in &self.objects {
+            let obj = desc.spawn(world)?;
+            world.add_child(obj);
+            roots.push(obj);
+        }
+        Ok(roots)
+    }
+}
+
+impl ObjectDesc {
+    pub fn from_object(world: &World, obj: GameObjectId) -> ObjectDesc {
+        PrefabNode::from_object(world, obj).to_desc(world)
+    }
+
+    // Creates the object and its children. The object is not added to the world root.
+    pub fn spawn(&self, world: &mut World) -> Result<GameObjectId, Box<dyn Error>> {
+        PrefabNode::from_desc(world, self)?.spawn(world, &self.transform, &PrefabOverrides::default())
+    }
+}
+
+// Asset ids only mean something in the running world, so scene files refer to meshes by their
+// mesh path instead. Other assets have no path yet and are stored by id.
+impl ComponentDesc {
+    // Replaces mesh ids with mesh paths, so the component can be written to a scene file
+    pub fn save_assets(&self, world: &World, owner: &str) -> ComponentDesc {
+        let mut comp = self.clone();
+        for (name, value) in comp.fields.iter_mut() {
+            Self::save_asset(world, owner, name, value);
+        }
+        comp
+    }
+
+    fn save_asset(world: &World, owner: &str, name: &str, value: &mut FieldValue) {
+        match value {
+            FieldValue::Mesh(id) => match world.assets.meshes.get_mesh_path(*id) {
+                Some(path) => *value = FieldValue::MeshPath(path.to_owned()),
+                None => warn!("Mesh field \"{name}\" of \"{owner}\" has no mesh path and won't load"),
+            },
+            FieldValue::Material(_) | FieldValue::Texture(_) => {
+                warn!("Asset field \"{name}\" of \"{owner}\" is saved as a plain id and might not load")
+            }
+            FieldValue::List(values) => {
+                for value in values {
+                    Self::save_asset(world, owner, name, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Replaces mesh paths with mesh ids, importing model files where needed
+    pub fn resolve_assets(&self, world: &mut World) -> Result<ComponentDesc, Box<dyn Error>> {
+        let mut comp = self.clone();
+        for value in comp.fields.values_mut() {
+            Self::resolve_asset(world, value)?;
+        }
+        Ok(comp)
+    }
+
+    fn resolve_asset(world: &mut World, value: &mut FieldValue) -> Result<(), Box<dyn Error>> {
+        match value {
+            FieldValue::MeshPath(path) => *value = FieldValue::Mesh(SceneLoader::find_or_load_mesh(world, path)?),
+            FieldValue::List(values) => {
+                for value in values {
+                    Self::resolve_asset(world, value)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use super::SceneDesc;
+    use crate::components::registry::FieldValue;
+    use crate::components::GravityComp;
+    use crate::world::World;
+
+    #[test]
+    fn scene_round_trips_hierarchy_transforms_and_fields() {
+        let mut world = World::new();
+        let mut root = world.new_object("Root");
+        let mut child = world.new_object("Child");
+        world.add_child(root);
+        root.add_child(child);
+        root.transform.set_local_position(Vector3::new(1.0, 2.0, 3.0));
+        child
+            .transform
+            .set_local_rotation(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.5));
+        child.transform.set_nonuniform_local_scale(Vector3::new(1.0, 2.0, 3.0));
+        child.add_component::<GravityComp>().acceleration_per_sec = 3.0;
+        child.set_component_enabled::<GravityComp>(false);
+
+        let text = SceneDesc::from_world(&world).to_ron().unwrap();
+        let mut loaded = World::new();
+        let roots = SceneDesc::from_ron(&text).unwrap().spawn(&mut loaded).unwrap();
+
+        assert_eq!(loaded.children, roots);
+        let [root] = roots[..] else {
+            panic!("expected a single root, got {roots:?}");
+        };
+        assert_eq!(root.name, "Root");
+        assert_eq!(*root.transform.local_position(), Vector3::new(1.0, 2.0, 3.0));
+
+        let [child] = root.children[..] else {
+            panic!("expected a single child, got {:?}", root.children);
+        };
+        assert_eq!(child.name, "Child");
+        assert_eq!(child.parent, Some(root));
+        assert!((child.transform.local_rotation().angle() - 0.5).abs() < 1e-5);
+        assert_eq!(*child.transform.local_scale(), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(
+            loaded.get_component_field(child, "Gravity", "acceleration_per_sec"),
+            Some(FieldValue::Float(3.0))
+        );
+        assert!(!child.get_component::<GravityComp>().unwrap().is_enabled());
+    }
+
+    #[test]
+    fn unknown_and_mistyped_fields_are_skipped() {
+        let text = r#"(objects: [(name: "Falling", components: [(type: "Gravity", fields: {
+            "acceleration_per_sec": Float(2.0),
+            "bogus": Float(1.0),
+            "max_acceleration": Bool(true),
+        })])])"#;
+        let mut world = World::new();
+        let roots = SceneDesc::from_ron(text).unwrap().spawn(&mut world).unwrap();
+
+        let get = |field| world.get_component_field(roots[0], "Gravity", field);
+        assert_eq!(get("acceleration_per_sec"), Some(FieldValue::Float(2.0)));
+        assert_eq!(get("max_acceleration"), Some(FieldValue::Float(100.0)));
+        assert_eq!(get("bogus"), None);
+    }
+
+    #[test]
+    fn unknown_component_type_fails_without_creating_objects() {
+        let text = r#"(objects: [(name: "Thing", children: [(name: "Part", components: [(type: "Nope")])])])"#;
+        let mut world = World::new();
+
+        let error = SceneDesc::from_ron(text).unwrap().spawn(&mut world).unwrap_err();
+        assert!(error.to_string().contains("Nope"), "{error}");
+        assert!(world.objects.is_empty());
+    }
+}
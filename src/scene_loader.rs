@@ -6,7 +6,7 @@ use std::rc::Rc;
 use bytemuck::Contiguous;
 use itertools::izip;
 use log::warn;
-use nalgebra::{Matrix4, Vector2, Vector3};
+use nalgebra::{Matrix4, UnitQuaternion, Vector2, Vector3};
 use num_traits::{ToPrimitive, Zero};
 use russimp::material::{DataContent, MaterialProperty, PropertyTypeInfo, TextureType};
 use russimp::node::Node;
@@ -14,22 +14,128 @@ use russimp::scene::{PostProcess, Scene};
 use russimp::Vector3D;
 use wgpu::TextureFormat;
 
-use crate::asset_management::materialmanager::{Material, MaterialId};
+use crate::asset_management::materialmanager::{BlendMode, Material, MaterialId, PbrMaterial};
 use crate::asset_management::mesh::{Mesh, Vertex3D};
-use crate::asset_management::shadermanager::ShaderId;
+use crate::asset_management::meshmanager::MeshId;
+use crate::asset_management::shadermanager::{ShaderId, DIM3_SHADER_ID};
 use crate::asset_management::texturemanager::{TextureId, FALLBACK_DIFFUSE_TEXTURE};
 use crate::drawables::mesh_renderer::MeshRenderer;
 use crate::object::GameObjectId;
+use crate::prefab::{Prefab, PrefabId, PrefabNode};
+use crate::scene::TransformDesc;
 use crate::utils::math::ExtraMatrixMath;
 use crate::world::World;
 
+// Alpha cutoff of textured materials that don't state their own, same as the glTF default
+const DEFAULT_ALPHA_CUTOFF: f32 = 0.5;
+
 #[allow(dead_code)]
 pub struct SceneLoader;
 
 #[allow(dead_code)]
 impl SceneLoader {
     pub fn load(world: &mut World, path: &str) -> Result<GameObjectId, Box<dyn Error>> {
-        let mut scene = Scene::from_file(
+        let mut scene = Self::import(path)?;
+
+        let root = match &scene.root {
+            Some(node) => node.clone(),
+            None => return Ok(world.new_object("EmptyLoadedObject")),
+        };
+
+        let materials = Self::load_materials(&scene, world);
+        Self::update_material_indicies(&mut scene, materials);
+        let root_object = world.new_object(&root.name);
+        Self::load_rec(world, &scene, path, &root, &root.name, root_object);
+        Ok(root_object)
+    }
+
+    // Only imports the meshes and materials of the file, without creating any objects.
+    // The meshes can be found by their mesh path afterward.
+    pub fn load_meshes(world: &mut World, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut scene = Self::import(path)?;
+
+        let root = match &scene.root {
+            Some(node) => node.clone(),
+            None => return Ok(()),
+        };
+
+        let materials = Self::load_materials(&scene, world);
+        Self::update_material_indicies(&mut scene, materials);
+        Self::load_meshes_rec(world, &scene, path, &root, &root.name);
+        Ok(())
+    }
+
+    // Imports the file once and registers it as a prefab named after the path,
+    // so it can be instantiated many times without touching the file again
+    pub fn load_prefab(world: &mut World, path: &str) -> Result<PrefabId, Box<dyn Error>> {
+        if let Some(id) = world.prefabs.find_prefab_by_name(path) {
+            return Ok(id);
+        }
+
+        let mut scene = Self::import(path)?;
+
+        let root = match scene.root.clone() {
+            Some(node) => {
+                let materials = Self::load_materials(&scene, world);
+                Self::update_material_indicies(&mut scene, materials);
+                Self::load_prefab_rec(world, &scene, path, &node, &node.name)
+            }
+            None => PrefabNode {
+                name: "EmptyLoadedObject".to_owned(),
+                transform: TransformDesc::default(),
+                mesh: None,
+                components: vec![],
+                children: vec![],
+            },
+        };
+
+        Ok(world.prefabs.add_prefab(Prefab::new(path, root)))
+    }
+
+    // The path a mesh of a node in a model file is registered under, like
+    // "models/crate.fbx#RootNode/Cube". See node_children for how the node path is built.
+    pub fn mesh_path(path: &str, node_path: &str) -> String {
+        format!("{path}#{node_path}")
+    }
+
+    // The children of a node with their node paths. A node path is made of the names of all
+    // nodes from the root down. Siblings sharing a name get their index appended, like
+    // "RootNode/Wheel[2]", so every node of a file has its own path.
+    fn node_children(node: &Node, node_path: &str) -> Vec<(Rc<Node>, String)> {
+        let children = node.children.borrow();
+        children
+            .iter()
+            .enumerate()
+            .map(|(i, child)| {
+                let path = match children.iter().filter(|other| other.name == child.name).count() {
+                    1 => format!("{node_path}/{}", child.name),
+                    _ => format!("{node_path}/{}[{i}]", child.name),
+                };
+                (child.clone(), path)
+            })
+            .collect()
+    }
+
+    // Returns the mesh with the given mesh path, importing its model file if it wasn't loaded yet
+    pub fn find_or_load_mesh(world: &mut World, mesh_path: &str) -> Result<MeshId, Box<dyn Error>> {
+        if let Some(id) = world.assets.meshes.find_mesh_by_path(mesh_path) {
+            return Ok(id);
+        }
+
+        let (file, _) = mesh_path
+            .split_once('#')
+            .ok_or_else(|| format!("\"{mesh_path}\" is not a mesh path"))?;
+        Self::load_meshes(world, file)?;
+
+        world
+            .assets
+            .meshes
+            .find_mesh_by_path(mesh_path)
+            .ok_or_else(|| format!("\"{file}\" has no mesh for \"{mesh_path}\"").into())
+    }
+
+    fn import(path: &str) -> Result<Scene, Box<dyn Error>> {
+        Ok(Scene::from_file(
             path,
             vec![
                 PostProcess::CalculateTangentSpace,
@@ -41,26 +147,53 @@ impl SceneLoader {
                 PostProcess::ForceGenerateNormals,
                 PostProcess::EmbedTextures,
             ],
-        )?;
+        )?)
+    }
 
-        let root = match &scene.root {
-            Some(node) => node.clone(),
-            None => return Ok(world.new_object("EmptyLoadedObject")),
+    fn load_rec(
+        world: &mut World,
+        scene: &Scene,
+        path: &str,
+        node: &Rc<Node>,
+        node_path: &str,
+        mut node_obj: GameObjectId,
+    ) {
+        Self::load_data(world, scene, path, node, node_path, node_obj);
+        for (child, child_path) in Self::node_children(node, node_path) {
+            let obj = world.new_object(&child.name);
+            node_obj.add_child(obj);
+            Self::load_rec(world, scene, path, &child, &child_path, obj);
+        }
+    }
+
+    fn load_prefab_rec(world: &mut World, scene: &Scene, path: &str, node: &Rc<Node>, node_path: &str) -> PrefabNode {
+        let mesh = Self::load_mesh(world, scene, path, node, node_path);
+        let (position, rotation, scale) = match mesh {
+            Some(_) => Self::node_transform(node),
+            None => (Vector3::zeros(), UnitQuaternion::identity(), Vector3::new(1.0, 1.0, 1.0)),
         };
 
-        let materials = Self::load_materials(&scene, world);
-        Self::update_material_indicies(&mut scene, materials);
-        let root_object = world.new_object(&root.name);
-        Self::load_rec(world, &scene, &root, root_object);
-        Ok(root_object)
+        PrefabNode {
+            name: node.name.clone(),
+            transform: TransformDesc {
+                position,
+                rotation,
+                scale,
+                ..TransformDesc::default()
+            },
+            mesh,
+            components: vec![],
+            children: Self::node_children(node, node_path)
+                .iter()
+                .map(|(child, child_path)| Self::load_prefab_rec(world, scene, path, child, child_path))
+                .collect(),
+        }
     }
 
-    fn load_rec(world: &mut World, scene: &Scene, node: &Rc<Node>, mut node_obj: GameObjectId) {
-        Self::load_data(world, scene, node, node_obj);
-        for child in node.children.borrow().iter() {
-            let obj = world.new_object(&child.name);
-            node_obj.add_child(obj);
-            Self::load_rec(world, scene, child, obj);
+    fn load_meshes_rec(world: &mut World, scene: &Scene, path: &str, node: &Rc<Node>, node_path: &str) {
+        Self::load_mesh(world, scene, path, node, node_path);
+        for (child, child_path) in Self::node_children(node, node_path) {
+            Self::load_meshes_rec(world, scene, path, &child, &child_path);
         }
     }
 
@@ -112,9 +245,36 @@ impl SceneLoader {
         }
     }
 
-    fn load_data(world: &mut World, scene: &Scene, node: &Rc<Node>, node_obj: GameObjectId) {
+    fn load_data(world: &mut World, scene: &Scene, path: &str, node: &Rc<Node>, node_path: &str, node_obj: GameObjectId) {
+        let id = match Self::load_mesh(world, scene, path, node, node_path) {
+            Some(id) => id,
+            None => return,
+        };
+
+        let mut node_obj = node_obj;
+        node_obj.drawable = Some(MeshRenderer::new(id));
+
+        // set transformations
+        let (position, rotation, scale) = Self::node_transform(node);
+        node_obj.transform.set_local_position(position);
+        node_obj.transform.set_local_rotation(rotation);
+        node_obj.transform.set_nonuniform_local_scale(scale);
+    }
+
+    fn node_transform(node: &Node) -> (Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>) {
+        let t = node.transformation;
+        Matrix4::from([
+            [t.a1, t.b1, t.c1, t.d1],
+            [t.a2, t.b2, t.c2, t.d2],
+            [t.a3, t.b3, t.c3, t.d3],
+            [t.a4, t.b4, t.c4, t.d4],
+        ])
+        .decompose() // convert row to column major (assimp to cgmath)
+    }
+
+    fn load_mesh(world: &mut World, scene: &Scene, path: &str, node: &Rc<Node>, node_path: &str) -> Option<MeshId> {
         if node.meshes.is_empty() {
-            return;
+            return None;
         }
 
         let mut positions: Vec<Vector3<f32>> = Vec::new();
@@ -194,35 +354,18 @@ impl SceneLoader {
 
         let mesh = Mesh::new(vertices, None, Some(material_ranges));
         let id = world.assets.meshes.add_mesh(mesh);
+        world
+            .assets
+            .meshes
+            .set_mesh_path(id, &Self::mesh_path(path, node_path));
 
-        let mut node_obj = node_obj;
-        node_obj.drawable = Some(MeshRenderer::new(id));
-
-        // set transformations
-        let t = node.transformation;
-        let (position, rotation, scale) = Matrix4::from([
-            [t.a1, t.b1, t.c1, t.d1],
-            [t.a2, t.b2, t.c2, t.d2],
-            [t.a3, t.b3, t.c3, t.d3],
-            [t.a4, t.b4, t.c4, t.d4],
-        ])
-        .decompose(); // convert row to column major (assimp to cgmath)
-
-        node_obj.transform.set_local_position(position);
-        node_obj.transform.set_local_rotation(rotation);
-        node_obj.transform.set_nonuniform_local_scale(scale);
+        Some(id)
     }
 
     fn load_materials(scene: &Scene, world: &mut World) -> HashMap<u32, MaterialId> {
-        let shader3d = world
-            .assets
-            .shaders
-            .find_shader_by_name("3D")
-            .unwrap_or_default();
-
         let mut mapping = HashMap::new();
         for (i, material) in scene.materials.iter().enumerate() {
-            let mat_id = Self::load_material(world, material, shader3d);
+            let mat_id = Self::load_material(world, material, DIM3_SHADER_ID);
             mapping.insert(i as u32, mat_id);
         }
         mapping
@@ -239,10 +382,12 @@ impl SceneLoader {
         }
     }
 
+    // Color textures should be loaded as sRGB, data like normals as plain unorm
     fn load_texture(
         world: &mut World,
         texture: Rc<RefCell<russimp::material::Texture>>,
-    ) -> TextureId {
+        format: TextureFormat,
+    ) -> Option<TextureId> {
         // TODO: Don't load textures that were loaded before and are just shared between two materials
         let texture = texture.borrow();
         match &texture.data {
@@ -251,8 +396,8 @@ impl SceneLoader {
                 let decoded = match image::load_from_memory(data) {
                     Ok(decoded) => decoded,
                     Err(e) => {
-                        warn!("Failed to load texture: {}. Using fallback texture.", e);
-                        return FALLBACK_DIFFUSE_TEXTURE;
+                        warn!("Failed to load texture: {}", e);
+                        return None;
                     }
                 };                let rgba = decoded.into_rgba8();
                 let mut data = Vec::with_capacity((rgba.width() * rgba.height() * 4) as usize);
@@ -262,12 +407,12 @@ impl SceneLoader {
                     data.push(pixel[0]); // R
                     data.push(pixel[3]); // A
                 }
-                world.assets.textures.add_texture(
+                Some(world.assets.textures.add_texture(
                     rgba.width(),
                     rgba.height(),
-                    TextureFormat::Bgra8UnormSrgb,
+                    format,
                     Some(data),
-                )
+                ))
             }
         }
     }
@@ -284,8 +429,9 @@ impl SceneLoader {
         match prop {
             None => default(),
             Some(prop) => match &prop.data {
+                // colors are often stored with an alpha value, which is ignored here
                 PropertyTypeInfo::FloatArray(arr) => {
-                    if arr.len() == 3 {
+                    if arr.len() >= 3 {
                         Vector3::new(arr[0], arr[1], arr[2])
                     } else {
                         warn!(
@@ -315,6 +461,10 @@ impl SceneLoader {
         }
     }
 
+    fn has_property(properties: &[MaterialProperty], key: &str) -> bool {
+        properties.iter().any(|prop| prop.key.contains(key))
+    }
+
     fn extract_float_property(properties: &[MaterialProperty], key: &str, default: f32) -> f32 {
         let prop = properties.iter().find(|prop| prop.key.contains(key));
         match prop {
@@ -334,26 +484,118 @@ impl SceneLoader {
         let name =
             Self::extract_string_property(&material.properties, "name", || "Material".to_string());
 
-        let diffuse = Self::extract_vec3_property(&material.properties, "diffuse", || {
-            Vector3::new(0.788, 0.788, 0.788)
+        let properties = &material.properties;
+        let textures = &material.textures;
+
+        // metallic-roughness materials, like the ones of glTF files, have a base color instead of a diffuse color
+        let is_pbr = [TextureType::BaseColor, TextureType::Metalness, TextureType::Roughness]
+            .iter()
+            .any(|ty| textures.contains_key(ty))
+            || Self::has_property(properties, "$mat.metallicFactor")
+            || Self::has_property(properties, "$mat.roughnessFactor");
+
+        let default_diffuse = || Vector3::new(0.788, 0.788, 0.788);
+        let diffuse = if is_pbr && Self::has_property(properties, "$clr.base") {
+            Self::extract_vec3_property(properties, "$clr.base", default_diffuse)
+        } else {
+            Self::extract_vec3_property(properties, "diffuse", default_diffuse)
+        };
+        let diffuse_tex = textures
+            .get(&TextureType::BaseColor)
+            .filter(|_| is_pbr)
+            .or_else(|| textures.get(&TextureType::Diffuse));
+        let diffuse_tex_id = diffuse_tex.map(|tex| {
+            // a texture that fails to load shows up as the fallback pattern instead of a plain color
+            Self::load_texture(world, tex.clone(), TextureFormat::Bgra8UnormSrgb)
+                .unwrap_or(FALLBACK_DIFFUSE_TEXTURE)
         });
-        let diffuse_tex = material.textures.get(&TextureType::Diffuse);
-        let diffuse_tex_id = diffuse_tex.map(|tex| Self::load_texture(world, tex.clone()));
 
-        let normal_tex = material.textures.get(&TextureType::Normals);
-        let normal_tex_id = normal_tex.map(|tex| Self::load_texture(world, tex.clone()));
+        let normal_tex = textures.get(&TextureType::Normals);
+        let normal_tex_id = normal_tex
+            .and_then(|tex| Self::load_texture(world, tex.clone(), TextureFormat::Bgra8Unorm));
 
-        let shininess = Self::extract_float_property(&material.properties, "shininess", 0.0);
+        let shininess_tex = textures
+            .get(&TextureType::Specular)
+            .or_else(|| textures.get(&TextureType::Shininess));
+        let shininess_tex_id = shininess_tex
+            .and_then(|tex| Self::load_texture(world, tex.clone(), TextureFormat::Bgra8Unorm));
+
+        let emissive_intensity = Self::extract_float_property(properties, "$mat.emissiveIntensity", 1.0);
+        let emissive = Self::extract_vec3_property(properties, "$clr.emissive", Vector3::zeros) * emissive_intensity;
+        let emissive_tex = textures
+            .get(&TextureType::Emissive)
+            .or_else(|| textures.get(&TextureType::EmissionColor));
+        let emissive_tex_id = emissive_tex
+            .and_then(|tex| Self::load_texture(world, tex.clone(), TextureFormat::Bgra8UnormSrgb));
+
+        let pbr = is_pbr.then(|| Self::load_pbr_material(world, material));
+
+        let opacity = Self::extract_float_property(properties, "$mat.opacity", 1.0);
+        let blend_mode = Self::blend_mode(properties, opacity, diffuse_tex_id.is_some());
+
+        let shininess = Self::extract_float_property(properties, "shininess", 0.0);
         let new_material = Material {
             name,
             diffuse,
             shininess,
             diffuse_texture: diffuse_tex_id,
             normal_texture: normal_tex_id,
-            shininess_texture: None,
-            opacity: 1.0,
+            shininess_texture: shininess_tex_id,
+            opacity,
+            blend_mode,
+            emissive,
+            emissive_texture: emissive_tex_id,
+            pbr,
             shader,
         };
         world.assets.materials.add_material(new_material)
     }
+
+    // glTF files state the blend mode, other formats only give an opacity. Textures may still have
+    // cut out parts then, so they are alpha tested.
+    fn blend_mode(properties: &[MaterialProperty], opacity: f32, has_diffuse_texture: bool) -> BlendMode {
+        let alpha_mode = Self::extract_string_property(properties, "$mat.gltf.alphaMode", String::new);
+        let cutoff = Self::extract_float_property(properties, "$mat.gltf.alphaCutoff", DEFAULT_ALPHA_CUTOFF);
+        match alpha_mode.as_str() {
+            "OPAQUE" => BlendMode::Opaque,
+            "MASK" => BlendMode::AlphaTest { cutoff },
+            "BLEND" => BlendMode::AlphaBlend,
+            _ if opacity < 1.0 => BlendMode::AlphaBlend,
+            _ if has_diffuse_texture => BlendMode::AlphaTest { cutoff },
+            _ => BlendMode::Opaque,
+        }
+    }
+
+    fn load_pbr_material(world: &mut World, material: &russimp::material::Material) -> PbrMaterial {
+        let properties = &material.properties;
+        let textures = &material.textures;
+
+        let metallic_tex = textures.get(&TextureType::Metalness);
+        let roughness_tex = textures.get(&TextureType::Roughness);
+
+        let metallic_tex_id = metallic_tex
+            .and_then(|tex| Self::load_texture(world, tex.clone(), TextureFormat::Bgra8Unorm));
+        // glTF files pack both into the same texture, which only needs to be loaded once
+        let roughness_tex_id = match (metallic_tex, roughness_tex) {
+            (Some(metallic), Some(roughness)) if Rc::ptr_eq(metallic, roughness) => metallic_tex_id,
+            _ => roughness_tex
+                .and_then(|tex| Self::load_texture(world, tex.clone(), TextureFormat::Bgra8Unorm)),
+        };
+
+        // assimp imports glTF occlusion maps as light maps
+        let occlusion_tex = textures
+            .get(&TextureType::AmbientOcclusion)
+            .or_else(|| textures.get(&TextureType::LightMap));
+        let occlusion_tex_id = occlusion_tex
+            .and_then(|tex| Self::load_texture(world, tex.clone(), TextureFormat::Bgra8Unorm));
+
+        // glTF defaults to fully metallic and rough, the textures usually scale that down
+        PbrMaterial {
+            metallic: Self::extract_float_property(properties, "$mat.metallicFactor", 1.0),
+            roughness: Self::extract_float_property(properties, "$mat.roughnessFactor", 1.0),
+            metallic_texture: metallic_tex_id,
+            roughness_texture: roughness_tex_id,
+            occlusion_texture: occlusion_tex_id,
+        }
+    }
 }
@@ -22,19 +22,20 @@ struct CameraData {
 
 struct ModelData {
     model_mat: mat4x4<f32>,
+    receive_shadows: u32,
 }
 
 @group(0) @binding(0)
 var<uniform> camera: CameraData;
 
 @group(1) @binding(0)
-var<uniform> model: ModelData;
+var<storage, read> models: array<ModelData>;
 
 @vertex
-fn vs_main(in: VInput) -> VOutput {
+fn vs_main(in: VInput, @builtin(instance_index) instance: u32) -> VOutput {
     var out: VOutput;
 
-    let mvp_matrix = camera.view_proj_mat * model.model_mat;
+    let mvp_matrix = camera.view_proj_mat * models[instance].model_mat;
 
     out.position = mvp_matrix * vec4<f32>(in.vpos, 1.0);
     out.local_position = in.vpos;
@@ -0,0 +1,46 @@
+// Every pass reads the image at postTexture, with effect.texel_size being the size of its texels.
+// params.x: threshold, params.y: knee
+// The shader manager adds a fs_main to every pass, calling the pass' function.
+
+// Averages a 4x4 texel block with four bilinear samples
+fn box_filter(uv: vec2f) -> vec3f {
+    let offset = effect.texel_size;
+    var color = textureSample(postTexture, postSampler, uv + vec2f(-offset.x, -offset.y)).rgb;
+    color += textureSample(postTexture, postSampler, uv + vec2f( offset.x, -offset.y)).rgb;
+    color += textureSample(postTexture, postSampler, uv + vec2f(-offset.x,  offset.y)).rgb;
+    color += textureSample(postTexture, postSampler, uv + vec2f( offset.x,  offset.y)).rgb;
+    return color * 0.25;
+}
+
+// Keeps only the parts brighter than the threshold, with a soft transition around it
+fn prefilter(uv: vec2f) -> vec4f {
+    let color = box_filter(uv);
+    let threshold = effect.params.x;
+    let knee = max(effect.params.y, 0.0001);
+
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 0.0001);
+
+    return vec4f(color * contribution, 1.0);
+}
+
+fn downsample(uv: vec2f) -> vec4f {
+    return vec4f(box_filter(uv), 1.0);
+}
+
+// 3x3 tent filter, added on top of the bigger mip level by the additive pipeline
+fn upsample(uv: vec2f) -> vec4f {
+    let o = effect.texel_size;
+    var color = textureSample(postTexture, postSampler, uv).rgb * 4.0;
+    color += textureSample(postTexture, postSampler, uv + vec2f(-o.x, 0.0)).rgb * 2.0;
+    color += textureSample(postTexture, postSampler, uv + vec2f( o.x, 0.0)).rgb * 2.0;
+    color += textureSample(postTexture, postSampler, uv + vec2f(0.0, -o.y)).rgb * 2.0;
+    color += textureSample(postTexture, postSampler, uv + vec2f(0.0,  o.y)).rgb * 2.0;
+    color += textureSample(postTexture, postSampler, uv + vec2f(-o.x, -o.y)).rgb;
+    color += textureSample(postTexture, postSampler, uv + vec2f( o.x, -o.y)).rgb;
+    color += textureSample(postTexture, postSampler, uv + vec2f(-o.x,  o.y)).rgb;
+    color += textureSample(postTexture, postSampler, uv + vec2f( o.x,  o.y)).rgb;
+    return vec4f(color / 16.0, 1.0);
+}
@@ -0,0 +1,37 @@
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3f(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3f(2.4));
+    return select(high, low, color <= vec3f(0.04045));
+}
+
+// params.x: intensity
+// The lookup table is a strip of N slices of N x N texels each, with red growing to the right,
+// green growing downwards and blue growing from slice to slice. It maps sRGB colors to sRGB colors.
+@fragment
+fn fs_main(@location(0) uv: vec2f) -> @location(0) vec4f {
+    let color = textureSample(postTexture, postSampler, uv);
+    let size = f32(textureDimensions(effectTexture).y);
+
+    let srgb = linear_to_srgb(clamp(color.rgb, vec3f(0.0), vec3f(1.0)));
+    let texel = 1.0 / vec2f(size * size, size);
+
+    // blue picks the two closest slices, red and green are filtered within each of them
+    let blue = srgb.b * (size - 1.0);
+    let slice_low = floor(blue);
+    let slice_high = min(slice_low + 1.0, size - 1.0);
+    let in_slice = (srgb.rg * (size - 1.0) + 0.5) * texel;
+
+    let uv_low = vec2f(in_slice.x + slice_low * size * texel.x, in_slice.y);
+    let uv_high = vec2f(in_slice.x + slice_high * size * texel.x, in_slice.y);
+    let graded_low = textureSampleLevel(effectTexture, postSampler, uv_low, 0.0).rgb;
+    let graded_high = textureSampleLevel(effectTexture, postSampler, uv_high, 0.0).rgb;
+    let graded = srgb_to_linear(mix(graded_low, graded_high, blue - slice_low));
+
+    return vec4f(mix(color.rgb, graded, clamp(effect.params.x, 0.0, 1.0)), color.a);
+}
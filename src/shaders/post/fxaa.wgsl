@@ -0,0 +1,50 @@
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+// Perceived brightness. The image is linear, so it's brought closer to gamma space first.
+fn luma(color: vec3f) -> f32 {
+    return sqrt(dot(color, vec3f(0.299, 0.587, 0.114)));
+}
+
+fn sample_at(uv: vec2f) -> vec3f {
+    return textureSampleLevel(postTexture, postSampler, uv, 0.0).rgb;
+}
+
+// Blurs along the edge direction found from the luma of the four diagonal neighbours
+@fragment
+fn fs_main(@location(0) uv: vec2f) -> @location(0) vec4f {
+    let texel = effect.texel_size;
+    let center = textureSampleLevel(postTexture, postSampler, uv, 0.0);
+
+    let luma_nw = luma(sample_at(uv + vec2f(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_at(uv + vec2f( 1.0, -1.0) * texel));
+    let luma_sw = luma(sample_at(uv + vec2f(-1.0,  1.0) * texel));
+    let luma_se = luma(sample_at(uv + vec2f( 1.0,  1.0) * texel));
+    let luma_m = luma(center.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2f(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2f(-FXAA_SPAN_MAX), vec2f(FXAA_SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (
+        sample_at(uv + dir * (1.0 / 3.0 - 0.5)) +
+        sample_at(uv + dir * (2.0 / 3.0 - 0.5))
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        sample_at(uv + dir * -0.5) +
+        sample_at(uv + dir * 0.5)
+    );
+
+    // the wider blur overshoots if it reaches past the local contrast
+    let luma_b = luma(rgb_b);
+    let outside = luma_b < luma_min || luma_b > luma_max;
+    return vec4f(select(rgb_b, rgb_a, outside), center.a);
+}
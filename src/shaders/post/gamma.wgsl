@@ -0,0 +1,7 @@
+// params.x: gamma
+@fragment
+fn fs_main(@location(0) uv: vec2f) -> @location(0) vec4f {
+    let color = textureSample(postTexture, postSampler, uv);
+    let gamma = max(effect.params.x, 0.0001);
+    return vec4f(pow(max(color.rgb, vec3f(0.0)), vec3f(1.0 / gamma)), color.a);
+}
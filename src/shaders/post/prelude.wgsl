@@ -0,0 +1,47 @@
+struct VertexOutput {
+    @builtin(position) pos: vec4f,
+    @location(0) uv: vec2f,
+};
+
+// Has to match PostEffectUniform in renderer/post_process.rs
+struct PostEffectData {
+    params: vec4f,
+    texel_size: vec2f,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let positions = array<vec2f,6>(
+        vec2f(-1.0, -1.0),
+        vec2f( 1.0, -1.0),
+        vec2f(-1.0,  1.0),
+        vec2f(-1.0,  1.0),
+        vec2f( 1.0, -1.0),
+        vec2f( 1.0,  1.0),
+    );
+    let uvs = array<vec2f,6>(
+        vec2f(0.0, 1.0),
+        vec2f(1.0, 1.0),
+        vec2f(0.0, 0.0),
+        vec2f(0.0, 0.0),
+        vec2f(1.0, 1.0),
+        vec2f(1.0, 0.0),
+    );
+
+    var output: VertexOutput;
+    output.pos = vec4f(positions[vertex_index], 0.0, 1.0);
+    output.uv = uvs[vertex_index];
+    return output;
+}
+
+// The image of the previous effect
+@group(0) @binding(0)
+var postTexture: texture_2d<f32>;
+@group(0) @binding(1)
+var postSampler: sampler;
+
+@group(1) @binding(0)
+var<uniform> effect: PostEffectData;
+// White, unless the effect brings its own texture
+@group(1) @binding(1)
+var effectTexture: texture_2d<f32>;
@@ -0,0 +1,37 @@
+const TONE_MAPPING_NONE: u32 = 0u;
+const TONE_MAPPING_REINHARD: u32 = 1u;
+const TONE_MAPPING_ACES: u32 = 2u;
+
+// ACES filmic curve, as fitted by Krzysztof Narkowicz
+fn aces(x: vec3f) -> vec3f {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3f(0.0), vec3f(1.0));
+}
+
+// params.x: exposure, params.y: operator, params.z: bloom intensity
+// The bloom is read from effectTexture, which is white when there's no bloom, so z is 0 then.
+@fragment
+fn fs_main(@location(0) uv: vec2f) -> @location(0) vec4f {
+    let color = textureSample(postTexture, postSampler, uv);
+    let bloom = textureSample(effectTexture, postSampler, uv).rgb;
+    let hdr = (color.rgb + bloom * effect.params.z) * effect.params.x;
+
+    var mapped: vec3f;
+    switch u32(effect.params.y) {
+        case TONE_MAPPING_REINHARD: {
+            mapped = hdr / (hdr + vec3f(1.0));
+        }
+        case TONE_MAPPING_ACES: {
+            mapped = aces(hdr);
+        }
+        default: {
+            mapped = clamp(hdr, vec3f(0.0), vec3f(1.0));
+        }
+    }
+
+    return vec4f(mapped, color.a);
+}
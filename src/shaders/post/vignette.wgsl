@@ -0,0 +1,12 @@
+// params.x: intensity, params.y: smoothness
+@fragment
+fn fs_main(@location(0) uv: vec2f) -> @location(0) vec4f {
+    let color = textureSample(postTexture, postSampler, uv);
+
+    // 0 in the center of the screen, 1 in the corners
+    let distance = length(uv - vec2f(0.5)) * 1.41421356;
+    let smoothness = clamp(effect.params.y, 0.0001, 1.0);
+    let shade = 1.0 - effect.params.x * smoothstep(1.0 - smoothness, 1.0, distance);
+
+    return vec4f(color.rgb * max(shade, 0.0), color.a);
+}
@@ -13,6 +13,8 @@ struct VOutput {
     @location(2) vnorm: vec3<f32>,
     @location(3) tangent: vec3<f32>,
     @location(4) bitangent: vec3<f32>,
+    @location(5) @interpolate(flat) receive_shadows: u32,
+    @location(6) @interpolate(flat) lod_fade: f32,
 }
 
 struct CameraData {
@@ -26,22 +28,78 @@ struct CameraData {
 
 struct ModelData {
     model_mat: mat4x4<f32>,
+    receive_shadows: u32,
+    lod_fade: f32,
+}
+
+const MAX_LIGHTS: u32 = 16u;
+
+const LIGHT_TYPE_DIRECTIONAL: u32 = 0u;
+const LIGHT_TYPE_POINT: u32 = 1u;
+const LIGHT_TYPE_SPOT: u32 = 2u;
+
+struct Light {
+    position: vec3<f32>,
+    light_type: u32,
+    direction: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    inner_cos: f32,
+    outer_cos: f32,
+    shadow_index: i32,
+    shadow_view_proj: mat4x4<f32>,
+}
+
+struct Lights {
+    ambient: vec3<f32>,
+    count: u32,
+    lights: array<Light, MAX_LIGHTS>,
+    shadow_count: u32,
+}
+
+const SKY_MODE_GRADIENT: u32 = 1u;
+const SKY_MODE_CUBEMAP: u32 = 2u;
+
+// Has to match SkyUniform in renderer/sky.rs
+struct Sky {
+    zenith: vec3<f32>,
+    mode: u32,
+    horizon: vec3<f32>,
+    intensity: f32,
+    ground: vec3<f32>,
+    lighting: u32,
+    max_lod: f32,
 }
 
 struct Material {
     diffuse: vec3<f32>,
-    _padding1: u32,
+    use_shininess_texture: u32,
     use_diffuse_texture: u32,
     use_normal_texture: u32,
     shininess: f32,
     opacity: f32,
+    emissive: vec3<f32>,
+    use_emissive_texture: u32,
+    use_pbr: u32,
+    metallic: f32,
+    roughness: f32,
+    use_metallic_texture: u32,
+    use_roughness_texture: u32,
+    use_occlusion_texture: u32,
+    // zero unless the material is alpha tested
+    alpha_cutoff: f32,
 }
 
 @group(0) @binding(0)
 var<uniform> camera: CameraData;
 
+// the models of all drawables, indexed by the instance index
 @group(1) @binding(0)
-var<uniform> model: ModelData;
+var<storage, read> models: array<ModelData>;
+
+// set by fs_main for the fragment being shaded
+var<private> receive_shadows: u32 = 1u;
 
 @group(2) @binding(0)
 var<uniform> material: Material;
@@ -58,9 +116,321 @@ var t_normal: texture_2d<f32>;
 @group(2) @binding(4)
 var s_normal: sampler;
 
+@group(2) @binding(5)
+var t_shininess: texture_2d<f32>;
+
+@group(2) @binding(6)
+var s_shininess: sampler;
+
+@group(2) @binding(7)
+var t_metallic: texture_2d<f32>;
+
+@group(2) @binding(8)
+var s_metallic: sampler;
+
+@group(2) @binding(9)
+var t_roughness: texture_2d<f32>;
+
+@group(2) @binding(10)
+var s_roughness: sampler;
+
+@group(2) @binding(11)
+var t_occlusion: texture_2d<f32>;
+
+@group(2) @binding(12)
+var s_occlusion: sampler;
+
+@group(2) @binding(13)
+var t_emissive: texture_2d<f32>;
+
+@group(2) @binding(14)
+var s_emissive: sampler;
+
+@group(3) @binding(0)
+var<uniform> lights: Lights;
+
+@group(3) @binding(1)
+var shadow_maps: texture_depth_2d_array;
+
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+@group(3) @binding(3)
+var<uniform> sky: Sky;
+
+@group(3) @binding(4)
+var sky_texture: texture_cube<f32>;
+
+@group(3) @binding(5)
+var sky_sampler: sampler;
+
+fn sky_gradient(dir: vec3<f32>) -> vec3<f32> {
+    if dir.y >= 0.0 {
+        return mix(sky.horizon, sky.zenith, sqrt(dir.y));
+    }
+    return mix(sky.horizon, sky.ground, sqrt(-dir.y));
+}
+
+// The sky's light coming from a direction. Blurriness goes from 0 (sharp) to 1 (the average of
+// a whole side of the sky).
+fn sky_radiance(dir: vec3<f32>, blurriness: f32) -> vec3<f32> {
+    var color: vec3<f32>;
+    if sky.mode == SKY_MODE_CUBEMAP {
+        color = textureSampleLevel(sky_texture, sky_sampler, dir, blurriness * sky.max_lod).rgb;
+    } else {
+        color = sky_gradient(dir);
+    }
+    return color * sky.intensity;
+}
+
+fn uses_sky_lighting() -> bool {
+    return sky.lighting != 0u;
+}
+
+// The ambient light reaching a surface facing the normal
+fn ambient_light(normal: vec3<f32>) -> vec3<f32> {
+    if uses_sky_lighting() {
+        return sky_radiance(normal, 1.0);
+    }
+    return lights.ambient;
+}
+
+// Has to match SHADOW_MAP_SIZE in renderer/shadows.rs
+const SHADOW_MAP_SIZE: f32 = 2048.0;
+
+// How much of the light reaches the fragment, filtered over 3x3 shadow map texels
+fn shadow_factor(light: Light, frag_pos: vec3<f32>, n_dot_l: f32) -> f32 {
+    if light.shadow_index < 0 || receive_shadows == 0u {
+        return 1.0;
+    }
+
+    let light_pos = light.shadow_view_proj * vec4<f32>(frag_pos, 1.0);
+    if light_pos.w <= 0.0 {
+        return 1.0;
+    }
+
+    let ndc = light_pos.xyz / light_pos.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    // surfaces facing away from the light need a bigger bias against acne
+    let bias = max(0.002 * (1.0 - n_dot_l), 0.0005);
+    let texel = 1.0 / SHADOW_MAP_SIZE;
+
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_maps, shadow_sampler, uv + offset, light.shadow_index, ndc.z - bias);
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// Smoothly fades point and spot lights out towards their range
+fn range_attenuation(distance: f32, range: f32) -> f32 {
+    if range <= 0.0 {
+        return 1.0 / (distance * distance + 1.0);
+    }
+    let ratio = distance / range;
+    let falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return falloff * falloff / (distance * distance + 1.0);
+}
+
+const PI: f32 = 3.14159265;
+
+// Direction towards the light in xyz and how much of it reaches the fragment in w,
+// including spot cones and shadows
+fn light_incidence(light: Light, frag_pos: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+    var light_dir: vec3<f32>;
+    var attenuation = 1.0;
+    if light.light_type == LIGHT_TYPE_DIRECTIONAL {
+        light_dir = -light.direction;
+    } else {
+        let to_light = light.position - frag_pos;
+        let distance = length(to_light);
+        light_dir = to_light / max(distance, 0.0001);
+        attenuation = range_attenuation(distance, light.range);
+
+        if light.light_type == LIGHT_TYPE_SPOT {
+            let theta = dot(-light_dir, light.direction);
+            attenuation *= smoothstep(light.outer_cos, light.inner_cos, theta);
+        }
+    }
+
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    if n_dot_l <= 0.0 || attenuation <= 0.0 {
+        return vec4<f32>(light_dir, 0.0);
+    }
+
+    attenuation *= shadow_factor(light, frag_pos, n_dot_l);
+    return vec4<f32>(light_dir, attenuation);
+}
+
+// Blinn-Phong lighting of all lights. Returns the diffuse and specular light reaching the surface.
+fn calculate_lighting(frag_pos: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>, specular_strength: f32) -> array<vec3<f32>, 2> {
+    var diffuse = ambient_light(normal);
+    var specular = vec3<f32>(0.0);
+
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        let light = lights.lights[i];
+
+        let incidence = light_incidence(light, frag_pos, normal);
+        if incidence.w <= 0.0 {
+            continue;
+        }
+        let light_dir = incidence.xyz;
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+
+        let radiance = light.color * light.intensity * incidence.w;
+        diffuse += radiance * n_dot_l;
+
+        if material.shininess > 0.0 {
+            let half_dir = normalize(light_dir + view_dir);
+            let n_dot_h = max(dot(normal, half_dir), 0.0);
+            specular += radiance * pow(n_dot_h, material.shininess) * specular_strength;
+        }
+    }
+
+    return array<vec3<f32>, 2>(diffuse, specular);
+}
+
+// GGX / Trowbridge-Reitz normal distribution
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / max(PI * denom * denom, 0.0000001);
+}
+
+// Smith's method with the Schlick-GGX approximation for both view and light direction
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    let ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx_v * ggx_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Rough surfaces reflect less at grazing angles
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Ambient light of a PBR surface. With sky lighting, the sky is reflected, more blurry the rougher
+// the surface is.
+fn pbr_ambient(normal: vec3<f32>, view_dir: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    if !uses_sky_lighting() {
+        return lights.ambient * albedo;
+    }
+
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let f = fresnel_schlick_roughness(max(dot(normal, view_dir), 0.0), f0, roughness);
+    let k_d = (1.0 - f) * (1.0 - metallic);
+
+    let diffuse = sky_radiance(normal, 1.0) * albedo;
+    let specular = sky_radiance(reflect(-view_dir, normal), roughness) * f;
+    return k_d * diffuse + specular;
+}
+
+// Cook-Torrance lighting of all lights, without the ambient part.
+// Light intensities are scaled by PI, so a light lights a surface as bright as with Blinn-Phong.
+fn calculate_pbr_lighting(frag_pos: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    // dielectrics reflect about 4% of the light head-on, metals tint the reflection with their color
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        let light = lights.lights[i];
+
+        let incidence = light_incidence(light, frag_pos, normal);
+        if incidence.w <= 0.0 {
+            continue;
+        }
+        let light_dir = incidence.xyz;
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+
+        let half_dir = normalize(light_dir + view_dir);
+        let n_dot_h = max(dot(normal, half_dir), 0.0);
+        let h_dot_v = max(dot(half_dir, view_dir), 0.0);
+
+        let d = distribution_ggx(n_dot_h, roughness);
+        let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+        let f = fresnel_schlick(h_dot_v, f0);
+
+        let specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+        // metals have no diffuse part, everything that isn't reflected gets absorbed
+        let k_d = (1.0 - f) * (1.0 - metallic);
+
+        let radiance = light.color * light.intensity * incidence.w;
+        color += (k_d * albedo + specular * PI) * radiance * n_dot_l;
+    }
+
+    return color;
+}
+
+// From the fragment towards the camera. Orthographic cameras look along the same direction
+// everywhere, their projection keeps w at 1.
+fn view_direction(frag_pos: vec3<f32>) -> vec3<f32> {
+    if camera.projection_mat[3][3] == 1.0 {
+        return normalize(vec3<f32>(camera.view_mat[0].z, camera.view_mat[1].z, camera.view_mat[2].z));
+    }
+    return normalize(camera.pos - frag_pos);
+}
+
+// Whether a LOD cross-fade leaves this pixel to the other level. Both levels use the same 4x4
+// ordered dither, the one fading in keeps the pixels below the fade and the other the rest.
+fn lod_faded_out(frag_coord: vec2<f32>, fade: f32) -> bool {
+    if fade == 0.0 {
+        return false;
+    }
+    var bayer = array<f32, 16>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    let cell = vec2<u32>(frag_coord) % 4u;
+    let threshold = (bayer[cell.y * 4u + cell.x] + 0.5) / 16.0;
+    if fade > 0.0 {
+        return threshold > fade;
+    }
+    return threshold <= -fade;
+}
+
+// Moves the normal of the normal texture from tangent space into world space
+fn surface_normal(in: VOutput) -> vec3<f32> {
+    let normal = normalize(in.vnorm);
+    if material.use_normal_texture == 0u {
+        return normal;
+    }
+
+    // sampled before any non-uniform branch, as implicit derivatives need uniform control flow
+    let tangent_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+
+    // meshes without uv coordinates don't have a tangent frame
+    if dot(in.tangent, in.tangent) < 0.000001 || dot(in.bitangent, in.bitangent) < 0.000001 {
+        return normal;
+    }
+
+    // re-orthogonalize, as the interpolated frame isn't orthogonal anymore
+    let tangent = normalize(in.tangent - normal * dot(normal, in.tangent));
+    var bitangent = cross(normal, tangent);
+    if dot(bitangent, in.bitangent) < 0.0 {
+        bitangent = -bitangent;
+    }
+
+    let tbn = mat3x3<f32>(tangent, bitangent, normal);
+    return normalize(tbn * tangent_normal);
+}
+
 @vertex
-fn vs_main(in: VInput) -> VOutput {
+fn vs_main(in: VInput, @builtin(instance_index) instance: u32) -> VOutput {
     var out: VOutput;
+    let model = models[instance];
 
     let model_view_mat = camera.view_proj_mat * model.model_mat;
 
@@ -68,14 +438,18 @@ fn vs_main(in: VInput) -> VOutput {
     out.tex_coords = vec2<f32>(in.vtex.x, 1.0 - in.vtex.y);
     out.frag_pos = (model.model_mat * vec4<f32>(in.vpos, 1.0)).xyz;
     out.vnorm = normalize((model.model_mat * vec4<f32>(in.vnorm, 0.0)).xyz);
-    out.tangent = normalize((model.model_mat * vec4<f32>(in.vtan, 0.0)).xyz);
-    out.bitangent = normalize((model.model_mat * vec4<f32>(in.vbitan, 0.0)).xyz);
+    // not normalized here, as meshes without a tangent frame have zeroed tangents
+    out.tangent = (model.model_mat * vec4<f32>(in.vtan, 0.0)).xyz;
+    out.bitangent = (model.model_mat * vec4<f32>(in.vbitan, 0.0)).xyz;
+    out.receive_shadows = model.receive_shadows;
+    out.lod_fade = model.lod_fade;
 
     return out;
 }
 
 @fragment
 fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    receive_shadows = in.receive_shadows;
     var diffuse: vec4<f32>;
 
     // diffuse = vec4<f32>(in.vnorm, 1.0);
@@ -85,10 +459,52 @@ fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
     } else {
         diffuse = vec4<f32>(material.diffuse, 1.0);
     }
+    diffuse.w *= material.opacity;
 
-    if diffuse.w < 0.8 {
+    if diffuse.w < material.alpha_cutoff || lod_faded_out(in.position.xy, in.lod_fade) {
         discard;
     }
 
-    return diffuse;
+    let normal = surface_normal(in);
+    let view_dir = view_direction(in.frag_pos);
+
+    var emissive = material.emissive;
+    if material.use_emissive_texture != 0u {
+        emissive *= textureSample(t_emissive, s_emissive, in.tex_coords).rgb;
+    }
+
+    var color: vec3<f32>;
+    if material.use_pbr != 0u {
+        var metallic = material.metallic;
+        if material.use_metallic_texture != 0u {
+            metallic *= textureSample(t_metallic, s_metallic, in.tex_coords).b;
+        }
+
+        var roughness = material.roughness;
+        if material.use_roughness_texture != 0u {
+            roughness *= textureSample(t_roughness, s_roughness, in.tex_coords).g;
+        }
+        // perfectly smooth surfaces would make the highlights vanish
+        roughness = clamp(roughness, 0.04, 1.0);
+
+        var occlusion = 1.0;
+        if material.use_occlusion_texture != 0u {
+            occlusion = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
+        }
+
+        let ambient = pbr_ambient(normal, view_dir, diffuse.rgb, metallic, roughness) * occlusion;
+        color = ambient + calculate_pbr_lighting(in.frag_pos, normal, view_dir, diffuse.rgb, metallic, roughness);
+    } else {
+        var specular_strength = 1.0;
+        if material.use_shininess_texture != 0u {
+            specular_strength = textureSample(t_shininess, s_shininess, in.tex_coords).r;
+        }
+
+        let lighting = calculate_lighting(in.frag_pos, normal, view_dir, specular_strength);
+        color = diffuse.rgb * lighting[0] + lighting[1];
+    }
+
+    color += emissive;
+
+    return vec4<f32>(color, diffuse.w);
 }
@@ -0,0 +1,80 @@
+struct VInput {
+    @location(0) vpos: vec3<f32>,
+    @location(1) vtex: vec2<f32>,
+    @location(2) vnorm: vec3<f32>,
+    @location(3) vtan: vec3<f32>,
+    @location(4) vbitan: vec3<f32>,
+}
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct LightView {
+    view_proj_mat: mat4x4<f32>,
+}
+
+struct ModelData {
+    model_mat: mat4x4<f32>,
+    receive_shadows: u32,
+}
+
+// Has to match the Material of shader3d.wgsl, only the alpha is of interest here
+struct Material {
+    diffuse: vec3<f32>,
+    use_shininess_texture: u32,
+    use_diffuse_texture: u32,
+    use_normal_texture: u32,
+    shininess: f32,
+    opacity: f32,
+    emissive: vec3<f32>,
+    use_emissive_texture: u32,
+    use_pbr: u32,
+    metallic: f32,
+    roughness: f32,
+    use_metallic_texture: u32,
+    use_roughness_texture: u32,
+    use_occlusion_texture: u32,
+    // zero unless the material is alpha tested
+    alpha_cutoff: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> light_view: LightView;
+
+@group(1) @binding(0)
+var<storage, read> models: array<ModelData>;
+
+@group(2) @binding(0)
+var<uniform> material: Material;
+
+@group(2) @binding(1)
+var t_diffuse: texture_2d<f32>;
+
+@group(2) @binding(2)
+var s_diffuse: sampler;
+
+@vertex
+fn vs_main(in: VInput, @builtin(instance_index) instance: u32) -> VOutput {
+    var out: VOutput;
+    out.position = light_view.view_proj_mat * models[instance].model_mat * vec4<f32>(in.vpos, 1.0);
+    out.tex_coords = in.vtex;
+    return out;
+}
+
+// Depth only, alpha tested materials cast no shadow where they are cut out
+@fragment
+fn fs_main(in: VOutput) {
+    if material.alpha_cutoff <= 0.0 {
+        return;
+    }
+
+    var alpha = material.opacity;
+    if material.use_diffuse_texture != 0 {
+        alpha *= textureSample(t_diffuse, s_diffuse, in.tex_coords).a;
+    }
+    if alpha < material.alpha_cutoff {
+        discard;
+    }
+}
@@ -0,0 +1,79 @@
+struct CameraData {
+    pos: vec3<f32>,
+    rot: vec3<f32>,
+    scale: vec3<f32>,
+    view_mat: mat4x4<f32>,
+    projection_mat: mat4x4<f32>,
+    view_proj_mat: mat4x4<f32>,
+}
+
+const SKY_MODE_GRADIENT: u32 = 1u;
+const SKY_MODE_CUBEMAP: u32 = 2u;
+
+// Has to match SkyUniform in renderer/sky.rs
+struct Sky {
+    zenith: vec3<f32>,
+    mode: u32,
+    horizon: vec3<f32>,
+    intensity: f32,
+    ground: vec3<f32>,
+    lighting: u32,
+    max_lod: f32,
+}
+
+struct VOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraData;
+
+// the sky bindings of the light bind group
+@group(1) @binding(3)
+var<uniform> sky: Sky;
+
+@group(1) @binding(4)
+var sky_texture: texture_cube<f32>;
+
+@group(1) @binding(5)
+var sky_sampler: sampler;
+
+fn sky_gradient(dir: vec3<f32>) -> vec3<f32> {
+    if dir.y >= 0.0 {
+        return mix(sky.horizon, sky.zenith, sqrt(dir.y));
+    }
+    return mix(sky.horizon, sky.ground, sqrt(-dir.y));
+}
+
+// A single triangle covering the screen, on the far plane so everything else is in front of it
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VOutput {
+    let ndc = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u)) * 2.0 - 1.0;
+
+    var out: VOutput;
+    out.position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VOutput) -> @location(0) vec4<f32> {
+    // the direction through the pixel in view space, rotated back into world space.
+    // Orthographic cameras look straight ahead through every pixel.
+    var view_dir = vec3<f32>(in.ndc.x / camera.projection_mat[0][0], in.ndc.y / camera.projection_mat[1][1], -1.0);
+    if camera.projection_mat[3][3] == 1.0 {
+        view_dir = vec3<f32>(0.0, 0.0, -1.0);
+    }
+    let view_rot = mat3x3<f32>(camera.view_mat[0].xyz, camera.view_mat[1].xyz, camera.view_mat[2].xyz);
+    let dir = normalize(transpose(view_rot) * view_dir);
+
+    var color: vec3<f32>;
+    if sky.mode == SKY_MODE_CUBEMAP {
+        color = textureSampleLevel(sky_texture, sky_sampler, dir, 0.0).rgb;
+    } else {
+        color = sky_gradient(dir);
+    }
+
+    return vec4<f32>(color * sky.intensity, 1.0);
+}
@@ -1,9 +1,12 @@
+use std::error::Error;
 use std::rc::Rc;
+use log::warn;
 use wgpu::{
-    Adapter, CompositeAlphaMode, Device, DeviceDescriptor, Extent3d, Features, Instance,
-    PowerPreference, PresentMode, Queue, RequestAdapterOptions, Surface, SurfaceConfiguration,
-    Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    Adapter, CompositeAlphaMode, Device, DeviceDescriptor, Features, Instance, PowerPreference,
+    PresentMode, Queue, RequestAdapterOptions, Surface, SurfaceConfiguration, TextureFormat,
+    TextureUsages,
 };
+use crate::renderer::HDR_FORMAT;
 use winit::dpi::PhysicalSize;
 use winit::event::WindowEvent;
 use winit::window::Window;
@@ -11,12 +14,14 @@ use winit::window::Window;
 #[allow(unused)]
 pub struct State {
     pub(crate) instance: Instance,
-    pub(crate) surface: Surface<'static>,
+    pub(crate) surface: Option<Surface<'static>>,
     pub(crate) device: Rc<Device>,
     pub(crate) queue: Rc<Queue>,
     pub(crate) config: SurfaceConfiguration,
     pub(crate) size: PhysicalSize<u32>,
-    pub(crate) depth_texture: Texture,
+    // MSAA samples of the scene pass, 1 without multisampling
+    pub(crate) sample_count: u32,
+    pub(crate) supported_sample_counts: Vec<u32>,
 }
 
 impl State {
@@ -48,12 +53,35 @@ impl State {
             )
     }
 
+    async fn setup_headless_adapter(instance: &Instance) -> Option<Adapter> {
+        let adapter = instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::HighPerformance,
+                compatible_surface: None,
+                ..Default::default()
+            })
+            .await;
+        if adapter.is_some() {
+            return adapter;
+        }
+
+        warn!("No hardware adapter found. Falling back to a software adapter.");
+        instance
+            .request_adapter(&RequestAdapterOptions {
+                power_preference: PowerPreference::LowPower,
+                force_fallback_adapter: true,
+                compatible_surface: None,
+            })
+            .await
+    }
+
     async fn get_device_and_queue(adapter: &Adapter) -> (Rc<Device>, Rc<Queue>) {
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: Some("Renderer Hardware"),
-                    required_features: Features::default(),
+                    // lets MSAA use every sample count the adapter supports, not just 1 and 4
+                    required_features: adapter.features() & Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                     required_limits: Default::default(),
                     memory_hints: Default::default(),
                 },
@@ -94,21 +122,24 @@ impl State {
         config
     }
 
-    fn setup_depth_texture(size: &PhysicalSize<u32>, device: &Device) -> Texture {
-        device.create_texture(&TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: Extent3d {
-                width: size.width,
-                height: size.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[TextureFormat::Depth32Float],
-        })
+    // The sample counts both the HDR color target and the depth texture can be created with
+    fn find_supported_sample_counts(adapter: &Adapter, device: &Device) -> Vec<u32> {
+        let format_features = |format: TextureFormat| {
+            if device.features().contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                adapter.get_texture_format_features(format)
+            } else {
+                format.guaranteed_format_features(device.features())
+            }
+        };
+        let color = format_features(HDR_FORMAT);
+        let depth = format_features(TextureFormat::Depth32Float);
+
+        [1, 2, 4, 8]
+            .into_iter()
+            .filter(|&count| {
+                color.flags.sample_count_supported(count) && depth.flags.sample_count_supported(count)
+            })
+            .collect()
     }
 
     pub async fn new(window: &Window) -> Self {
@@ -124,19 +155,59 @@ impl State {
         let (device, queue) = Self::get_device_and_queue(&adapter).await;
         let config = Self::configure_surface(&size, &surface, &adapter, &device);
 
-        let depth_texture = Self::setup_depth_texture(&size, &device);
+        let supported_sample_counts = Self::find_supported_sample_counts(&adapter, &device);
 
         State {
             instance,
-            surface,
+            surface: Some(surface),
             device,
             queue,
             config,
             size,
-            depth_texture,
+            sample_count: 1,
+            supported_sample_counts,
         }
     }
 
+    // Creates a state without any window or surface. Everything is rendered into offscreen textures.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+        let size = PhysicalSize {
+            width: width.max(1),
+            height: height.max(1),
+        };
+
+        let instance = Self::setup_instance();
+        let adapter = Self::setup_headless_adapter(&instance)
+            .await
+            .ok_or("Couldn't find any adapter for headless rendering")?;
+        let (device, queue) = Self::get_device_and_queue(&adapter).await;
+
+        // not used to configure anything, but keeps the size and format in one place like with a surface
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            format: TextureFormat::Bgra8UnormSrgb,
+            width: size.width,
+            height: size.height,
+            desired_maximum_frame_latency: 2,
+            present_mode: PresentMode::AutoNoVsync,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+
+        let supported_sample_counts = Self::find_supported_sample_counts(&adapter, &device);
+
+        Ok(State {
+            instance,
+            surface: None,
+            device,
+            queue,
+            config,
+            size,
+            sample_count: 1,
+            supported_sample_counts,
+        })
+    }
+
     pub fn resize(&mut self, mut new_size: PhysicalSize<u32>) {
         new_size.height = new_size.height.max(1);
         new_size.width = new_size.width.max(1);
@@ -147,8 +218,13 @@ impl State {
     }
 
     pub fn recreate_surface(&mut self) {
-        self.surface.configure(&self.device, &self.config);
-        self.depth_texture = Self::setup_depth_texture(&self.size, &self.device);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    pub(crate) fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
     }
 
     pub fn update(&mut self) {
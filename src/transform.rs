@@ -1,3 +1,5 @@
+use std::cell::Cell;
+
 use nalgebra::{Affine3, Scale3, Translation3, UnitQuaternion, Vector3};
 
 use crate::object::GameObjectId;
@@ -13,6 +15,11 @@ pub struct Transform {
     invert_position: bool,
     owner: GameObjectId,
     compound_pos_first: bool,
+    // world space values, recalculated lazily after this or any parent transform changed
+    global_mat: Cell<Affine3<f32>>,
+    global_rot: Cell<UnitQuaternion<f32>>,
+    global_scale: Cell<Scale3<f32>>,
+    dirty: Cell<bool>,
 }
 
 #[allow(dead_code)]
@@ -28,6 +35,10 @@ impl Transform {
             invert_position: false,
             owner,
             compound_pos_first: true,
+            global_mat: Cell::new(Affine3::identity()),
+            global_rot: Cell::new(UnitQuaternion::identity()),
+            global_scale: Cell::new(Scale3::identity()),
+            dirty: Cell::new(true),
         }
     }
 
@@ -41,31 +52,55 @@ impl Transform {
         Vector3::new(mat.m14, mat.m24, mat.m34)
     }
 
-    fn get_parent_list(&self) -> Vec<GameObjectId> {
-        let mut parents = vec![];
-        let mut parent_opt = Some(self.owner);
-
-        while let Some(parent) = parent_opt {
-            parents.push(parent);
-            parent_opt = parent.parent;
+    // Marks the global values of this transform and all of its children as outdated.
+    // Needs to be called when the local values or the parent of the owner change.
+    pub fn mark_dirty(&self) {
+        // a transform is only up to date if all its parents are, so a dirty transform
+        // can't have up to date children
+        if self.dirty.replace(true) {
+            return;
         }
-        parents.reverse();
 
-        parents
+        let Some(owner) = self.owner.get() else {
+            return;
+        };
+        for child in owner.children.iter().filter_map(GameObjectId::get) {
+            child.transform.mark_dirty();
+        }
     }
 
-    pub fn get_global_transform_matrix_ext(&self, include_self: bool) -> Affine3<f32> {
-        let mut mat = Affine3::identity();
-        let mut parents = self.get_parent_list();
-
-        if !include_self {
-            parents.pop();
+    fn update_global(&self) {
+        if !self.dirty.get() {
+            return;
         }
 
-        for parent in parents {
-            mat *= parent.transform.compound_mat;
+        let (parent_mat, parent_rot, parent_scale) = match self.parent_transform() {
+            Some(parent) => (parent.global_mat.get(), parent.global_rot.get(), parent.global_scale.get()),
+            None => (Affine3::identity(), UnitQuaternion::identity(), Scale3::identity()),
+        };
+
+        self.global_mat.set(parent_mat * self.compound_mat);
+        self.global_rot.set(parent_rot * self.rot);
+        self.global_scale.set(parent_scale * self.scale_mat);
+        self.dirty.set(false);
+    }
+
+    // The up to date transform of the owner's parent
+    fn parent_transform(&self) -> Option<&Transform> {
+        let parent = self.owner.get()?.parent?;
+        let parent = unsafe { (*self.owner.world()).get_object(&parent)? };
+        parent.transform.update_global();
+        Some(&parent.transform)
+    }
+
+    pub fn get_global_transform_matrix_ext(&self, include_self: bool) -> Affine3<f32> {
+        if include_self {
+            self.update_global();
+            return self.global_mat.get();
         }
-        mat
+        self.parent_transform()
+            .map(|parent| parent.global_mat.get())
+            .unwrap_or_else(Affine3::identity)
     }
 
     pub fn get_global_transform_matrix(&self) -> Affine3<f32> {
@@ -73,31 +108,23 @@ impl Transform {
     }
 
     pub fn get_global_rotation_ext(&self, include_self: bool) -> UnitQuaternion<f32> {
-        let mut global_rotation = UnitQuaternion::identity();
-        let mut parents = self.get_parent_list();
-
-        if !include_self {
-            parents.pop();
-        }
-
-        for parent in parents {
-            global_rotation *= parent.transform.rot;
+        if include_self {
+            self.update_global();
+            return self.global_rot.get();
         }
-        global_rotation
+        self.parent_transform()
+            .map(|parent| parent.global_rot.get())
+            .unwrap_or_else(UnitQuaternion::identity)
     }
 
     pub fn get_global_scale_matrix_ext(&self, include_self: bool) -> Scale3<f32> {
-        let mut mat = Scale3::identity();
-        let mut parents = self.get_parent_list();
-
-        if !include_self {
-            parents.pop();
+        if include_self {
+            self.update_global();
+            return self.global_scale.get();
         }
-
-        for parent in parents {
-            mat *= parent.transform.scale_mat;
-        }
-        mat
+        self.parent_transform()
+            .map(|parent| parent.global_scale.get())
+            .unwrap_or_else(Scale3::identity)
     }
 
     pub fn get_global_scale_matrix(&self) -> Scale3<f32> {
@@ -115,6 +142,7 @@ impl Transform {
 
     pub fn set_invert_position(&mut self, invert: bool) {
         self.invert_position = invert;
+        self.recalculate_pos_matrix();
     }
 
     pub fn translate(&mut self, other: Vector3<f32>) {
@@ -200,6 +228,11 @@ impl Transform {
 
     pub fn set_compound_pos_first(&mut self, state: bool) {
         self.compound_pos_first = state;
+        self.recalculate_combined_matrix();
+    }
+
+    pub fn compound_pos_first(&self) -> bool {
+        self.compound_pos_first
     }
 
     fn recalculate_combined_matrix(&mut self) {
@@ -216,6 +249,7 @@ impl Transform {
                     * self.scale_mat.to_homogeneous(),
             );
         }
+        self.mark_dirty();
     }
 
     pub fn full_matrix(&self) -> &Affine3<f32> {
@@ -246,3 +280,74 @@ impl Transform {
         self.local_rotation() * Vector3::new(0.0, 1.0, 0.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{UnitQuaternion, Vector3};
+
+    use crate::world::World;
+
+    fn assert_near(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).norm() < 1e-5, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn child_follows_parent_after_it_moved() {
+        let mut world = World::new();
+        let mut parent = world.new_object("Parent");
+        let mut child = world.new_object("Child");
+        parent.add_child(child);
+        child.transform.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+
+        // computes and caches the global values once
+        assert_near(child.transform.position(), Vector3::new(1.0, 0.0, 0.0));
+
+        parent.transform.set_local_position(Vector3::new(0.0, 5.0, 0.0));
+        assert_near(child.transform.position(), Vector3::new(1.0, 5.0, 0.0));
+
+        parent
+            .transform
+            .set_local_rotation(UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2));
+        parent.transform.set_uniform_local_scale(2.0);
+        assert_near(child.transform.position(), Vector3::new(0.0, 7.0, 0.0));
+        assert_near(child.transform.scale(), Vector3::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn grandchild_follows_root_after_it_moved() {
+        let mut world = World::new();
+        let mut root = world.new_object("Root");
+        let mut middle = world.new_object("Middle");
+        let mut leaf = world.new_object("Leaf");
+        root.add_child(middle);
+        middle.add_child(leaf);
+        middle.transform.set_local_position(Vector3::new(0.0, 1.0, 0.0));
+        leaf.transform.set_local_position(Vector3::new(0.0, 0.0, 1.0));
+        assert_near(leaf.transform.position(), Vector3::new(0.0, 1.0, 1.0));
+
+        root.transform.set_local_position(Vector3::new(3.0, 0.0, 0.0));
+        assert_near(leaf.transform.position(), Vector3::new(3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn reparented_child_takes_on_new_parent() {
+        let mut world = World::new();
+        let mut first = world.new_object("First");
+        let mut second = world.new_object("Second");
+        let child = world.new_object("Child");
+        first.transform.set_local_position(Vector3::new(1.0, 0.0, 0.0));
+        second.transform.set_local_position(Vector3::new(0.0, 0.0, -4.0));
+        first.add_child(child);
+        assert_near(child.transform.position(), Vector3::new(1.0, 0.0, 0.0));
+
+        second.add_child(child);
+        assert!(first.children.is_empty());
+        assert_near(child.transform.position(), Vector3::new(0.0, 0.0, -4.0));
+
+        // the old parent doesn't move the child anymore
+        first.transform.set_local_position(Vector3::new(9.0, 9.0, 9.0));
+        assert_near(child.transform.position(), Vector3::new(0.0, 0.0, -4.0));
+        second.transform.set_local_position(Vector3::new(0.0, 2.0, 0.0));
+        assert_near(child.transform.position(), Vector3::new(0.0, 2.0, 0.0));
+    }
+}
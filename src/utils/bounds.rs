@@ -0,0 +1,226 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+// An axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    // The smallest box around the points, or an empty box at the origin if there are none
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Vector3<f32>>,
+    {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Aabb {
+                min: Vector3::zeros(),
+                max: Vector3::zeros(),
+            };
+        };
+        points.fold(Aabb { min: first, max: first }, |aabb, point| Aabb {
+            min: aabb.min.inf(&point),
+            max: aabb.max.sup(&point),
+        })
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    // Half the size of the box on every axis
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    // The box around this box after transforming it, which may be bigger than the transformed
+    // box itself when the transform rotates it
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        let center = matrix.transform_point(&Point3::from(self.center())).coords;
+        let linear = matrix.fixed_view::<3, 3>(0, 0).abs();
+        let extents = linear * self.extents();
+        Aabb {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    // A sphere around `center` that reaches the farthest of the points
+    pub fn around<I>(center: Vector3<f32>, points: I) -> Self
+    where
+        I: IntoIterator<Item = Vector3<f32>>,
+    {
+        let radius = points
+            .into_iter()
+            .map(|point| (point - center).norm())
+            .fold(0.0, f32::max);
+        BoundingSphere { center, radius }
+    }
+
+    // Scaling by different amounts per axis grows the sphere by the biggest of them
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        let center = matrix.transform_point(&Point3::from(self.center)).coords;
+        let scale = (0..3)
+            .map(|i| matrix.fixed_view::<3, 1>(0, i).norm())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center,
+            radius: self.radius * scale,
+        }
+    }
+}
+
+// A box and a sphere around the same thing. The sphere is the cheaper test, the box the
+// tighter one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Vector3<f32>>,
+        I::IntoIter: Clone,
+    {
+        let points = points.into_iter();
+        let aabb = Aabb::from_points(points.clone());
+        let sphere = BoundingSphere::around(aabb.center(), points);
+        Bounds { aabb, sphere }
+    }
+
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        Bounds {
+            aabb: self.aabb.transformed(matrix),
+            sphere: self.sphere.transformed(matrix),
+        }
+    }
+}
+
+// The six planes enclosing what a camera sees. Every plane is stored as (normal, distance)
+// with the normal pointing inwards.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // Extracts the planes from a projection * view matrix with depth from 0 to 1, like wgpu's
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.xyz().norm();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Frustum { planes }
+    }
+
+    fn distance(plane: &Vector4<f32>, point: &Vector3<f32>) -> f32 {
+        plane.xyz().dot(point) + plane.w
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Self::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let extents = aabb.extents();
+        self.planes.iter().all(|plane| {
+            // how far the box reaches towards the plane's normal
+            let reach = extents.dot(&plane.xyz().abs());
+            Self::distance(plane, &center) >= -reach
+        })
+    }
+
+    pub fn intersects(&self, bounds: &Bounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Vector3};
+
+    use super::{Aabb, Frustum};
+
+    fn cube(center: Vector3<f32>, half_size: f32) -> Aabb {
+        Aabb {
+            min: center - Vector3::repeat(half_size),
+            max: center + Vector3::repeat(half_size),
+        }
+    }
+
+    // With the identity matrix, the frustum is the box from (-1, -1, 0) to (1, 1, 1)
+    fn unit_frustum() -> Frustum {
+        Frustum::from_view_proj(&Matrix4::identity())
+    }
+
+    // A point on every plane of the unit frustum and the direction pointing out of it
+    const FACES: [([f32; 3], [f32; 3]); 6] = [
+        ([-1.0, 0.0, 0.5], [-1.0, 0.0, 0.0]),
+        ([1.0, 0.0, 0.5], [1.0, 0.0, 0.0]),
+        ([0.0, -1.0, 0.5], [0.0, -1.0, 0.0]),
+        ([0.0, 1.0, 0.5], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, 0.0, 1.0], [0.0, 0.0, 1.0]),
+    ];
+
+    #[test]
+    fn box_inside_every_plane_intersects() {
+        let frustum = unit_frustum();
+        assert!(frustum.intersects_aabb(&cube(Vector3::new(0.0, 0.0, 0.5), 0.2)));
+        for (point, outwards) in FACES {
+            let inside = Vector3::from(point) - Vector3::from(outwards) * 0.3;
+            assert!(frustum.intersects_aabb(&cube(inside, 0.1)), "inside of plane at {point:?}");
+        }
+    }
+
+    #[test]
+    fn box_straddling_a_plane_intersects() {
+        let frustum = unit_frustum();
+        for (point, _) in FACES {
+            assert!(frustum.intersects_aabb(&cube(Vector3::from(point), 0.1)), "across plane at {point:?}");
+        }
+    }
+
+    #[test]
+    fn box_outside_any_plane_is_culled() {
+        let frustum = unit_frustum();
+        for (point, outwards) in FACES {
+            let outside = Vector3::from(point) + Vector3::from(outwards) * 0.3;
+            assert!(!frustum.intersects_aabb(&cube(outside, 0.1)), "outside of plane at {point:?}");
+        }
+    }
+
+    #[test]
+    fn box_around_the_frustum_intersects() {
+        assert!(unit_frustum().intersects_aabb(&cube(Vector3::zeros(), 10.0)));
+    }
+
+    #[test]
+    fn planes_follow_the_view() {
+        // the view moved 5 units to the right, so the frustum did as well
+        let frustum = Frustum::from_view_proj(&Matrix4::new_translation(&Vector3::new(-5.0, 0.0, 0.0)));
+        assert!(frustum.intersects_aabb(&cube(Vector3::new(5.0, 0.0, 0.5), 0.1)));
+        assert!(!frustum.intersects_aabb(&cube(Vector3::new(0.0, 0.0, 0.5), 0.1)));
+    }
+}
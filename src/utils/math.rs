@@ -1,6 +1,15 @@
 use nalgebra::{Matrix3, Matrix4, RealField, Rotation3, SimdRealField, UnitQuaternion, Vector3};
 use num_traits::Float;
 
+// nalgebra projections map depth to -1..1 like OpenGL, wgpu expects 0..1
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
 pub trait ExtraMatrixMath {
     fn decompose(self) -> (Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>);
 }
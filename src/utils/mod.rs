@@ -1 +1,4 @@
-pub mod math;
\ No newline at end of file
+pub mod bounds;
+pub mod math;
+#[cfg(test)]
+pub(crate) mod testing;
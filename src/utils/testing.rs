@@ -0,0 +1,19 @@
+// Setup shared by the unit tests
+
+use crate::components::Component;
+use crate::world::World;
+
+// A component on a new object of the world, which isn't attached to the object. Tests can call
+// into it directly, without the world running its callbacks.
+pub(crate) fn detached_component<C: Component>(world: &mut World, name: &str) -> C {
+    let obj = world.new_object(name);
+    unsafe { C::new(obj) }
+}
+
+// A world with an active camera at the origin, looking down -Z
+pub(crate) fn world_with_camera() -> Box<World> {
+    let mut world = World::new();
+    let camera = world.new_camera();
+    world.add_child(camera);
+    world
+}
@@ -1,22 +1,37 @@
 use std::collections::HashMap;
+use std::error::Error;
 use std::time::{Duration, Instant};
-use log::info;
+use log::{info, warn};
+use nalgebra::Vector3;
 use crate::asset_management::AssetManager;
-use crate::components::{CameraComp, Component};
+use crate::components::{CameraComp, Component, ComponentQuery, ComponentRef};
+use crate::components::registry::{ComponentRegistry, FieldValue};
 use crate::input::input_manager::InputManager;
 use crate::object::{GameObject, GameObjectId};
 use crate::physics::simulator::PhysicsSimulator;
+use crate::prefab::{PrefabId, PrefabManager, PrefabOverrides};
 use crate::renderer::Renderer;
+use crate::renderer::sky::Skybox;
+use crate::scene::{SceneDesc, TransformDesc};
 use crate::transform::Transform;
 
-static mut G_WORLD: *mut World = std::ptr::null_mut();
-
 pub struct World {
-    pub objects: HashMap<GameObjectId, Box<GameObject>>,
-    pub next_object_id: GameObjectId,
+    pub objects: HashMap<usize, Box<GameObject>>,
+    pub next_object_id: usize,
+    free_object_ids: Vec<usize>,
+    object_generations: Vec<u32>,
+    next_creation_order: u64,
     pub children: Vec<GameObjectId>,
+    // The main camera. Every enabled camera is rendered, but level of detail and the shadows of
+    // directional lights follow this one.
     pub active_camera: Option<GameObjectId>,
+    // Light that reaches every surface, no matter which lights are in the world
+    pub ambient_light: Vector3<f32>,
+    // Drawn where no geometry is. Without one, the background stays black.
+    pub skybox: Option<Skybox>,
     pub assets: AssetManager,
+    pub component_registry: ComponentRegistry,
+    pub prefabs: PrefabManager,
     pub physics: PhysicsSimulator,
     pub input: InputManager,
     delta_time: Duration,
@@ -24,46 +39,63 @@ pub struct World {
 }
 
 impl World {
-    pub unsafe fn new() -> Box<World> {
-        let mut world = Box::new(World {
+    // Worlds are always boxed, as every GameObjectId keeps a pointer to the world it belongs to
+    pub fn new() -> Box<World> {
+        Box::new(World {
             objects: HashMap::new(),
-            next_object_id: GameObjectId(0),
+            next_object_id: 0,
+            free_object_ids: vec![],
+            object_generations: vec![],
+            next_creation_order: 0,
             children: vec![],
             active_camera: None,
+            ambient_light: Vector3::new(0.1, 0.1, 0.1),
+            skybox: None,
             assets: AssetManager::new(),
+            component_registry: ComponentRegistry::new(),
+            prefabs: PrefabManager::new(),
             last_frame_time: Instant::now(),
             physics: PhysicsSimulator::default(),
             delta_time: Duration::default(),
             input: InputManager::new(),
-        });
-
-        // create a second mutable reference so G_WORLD can be used in (~un~)safe code
-        G_WORLD = world.as_mut();
-
-        world
+        })
     }
 
-    // TODO: make this an option later when it's too late
-    pub fn instance() -> &'static mut World {
-        unsafe {
-            if G_WORLD.is_null() {
-                panic!("G_WORLD has not been initialized");
-            }
-            &mut *G_WORLD
-        }
+    fn owns(&self, obj: &GameObjectId) -> bool {
+        std::ptr::eq(obj.world(), self)
     }
 
-    pub fn get_object(&self, obj: &GameObjectId) -> Option<&Box<GameObject>> {
-        self.objects.get(obj)
+    pub fn get_object(&self, obj: &GameObjectId) -> Option<&GameObject> {
+        if !self.owns(obj) {
+            return None;
+        }
+        self.objects
+            .get(&obj.index)
+            .filter(|o| o.id.generation == obj.generation)
+            .map(Box::as_ref)
     }
 
-    pub fn get_object_mut(&mut self, obj: &GameObjectId) -> Option<&mut Box<GameObject>> {
-        self.objects.get_mut(obj)
+    pub fn get_object_mut(&mut self, obj: &GameObjectId) -> Option<&mut GameObject> {
+        if !self.owns(obj) {
+            return None;
+        }
+        self.objects
+            .get_mut(&obj.index)
+            .filter(|o| o.id.generation == obj.generation)
+            .map(Box::as_mut)
     }
 
     pub fn new_object(&mut self, name: &str) -> GameObjectId {
-        let id = self.next_object_id;
-        self.next_object_id += 1;
+        let index = match self.free_object_ids.pop() {
+            Some(index) => index,
+            None => {
+                let index = self.next_object_id;
+                self.next_object_id += 1;
+                self.object_generations.push(0);
+                index
+            }
+        };
+        let id = GameObjectId::new(index, self.object_generations[index], self);
 
         let obj = Box::new(GameObject {
             id,
@@ -73,9 +105,11 @@ impl World {
             transform: Transform::new(id),
             drawable: None,
             components: vec![],
+            creation_order: self.next_creation_order,
         });
+        self.next_creation_order += 1;
 
-        self.objects.insert(id, obj);
+        self.objects.insert(id.index, obj);
 
         id
     }
@@ -84,57 +118,203 @@ impl World {
         let mut obj = self.new_object("Camera");
         obj.transform.set_compound_pos_first(true);
 
+        // the camera component makes itself the active camera if there is none yet
         obj.add_component::<CameraComp>();
 
-        if self.active_camera.is_none() {
-            self.active_camera = Some(obj);
-        }
         obj
     }
 
     pub fn add_child(&mut self, mut obj: GameObjectId) {
         self.children.push(obj);
         obj.parent = None;
+        obj.transform.mark_dirty();
+    }
+
+    // Removes the object and all of its children from the world. Components get their on_destroy
+    // callback before anything is removed. Any handle to a destroyed object stays invalid, even
+    // after its index is reused by a new object.
+    pub fn destroy_object(&mut self, obj: GameObjectId) {
+        if !obj.exists() {
+            return;
+        }
+
+        for child in obj.children.clone() {
+            self.destroy_object(child);
+        }
+
+        let components = obj.components.clone();
+        for slot in components {
+            unsafe {
+                slot.destroy(self);
+            }
+        }
+
+        match obj.parent {
+            Some(mut parent) if parent.exists() => parent.children.retain(|child| *child != obj),
+            _ => self.children.retain(|child| *child != obj),
+        }
+
+        if self.active_camera == Some(obj) {
+            self.active_camera = None;
+        }
+
+        self.objects.remove(&obj.index);
+        self.object_generations[obj.index] = obj.generation.wrapping_add(1);
+        self.free_object_ids.push(obj.index);
     }
 
-    unsafe fn execute_component_func(&mut self, func: unsafe fn(&mut dyn Component)) {
-        for (id, object) in &self.objects {
-            // just a big hack
-            // TODO!!!!: FIND OUT WHY IDS GO CRAZY
-            if id >= &self.next_object_id {
-                return;
+    unsafe fn execute_component_func(&mut self, func: unsafe fn(&mut dyn Component, &mut World)) {
+        // collect first, so components are free to add new objects or components while being called
+        let components: Vec<_> = self
+            .objects
+            .values()
+            .flat_map(|object| object.components.iter().cloned())
+            .collect();
+
+        for slot in components {
+            if !slot.is_enabled() {
+                continue;
             }
-            let object_ptr = object;
-            for comp in &object_ptr.components {
-                let comp_ptr = comp.as_ptr();
-                func(&mut **comp_ptr)
+            let comp_ptr = slot.component.as_ptr();
+            // the object could have been destroyed by an earlier component this frame
+            if !(**comp_ptr).get_parent().exists() {
+                continue;
             }
+            func(&mut **comp_ptr, self)
         }
     }
 
     pub fn update(&mut self) {
         self.tick_delta_time();
+        self.run_frame();
+    }
 
+    // Advances the world by exactly `delta` instead of measuring the time since the last frame.
+    // Useful for headless simulations that need to be reproducible.
+    pub fn tick(&mut self, delta: Duration) {
+        self.delta_time = delta;
+        self.last_frame_time = Instant::now();
+        self.run_frame();
+    }
+
+    fn run_frame(&mut self) {
         unsafe {
             self.execute_component_func(Component::update);
             self.execute_component_func(Component::late_update);
-            
-            while self.physics.last_update.elapsed() > self.physics.timestep {
-                self.physics.last_update += self.physics.timestep;
+
+            self.physics.time_accumulator += self.delta_time;
+            while self.physics.time_accumulator >= self.physics.timestep {
+                self.physics.time_accumulator -= self.physics.timestep;
+                self.execute_component_func(Component::fixed_update);
                 self.physics.step();
                 self.execute_component_func(Component::post_update);
             }
-            
+
             self.input.next_frame();
         }
     }
 
-    pub fn find_object_by_name(&self, name: &str) -> Option<GameObjectId> {
-        self.objects
+    // Writes all objects below the world root to a scene file
+    pub fn save_scene(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        SceneDesc::from_world(self).save(path)
+    }
+
+    // Adds all objects of a scene file to the world and returns the new root objects
+    pub fn load_scene(&mut self, path: &str) -> Result<Vec<GameObjectId>, Box<dyn Error>> {
+        SceneDesc::load(path)?.spawn(self)
+    }
+
+    // Creates a copy of the prefab at the world root. The root object is placed at the transform,
+    // its own rotation and scale from the prefab are kept on top. Components are created with the
+    // instance already in place.
+    pub fn instantiate(&mut self, prefab: PrefabId, transform: &TransformDesc) -> Option<GameObjectId> {
+        self.instantiate_with(prefab, transform, &PrefabOverrides::default())
+    }
+
+    pub fn instantiate_with(
+        &mut self,
+        prefab: PrefabId,
+        transform: &TransformDesc,
+        overrides: &PrefabOverrides,
+    ) -> Option<GameObjectId> {
+        // the prefab is cloned, so components are free to register new prefabs while spawning
+        let root = self.prefabs.get_prefab(prefab)?.root.clone();
+        let placed = TransformDesc {
+            position: transform.position,
+            rotation: transform.rotation * root.transform.rotation,
+            scale: transform.scale.component_mul(&root.transform.scale),
+            compound_pos_first: root.transform.compound_pos_first,
+        };
+        let obj = match root.spawn(self, &placed, overrides) {
+            Ok(obj) => obj,
+            Err(e) => {
+                warn!("Couldn't instantiate prefab {prefab}: {e}");
+                return None;
+            }
+        };
+
+        self.add_child(obj);
+        Some(obj)
+    }
+
+    // Reads a field of the object's first component registered under `component`
+    pub fn get_component_field(&self, obj: GameObjectId, component: &str, field: &str) -> Option<FieldValue> {
+        let registration = self.component_registry.get(component)?;
+        let slot = obj
+            .get()?
+            .components
+            .iter()
+            .find(|slot| slot.component_type() == registration.type_id)?;
+        registration.get_field(slot, field)
+    }
+
+    // Changes a field of the object's first component registered under `component`.
+    // Returns false if there is no such component or field, the value has the wrong kind, or the
+    // component is borrowed through a ComponentRef right now.
+    pub fn set_component_field(&mut self, obj: GameObjectId, component: &str, field: &str, value: FieldValue) -> bool {
+        let Some(registration) = self.component_registry.get(component) else {
+            return false;
+        };
+        let Some(object) = obj.get() else {
+            return false;
+        };
+        object
+            .components
             .iter()
-            .find(|(_, o)| o.name == name)
-            .map(|o| o.0)
-            .cloned()
+            .find(|slot| slot.component_type() == registration.type_id)
+            .is_some_and(|slot| registration.set_field(slot, field, value))
+    }
+
+    // Objects ordered by creation, so iterating them is the same on every run
+    fn sorted_objects(&self) -> Vec<&GameObject> {
+        let mut objects: Vec<&GameObject> = self.objects.values().map(|o| o.as_ref()).collect();
+        objects.sort_by_key(|o| o.creation_order);
+        objects
+    }
+
+    // All components of type C in the world, including disabled ones
+    pub fn get_components<C: Component>(&self) -> Vec<ComponentRef<C>> {
+        self.sorted_objects()
+            .into_iter()
+            .flat_map(|o| o.get_components::<C>())
+            .collect()
+    }
+
+    // All objects having every component of Q, e.g. world.query::<(RigidBodyComponent, Collider3D)>().
+    // Only the first component of each type is returned, disabled ones included.
+    pub fn query<Q: ComponentQuery>(&self) -> Vec<(GameObjectId, Q::Item)> {
+        self.sorted_objects()
+            .into_iter()
+            .filter_map(|o| Some((o.id, Q::fetch(o)?)))
+            .collect()
+    }
+
+    // The first object with the name that was created, if several share it
+    pub fn find_object_by_name(&self, name: &str) -> Option<GameObjectId> {
+        self.sorted_objects()
+            .into_iter()
+            .find(|o| o.name == name)
+            .map(|o| o.id)
     }
 
     pub fn print_objects(&self) {
@@ -175,3 +355,114 @@ impl World {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::components::Component;
+    use crate::object::GameObjectId;
+    use crate::world::World;
+
+    thread_local! {
+        // names of the objects whose DestroyLogger was destroyed, and whether they still existed
+        static DESTROYED: RefCell<Vec<(String, bool)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct DestroyLogger {
+        parent: GameObjectId,
+    }
+
+    impl Component for DestroyLogger {
+        unsafe fn new(parent: GameObjectId) -> Self {
+            DestroyLogger { parent }
+        }
+
+        fn on_destroy(&mut self, _world: &mut World) {
+            let entry = match self.parent.get() {
+                Some(obj) => (obj.name.clone(), true),
+                None => (String::new(), false),
+            };
+            DESTROYED.with_borrow_mut(|destroyed| destroyed.push(entry));
+        }
+
+        unsafe fn get_parent(&self) -> GameObjectId {
+            self.parent
+        }
+    }
+
+    #[test]
+    fn destroy_removes_children_recursively() {
+        let mut world = World::new();
+        let mut root = world.new_object("Root");
+        let mut middle = world.new_object("Middle");
+        let leaf = world.new_object("Leaf");
+        let other = world.new_object("Other");
+        world.add_child(root);
+        world.add_child(other);
+        root.add_child(middle);
+        middle.add_child(leaf);
+
+        world.destroy_object(root);
+
+        assert!(!root.exists() && !middle.exists() && !leaf.exists());
+        assert!(other.exists());
+        assert_eq!(world.children, vec![other]);
+        assert_eq!(world.objects.len(), 1);
+    }
+
+    #[test]
+    fn destroyed_id_stays_invalid_after_index_reuse() {
+        let mut world = World::new();
+        let old = world.new_object("Old");
+        world.destroy_object(old);
+        let new = world.new_object("New");
+
+        assert_eq!(new.index, old.index);
+        assert!(world.get_object(&old).is_none());
+        assert_eq!(world.get_object(&new).unwrap().name, "New");
+        // destroying the stale id again doesn't touch the new object
+        world.destroy_object(old);
+        assert!(new.exists());
+    }
+
+    #[test]
+    fn on_destroy_runs_for_children_first_while_objects_exist() {
+        let mut world = World::new();
+        let mut root = world.new_object("Root");
+        let mut child = world.new_object("Child");
+        world.add_child(root);
+        root.add_child(child);
+        root.add_component::<DestroyLogger>();
+        child.add_component::<DestroyLogger>();
+
+        world.destroy_object(root);
+
+        let destroyed = DESTROYED.with_borrow(|destroyed| destroyed.clone());
+        assert_eq!(destroyed, vec![("Child".to_owned(), true), ("Root".to_owned(), true)]);
+    }
+
+    #[test]
+    fn destroying_active_camera_clears_it() {
+        let mut world = World::new();
+        let camera = world.new_camera();
+        world.add_child(camera);
+        assert_eq!(world.active_camera, Some(camera));
+
+        world.destroy_object(camera);
+        assert_eq!(world.active_camera, None);
+    }
+
+    #[test]
+    fn find_object_by_name_returns_first_created() {
+        let mut world = World::new();
+        let filler = world.new_object("Filler");
+        let first = world.new_object("Twin");
+        world.destroy_object(filler);
+        // reuses the index of the destroyed object, which is lower than the first twin's
+        let second = world.new_object("Twin");
+        assert!(second.index < first.index);
+
+        assert_eq!(world.find_object_by_name("Twin"), Some(first));
+    }
+}